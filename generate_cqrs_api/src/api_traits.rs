@@ -0,0 +1,183 @@
+use std::sync::MutexGuard;
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::StateFormat;
+
+/// `PE` is the `ProcessingError`, generated by the macro
+pub trait Lifecycle<PE> {
+    /// due to frb's current capabilities we cannot define function arguments as types.
+    /// for return types it works. Thus, Error is defined this way, while AppConfig is a generic parameter.
+    type Error: AppStatePersistError<PE>;
+    /// loads the app's state, which can be io-heavy
+    /// get the instance with get_singleton(). Create the initial singleton with this function
+    fn initialise_with_app_config<AC: AppConfig + std::fmt::Debug>(
+        app_config: AC,
+    ) -> Result<&'static Self, Self::Error>;
+
+    /// frb doesn't support generics. Thus, we can call this concrete function.
+    fn initialise(app_state_url: Option<String>) -> Result<(), Self::Error>;
+
+    /// get the instance with get_singleton(). Create the initial singleton with Lifecycle::initialise()
+    /// This cannot be called from Flutter, as frb cannot handle references. Thus, it is called internally (by CQRS::process(), Lifecycle::shutdown() and others)
+    fn get_singleton() -> &'static Self;
+    /// persist the app state to the previously stored location
+    /// as we cannot pass references to frb (see 'get_singleton') persist() and shutdown() have to get 'self' by calling get_singleton() on their own.
+    fn persist() -> Result<(), PE>;
+    /// persists this instance's app state. Called by the generated `process_with()`.
+    /// Override it, if you use lifecycles other than the singleton.
+    fn persist_instance(&self) -> Result<(), PE> {
+        Self::persist()
+    }
+    fn shutdown() -> Result<(), PE>;
+    /// tells the shell what happened when loading the app state, e.g. that it was restored from a backup,
+    /// so that it can inform the user. Call it after `initialise()`.
+    fn initialisation_result() -> InitialisationResult {
+        InitialisationResult::Initialised
+    }
+}
+
+/// returned by `Lifecycle::initialisation_result()`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InitialisationResult {
+    /// the persisted app state was loaded - or a new one was created, if none was persisted yet
+    Initialised,
+    /// the newest snapshot of the app state could not be loaded, it was restored from an older one.
+    /// Changes made after that snapshot are lost.
+    RestoredFromBackup {
+        /// where the restored snapshot is stored
+        snapshot: String,
+        /// why the newer snapshots could not be loaded
+        error: String,
+    },
+}
+
+pub trait AppConfig: Default {
+    /// call to overwrite default values.
+    /// Doesn't trigger long initialization operations.
+    fn new(url: Option<String>) -> Self;
+    /// app state storage location
+    fn borrow_app_state_url(&self) -> &str;
+    /// the format the persisters of this crate write the app state in.
+    /// Loading detects the format, so it can be changed without losing the persisted state.
+    fn app_state_format(&self) -> StateFormat {
+        StateFormat::default()
+    }
+    /// how many snapshots of the app state the `BackupAppStatePersister` keeps, including the current one
+    fn app_state_snapshots(&self) -> usize {
+        3
+    }
+    /// the 256 bit key the `EncryptedAppStatePersister` encrypts the app state with (feature `encryption`).
+    /// Load it from a secure storage, like the platform's keychain - never hard-code it.
+    #[cfg(feature = "encryption")]
+    fn app_state_encryption_key(&self) -> Option<[u8; 32]> {
+        None
+    }
+}
+
+/// the app's state is not exposed external - it is guarded behind CQRS functions
+pub trait AppState: Serialize + DeserializeOwned {
+    fn new<AC: AppConfig>(app_config: &AC) -> Self;
+    fn dirty_flag_value(&self) -> bool;
+    fn mark_dirty(&self);
+    fn mark_persisted(&self);
+    /// called by the generated commands, which changed the model
+    fn mark_model_dirty(&self, _model: ModelId) {
+        self.mark_dirty();
+    }
+    /// the models changed since the last `mark_persisted()`. Empty, if not tracked.
+    fn dirty_models(&self) -> Vec<ModelId> {
+        Vec::new()
+    }
+    /// returns the models changed since the last persisting and marks the state persisted - atomically,
+    /// so that changes made meanwhile stay dirty. The generated lifecycle calls it before persisting.
+    fn take_dirty_models(&self) -> Vec<ModelId> {
+        let dirty_models = self.dirty_models();
+        self.mark_persisted();
+        dirty_models
+    }
+    /// held by the generated lifecycle from `take_dirty_models()` until the taken models are persisted,
+    /// so that concurrent persists run one after the other and an older state never overwrites a newer one.
+    /// Return `Some(self.dirty_flag.lock_persisting())`, if your state is persisted concurrently (like with persistence = "immediate").
+    fn lock_persisting(&self) -> Option<MutexGuard<'_, ()>> {
+        None
+    }
+    /// marks the taken models dirty again, after persisting them failed. An empty slice marks the whole state dirty.
+    fn mark_models_dirty(&self, models: &[ModelId]) {
+        if models.is_empty() {
+            self.mark_dirty();
+        }
+        for model in models {
+            self.mark_model_dirty(*model);
+        }
+    }
+    /// serializes only the given model, so that persisters can write models separately (like the `SqliteAppStatePersister`).
+    /// `None`, if the model is unknown or not serializable separately - then the whole state is serialized.
+    fn serialize_model<S: serde::Serializer>(
+        &self,
+        _model: ModelId,
+        _serializer: S,
+    ) -> Option<Result<S::Ok, S::Error>> {
+        None
+    }
+    /// the position of the last command in the `CommandJournal` this state contains (persistence = "journal")
+    fn journal_position(&self) -> u64 {
+        0
+    }
+    /// called by the generated commands after they were appended to the `CommandJournal`
+    fn set_journal_position(&self, _position: u64) {}
+}
+
+/// identifies a model by the name of its field in the `AppState`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ModelId(pub &'static str);
+
+/// `PE` is the `ProcessingError`, generated by the macro
+pub trait AppStatePersistError<PE>: std::error::Error {
+    /// convert to ProcessingError::NotPersisted
+    fn to_processing_error(&self) -> PE;
+    /// true, if no app state has been persisted yet - so a new one is created on initialisation.
+    /// Override this to use the generated `default_lifecycle`.
+    fn is_not_found(&self) -> bool {
+        false
+    }
+}
+
+/// implemented by the generated `ProcessingError`,
+/// so that the persisters of this crate can convert their errors to `ProcessingError::NotPersisted`
+pub trait NotPersistedError {
+    fn not_persisted(error: String, url: String) -> Self;
+}
+
+pub trait AppStatePersister {
+    /// prepares for persisting a new AppState. Not needed if the AppState is loaded!
+    type Error: std::error::Error;
+    fn new<AC: AppConfig>(app_config: &AC) -> Result<Self, Self::Error>
+    where
+        Self: Sized;
+    /// Persists the application state to storage.
+    /// Ensures that the `AppState` is stored in a durable way, regardless of the underlying mechanism.
+    fn persist_app_state<AS: AppState + std::fmt::Debug>(
+        &self,
+        state: &AS,
+    ) -> Result<(), Self::Error>;
+
+    /// Loads the application state.
+    /// Returns a result with the `AppState` if successful or an `InfrastructureError` otherwise.
+    fn load_app_state<AC: AppConfig, AS: AppState>(&self) -> Result<AS, Self::Error>;
+
+    /// Persists only the given (changed) models - an empty slice means all of them.
+    /// Override it, if your storage can write models separately. Persists the whole `AppState` by default.
+    fn persist_models<AS: AppState + std::fmt::Debug>(
+        &self,
+        state: &AS,
+        _models: &[ModelId],
+    ) -> Result<(), Self::Error> {
+        self.persist_app_state(state)
+    }
+
+    /// what happened in `load_app_state()`, see `Lifecycle::initialisation_result()`
+    fn initialisation_result(&self) -> InitialisationResult {
+        InitialisationResult::Initialised
+    }
+}
@@ -0,0 +1,73 @@
+use std::sync::{PoisonError, RwLock};
+
+/// decides, if the current session's principal may call a command or query annotated with `#[cqrs(requires = "role")]` (option `authorization`).
+/// Implementations hold the session themselves, e.g. as `Arc<RwLock<Session>>` updated on login.
+pub trait Authorizer: Send + Sync {
+    /// @param role: the role required by the annotation, like "admin"
+    /// @param command: the called variant, like "MyModelCommand::CleanList"
+    fn is_authorized(&self, role: &str, command: &str) -> bool;
+}
+
+/// holds the lifecycle's `Authorizer`. Until one is set, every call requiring a role is rejected.
+#[derive(Default)]
+pub struct Authorization {
+    authorizer: RwLock<Option<Box<dyn Authorizer>>>,
+}
+
+impl std::fmt::Debug for Authorization {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Authorization")
+            .field(
+                "has_authorizer",
+                &self
+                    .authorizer
+                    .read()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .is_some(),
+            )
+            .finish()
+    }
+}
+
+impl Authorization {
+    pub fn set_authorizer(&self, authorizer: impl Authorizer + 'static) {
+        *self
+            .authorizer
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = Some(Box::new(authorizer));
+    }
+
+    pub fn is_authorized(&self, role: &str, command: &str) -> bool {
+        self.authorizer
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .as_ref()
+            .is_some_and(|authorizer| authorizer.is_authorized(role, command))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Authorization, Authorizer};
+
+    struct AdminOnly;
+
+    impl Authorizer for AdminOnly {
+        fn is_authorized(&self, role: &str, _command: &str) -> bool {
+            role == "admin"
+        }
+    }
+
+    #[test]
+    fn reject_without_authorizer() {
+        let authorization = Authorization::default();
+        assert!(!authorization.is_authorized("admin", "MyModelCommand::CleanList"));
+    }
+    #[test]
+    fn ask_the_authorizer() {
+        let authorization = Authorization::default();
+        authorization.set_authorizer(AdminOnly);
+        assert!(authorization.is_authorized("admin", "MyModelCommand::CleanList"));
+        assert!(!authorization.is_authorized("editor", "MyModelCommand::CleanList"));
+    }
+}
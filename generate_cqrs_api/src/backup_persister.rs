@@ -0,0 +1,297 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{Mutex, PoisonError},
+};
+
+use crate::{
+    deserialize_detecting_format,
+    file_persister::{create_parent_dirs, replace_with_temp_file, write_temp_file},
+    AppConfig, AppState, AppStatePersistError, AppStatePersister, FormatError,
+    InitialisationResult, NotPersistedError, StateFormat,
+};
+
+/// starts the checksum line, like `#crc32:1a2b3c4d`, which is written in front of each snapshot
+const CHECKSUM_PREFIX: &[u8] = b"#crc32:";
+
+/// persists the app state into the file at `AppConfig::borrow_app_state_url()`, like the `FileAppStatePersister`,
+/// but keeps the last `AppConfig::app_state_snapshots()` snapshots: the previous ones are renamed to `<file>.1`, `<file>.2`, ...
+/// Each snapshot starts with its checksum. If the newest one is corrupt (e.g. truncated), loading falls back
+/// to the newest valid snapshot and reports it by `initialisation_result()`.
+#[derive(Debug)]
+pub struct BackupAppStatePersister {
+    path: PathBuf,
+    format: StateFormat,
+    snapshots: usize,
+    initialisation_result: Mutex<InitialisationResult>,
+    /// held while a state is serialized and the snapshots are rotated, so that concurrent persists don't interleave
+    writing: Mutex<()>,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum BackupAppStatePersisterError {
+    #[error("No app state file found in '{0}'")]
+    FileNotFound(PathBuf),
+    #[error("Can't access the app state file '{path}': {error}")]
+    IoError { error: io::Error, path: PathBuf },
+    #[error("Can't (de)serialize the app state in '{path}': {error}")]
+    SerializationError { error: FormatError, path: PathBuf },
+    #[error("The checksum of the app state file '{0}' doesn't match, it is corrupt")]
+    ChecksumMismatch(PathBuf),
+}
+
+impl BackupAppStatePersisterError {
+    fn path(&self) -> &Path {
+        match self {
+            BackupAppStatePersisterError::FileNotFound(path)
+            | BackupAppStatePersisterError::IoError { path, .. }
+            | BackupAppStatePersisterError::SerializationError { path, .. }
+            | BackupAppStatePersisterError::ChecksumMismatch(path) => path,
+        }
+    }
+}
+
+impl<PE: NotPersistedError> AppStatePersistError<PE> for BackupAppStatePersisterError {
+    fn to_processing_error(&self) -> PE {
+        PE::not_persisted(self.to_string(), self.path().to_string_lossy().to_string())
+    }
+    fn is_not_found(&self) -> bool {
+        matches!(self, BackupAppStatePersisterError::FileNotFound(_))
+    }
+}
+
+fn with_checksum(content: &[u8]) -> Vec<u8> {
+    let checksum = format!("{:08x}\n", crc32fast::hash(content));
+    [CHECKSUM_PREFIX, checksum.as_bytes(), content].concat()
+}
+
+/// @returns the content following the checksum line, if it matches
+fn verify_checksum(snapshot: &[u8]) -> Option<&[u8]> {
+    let checked = snapshot.strip_prefix(CHECKSUM_PREFIX)?;
+    let (checksum, content) = (checked.get(..8)?, checked.get(9..)?);
+    let checksum = u32::from_str_radix(std::str::from_utf8(checksum).ok()?, 16).ok()?;
+    (checked[8] == b'\n' && crc32fast::hash(content) == checksum).then_some(content)
+}
+
+impl BackupAppStatePersister {
+    fn io_error(&self, error: io::Error, path: &Path) -> BackupAppStatePersisterError {
+        BackupAppStatePersisterError::IoError {
+            error,
+            path: path.to_owned(),
+        }
+    }
+
+    /// the current app state is snapshot 0, the older ones are numbered
+    fn snapshot_path(&self, index: usize) -> PathBuf {
+        if index == 0 {
+            return self.path.to_owned();
+        }
+        let mut file_name = self.path.file_name().unwrap_or_default().to_owned();
+        file_name.push(format!(".{index}"));
+        self.path.with_file_name(file_name)
+    }
+
+    fn load_snapshot<AS: AppState>(&self, path: &Path) -> Result<AS, BackupAppStatePersisterError> {
+        let snapshot = fs::read(path).map_err(|error| match error.kind() {
+            io::ErrorKind::NotFound => BackupAppStatePersisterError::FileNotFound(path.to_owned()),
+            _ => self.io_error(error, path),
+        })?;
+        let content = verify_checksum(&snapshot)
+            .ok_or_else(|| BackupAppStatePersisterError::ChecksumMismatch(path.to_owned()))?;
+        deserialize_detecting_format(content).map_err(|error| {
+            BackupAppStatePersisterError::SerializationError {
+                error,
+                path: path.to_owned(),
+            }
+        })
+    }
+}
+
+impl AppStatePersister for BackupAppStatePersister {
+    type Error = BackupAppStatePersisterError;
+    fn new<AC: AppConfig>(app_config: &AC) -> Result<Self, Self::Error> {
+        let persister = BackupAppStatePersister {
+            path: PathBuf::from(app_config.borrow_app_state_url()),
+            format: app_config.app_state_format(),
+            snapshots: app_config.app_state_snapshots().max(1),
+            initialisation_result: Mutex::new(InitialisationResult::Initialised),
+            writing: Mutex::new(()),
+        };
+        create_parent_dirs(&persister.path)
+            .map_err(|error| persister.io_error(error, &persister.path))?;
+        Ok(persister)
+    }
+
+    fn persist_app_state<AS: AppState + std::fmt::Debug>(
+        &self,
+        state: &AS,
+    ) -> Result<(), Self::Error> {
+        let _writing = self.writing.lock().unwrap_or_else(PoisonError::into_inner);
+        let serialized = self.format.serialize(state).map_err(|error| {
+            BackupAppStatePersisterError::SerializationError {
+                error,
+                path: self.path.to_owned(),
+            }
+        })?;
+        create_parent_dirs(&self.path).map_err(|error| self.io_error(error, &self.path))?;
+        let temp_path = write_temp_file(&self.path, &with_checksum(&serialized))
+            .map_err(|error| self.io_error(error, &self.path))?;
+        // shift the snapshots, dropping the oldest one
+        for index in (1..self.snapshots).rev() {
+            let older_path = self.snapshot_path(index - 1);
+            match fs::rename(&older_path, self.snapshot_path(index)) {
+                Err(error) if error.kind() != io::ErrorKind::NotFound => {
+                    let _ = fs::remove_file(&temp_path);
+                    return Err(self.io_error(error, &older_path));
+                }
+                _ => {}
+            }
+        }
+        replace_with_temp_file(&temp_path, &self.path)
+            .map_err(|error| self.io_error(error, &self.path))
+    }
+
+    /// loads the newest valid snapshot
+    fn load_app_state<AC: AppConfig, AS: AppState>(&self) -> Result<AS, Self::Error> {
+        let mut errors = Vec::new();
+        for index in 0..self.snapshots {
+            let path = self.snapshot_path(index);
+            match self.load_snapshot::<AS>(&path) {
+                Ok(app_state) => {
+                    if let Some(newest_error) = errors.first() {
+                        *self
+                            .initialisation_result
+                            .lock()
+                            .unwrap_or_else(PoisonError::into_inner) =
+                            InitialisationResult::RestoredFromBackup {
+                                snapshot: path.to_string_lossy().to_string(),
+                                error: BackupAppStatePersisterError::to_string(newest_error),
+                            };
+                    }
+                    return Ok(app_state);
+                }
+                Err(error) => errors.push(error),
+            }
+        }
+        // not found only, if there is no snapshot at all - a corrupt one must not be replaced by a new state
+        Err(errors
+            .into_iter()
+            .find(|error| !matches!(error, BackupAppStatePersisterError::FileNotFound(_)))
+            .unwrap_or_else(|| BackupAppStatePersisterError::FileNotFound(self.path.to_owned())))
+    }
+
+    fn initialisation_result(&self) -> InitialisationResult {
+        self.initialisation_result
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BackupAppStatePersister, BackupAppStatePersisterError};
+    use crate::{
+        test_mocks::{test_app_config, TestAppConfig, TestAppState, TestProcessingError},
+        AppConfig, AppStatePersistError, AppStatePersister, InitialisationResult,
+    };
+
+    fn persist_items(persister: &BackupAppStatePersister, count: usize) {
+        let mut app_state = TestAppState::default();
+        for item in 0..count {
+            app_state.items.push(format!("item {item}"));
+            persister.persist_app_state(&app_state).unwrap();
+        }
+    }
+
+    #[test]
+    fn keep_last_snapshots() {
+        let app_config = test_app_config("backup_rotation", "app_state.json");
+        let persister = BackupAppStatePersister::new(&app_config).unwrap();
+        persist_items(&persister, 5);
+
+        let snapshot_items = (0..3)
+            .map(|index| {
+                persister
+                    .load_snapshot::<TestAppState>(&persister.snapshot_path(index))
+                    .unwrap()
+                    .items
+                    .len()
+            })
+            .collect::<Vec<usize>>();
+        assert_eq!(vec![5, 4, 3], snapshot_items);
+        assert!(!persister.snapshot_path(3).exists());
+        assert!(std::fs::read_to_string(app_config.borrow_app_state_url())
+            .unwrap()
+            .starts_with("#crc32:"));
+    }
+    #[test]
+    fn fall_back_to_newest_valid_snapshot() {
+        let app_config = test_app_config("backup_fallback", "app_state.json");
+        let persister = BackupAppStatePersister::new(&app_config).unwrap();
+        persist_items(&persister, 3);
+        // truncate the newest snapshot
+        let content = std::fs::read(app_config.borrow_app_state_url()).unwrap();
+        std::fs::write(
+            app_config.borrow_app_state_url(),
+            &content[..content.len() - 5],
+        )
+        .unwrap();
+
+        let persister = BackupAppStatePersister::new(&app_config).unwrap();
+        let loaded = persister
+            .load_app_state::<TestAppConfig, TestAppState>()
+            .unwrap();
+        assert_eq!(2, loaded.items.len());
+        assert_eq!(
+            InitialisationResult::RestoredFromBackup {
+                snapshot: persister.snapshot_path(1).to_string_lossy().to_string(),
+                error: BackupAppStatePersisterError::ChecksumMismatch(persister.snapshot_path(0))
+                    .to_string(),
+            },
+            persister.initialisation_result()
+        );
+    }
+    #[test]
+    fn load_valid_snapshot_is_initialised() {
+        let app_config = test_app_config("backup_valid", "app_state.json");
+        let persister = BackupAppStatePersister::new(&app_config).unwrap();
+        persist_items(&persister, 2);
+        let loaded = persister
+            .load_app_state::<TestAppConfig, TestAppState>()
+            .unwrap();
+        assert_eq!(2, loaded.items.len());
+        assert_eq!(
+            InitialisationResult::Initialised,
+            persister.initialisation_result()
+        );
+    }
+    #[test]
+    fn fail_if_no_snapshot_is_valid() {
+        let app_config = test_app_config("backup_all_corrupt", "app_state.json");
+        let persister = BackupAppStatePersister::new(&app_config).unwrap();
+        persist_items(&persister, 1);
+        std::fs::write(app_config.borrow_app_state_url(), "{").unwrap();
+        let error = persister
+            .load_app_state::<TestAppConfig, TestAppState>()
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            BackupAppStatePersisterError::ChecksumMismatch(_)
+        ));
+        assert!(!AppStatePersistError::<TestProcessingError>::is_not_found(
+            &error
+        ));
+    }
+    #[test]
+    fn load_missing_app_state_is_not_found() {
+        let app_config = test_app_config("backup_missing", "app_state.json");
+        let error = BackupAppStatePersister::new(&app_config)
+            .unwrap()
+            .load_app_state::<TestAppConfig, TestAppState>()
+            .unwrap_err();
+        assert!(AppStatePersistError::<TestProcessingError>::is_not_found(
+            &error
+        ));
+    }
+}
@@ -0,0 +1,434 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Mutex, MutexGuard, PoisonError,
+    },
+};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    file_persister::{create_parent_dirs, replace_with_temp_file, write_temp_file},
+    AppConfig, AppStatePersistError, NotPersistedError,
+};
+
+/// an append-only journal of the state changing commands, stored next to the app state in `<app state url>.journal`.
+/// Used by the generated code for persistence = "journal": instead of persisting the whole app state after each command,
+/// the command is appended. A snapshot of the app state is persisted every `snapshot_every` commands,
+/// after which the journal is compacted. On initialisation the commands since the snapshot are replayed.
+///
+/// Each line holds `<position> <crc32> <command as JSON>`. A corrupt or incomplete last line (e.g. of a crash while writing)
+/// is truncated on opening. A corrupt line followed by valid ones fails opening, instead of dropping the valid commands.
+#[derive(Debug)]
+pub struct CommandJournal {
+    path: PathBuf,
+    snapshot_every: u64,
+    replaying: AtomicBool,
+    /// held while a command is processed and journaled, and while a snapshot is persisted
+    commands: Mutex<()>,
+    state: Mutex<JournalState>,
+}
+
+#[derive(Debug)]
+struct JournalState {
+    file: File,
+    /// the position of the last appended (or replayed) command
+    last_position: u64,
+    entries: Vec<(u64, String)>,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum CommandJournalError {
+    #[error("Can't access the command journal '{path}': {error}")]
+    IoError { error: io::Error, path: PathBuf },
+    #[error("Can't serialize the command for the journal '{path}': {error}")]
+    SerializationError {
+        error: serde_json::Error,
+        path: PathBuf,
+    },
+    #[error("The command journal '{path}' is corrupt in line {line}, which is followed by valid commands")]
+    CorruptEntry { line: usize, path: PathBuf },
+    #[error("Can't replay command {position} of the journal '{path}': {error}")]
+    ReplayError {
+        position: u64,
+        error: String,
+        path: PathBuf,
+    },
+}
+
+impl CommandJournalError {
+    fn path(&self) -> &Path {
+        match self {
+            CommandJournalError::IoError { path, .. }
+            | CommandJournalError::SerializationError { path, .. }
+            | CommandJournalError::CorruptEntry { path, .. }
+            | CommandJournalError::ReplayError { path, .. } => path,
+        }
+    }
+}
+
+impl<PE: NotPersistedError> AppStatePersistError<PE> for CommandJournalError {
+    fn to_processing_error(&self) -> PE {
+        PE::not_persisted(self.to_string(), self.path().to_string_lossy().to_string())
+    }
+}
+
+/// the journal's position the app state contains all commands up to, stored in the generated `AppState`
+#[derive(Debug, Default)]
+pub struct JournalPosition(AtomicU64);
+
+impl JournalPosition {
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::SeqCst)
+    }
+    /// positions only increase, even if concurrent commands set them out of order
+    pub fn set(&self, position: u64) {
+        self.0.fetch_max(position, Ordering::SeqCst);
+    }
+}
+
+impl Serialize for JournalPosition {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.get().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for JournalPosition {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(JournalPosition(AtomicU64::new(u64::deserialize(
+            deserializer,
+        )?)))
+    }
+}
+
+fn journal_line(position: u64, entry: &str) -> String {
+    format!(
+        "{position} {:08x} {entry}\n",
+        crc32fast::hash(entry.as_bytes())
+    )
+}
+
+/// @returns (position, entry), if the line is complete and its checksum matches
+fn parse_journal_line(line: &str) -> Option<(u64, String)> {
+    let mut parts = line.strip_suffix('\n')?.splitn(3, ' ');
+    let position = parts.next()?.parse::<u64>().ok()?;
+    let checksum = u32::from_str_radix(parts.next()?, 16).ok()?;
+    let entry = parts.next()?;
+    (crc32fast::hash(entry.as_bytes()) == checksum).then(|| (position, entry.to_string()))
+}
+
+impl CommandJournal {
+    /// opens (or creates) the journal of the app state at `AppConfig::borrow_app_state_url()`,
+    /// truncating a corrupt last line
+    /// @param snapshot_every: after how many commands the app state is persisted and the journal compacted
+    pub fn open<AC: AppConfig>(
+        app_config: &AC,
+        snapshot_every: u64,
+    ) -> Result<Self, CommandJournalError> {
+        let path = PathBuf::from(format!("{}.journal", app_config.borrow_app_state_url()));
+        let io_error = |error| CommandJournalError::IoError {
+            error,
+            path: path.to_owned(),
+        };
+        create_parent_dirs(&path).map_err(io_error)?;
+        let content = match fs::read(&path) {
+            Ok(content) => content,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(error) => return Err(io_error(error)),
+        };
+        let parse_line = |line: &[u8]| std::str::from_utf8(line).ok().and_then(parse_journal_line);
+        let mut entries = Vec::new();
+        let mut valid_length = 0;
+        let mut lines = content.split_inclusive(|byte| *byte == b'\n');
+        for line in lines.by_ref() {
+            let Some((position, entry)) = parse_line(line).filter(|(position, _)| {
+                entries
+                    .last()
+                    .is_none_or(|(last_position, _)| position > last_position)
+            }) else {
+                break;
+            };
+            entries.push((position, entry));
+            valid_length += line.len();
+        }
+        // only an incomplete last line is expected, e.g. of a crash while appending
+        if lines.any(|line| parse_line(line).is_some()) {
+            return Err(CommandJournalError::CorruptEntry {
+                line: entries.len() + 1,
+                path,
+            });
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(io_error)?;
+        if valid_length < content.len() {
+            file.set_len(valid_length as u64)
+                .and_then(|_| file.sync_all())
+                .map_err(io_error)?;
+        }
+        Ok(CommandJournal {
+            snapshot_every: snapshot_every.max(1),
+            replaying: AtomicBool::new(false),
+            commands: Mutex::new(()),
+            state: Mutex::new(JournalState {
+                file,
+                last_position: entries.last().map_or(0, |(position, _)| *position),
+                entries,
+            }),
+            path,
+        })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, JournalState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// held by the generated commands from processing until their position is set in the app state,
+    /// and while the app state's snapshot is persisted - so that a snapshot contains a command together with its position, or neither.
+    /// Release it before persisting a snapshot within a command.
+    pub fn lock_commands(&self) -> MutexGuard<'_, ()> {
+        self.commands.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn io_error(&self, error: io::Error) -> CommandJournalError {
+        CommandJournalError::IoError {
+            error,
+            path: self.path.to_owned(),
+        }
+    }
+
+    /// serializes the command like the generated `AnyCommand`, e.g. `{"MyModel":{"AddItem":"text"}}`
+    pub fn serialize_entry<C: Serialize>(
+        &self,
+        model: &str,
+        command: &C,
+    ) -> Result<String, CommandJournalError> {
+        serde_json::to_string(&BTreeMap::from([(model, command)])).map_err(|error| {
+            CommandJournalError::SerializationError {
+                error,
+                path: self.path.to_owned(),
+            }
+        })
+    }
+
+    /// appends the command durably. While replaying, nothing is appended.
+    /// @returns the command's position in the journal
+    pub fn append(&self, entry: &str) -> Result<u64, CommandJournalError> {
+        let mut state = self.lock();
+        if self.replaying.load(Ordering::SeqCst) {
+            return Ok(state.last_position);
+        }
+        let position = state.last_position + 1;
+        let line = journal_line(position, entry);
+        state
+            .file
+            .write_all(line.as_bytes())
+            .and_then(|_| state.file.sync_data())
+            .map_err(|error| self.io_error(error))?;
+        state.last_position = position;
+        state.entries.push((position, entry.to_string()));
+        Ok(position)
+    }
+
+    /// true, if `snapshot_every` commands were appended since the last compaction
+    pub fn needs_snapshot(&self) -> bool {
+        !self.replaying.load(Ordering::SeqCst)
+            && self.lock().entries.len() as u64 >= self.snapshot_every
+    }
+
+    /// replays the commands after the app state's position. Called once on initialisation, before other commands are processed.
+    /// @param process: processes a command, serialized like the generated `AnyCommand`
+    /// @returns the number of replayed commands
+    pub fn replay(
+        &self,
+        app_state_position: u64,
+        mut process: impl FnMut(&str) -> Result<(), String>,
+    ) -> Result<usize, CommandJournalError> {
+        let entries = {
+            let mut state = self.lock();
+            // positions continue after the snapshot's, even if the journal was compacted
+            state.last_position = state.last_position.max(app_state_position);
+            state
+                .entries
+                .iter()
+                .filter(|(position, _)| *position > app_state_position)
+                .cloned()
+                .collect::<Vec<(u64, String)>>()
+        };
+        self.replaying.store(true, Ordering::SeqCst);
+        let result = entries.iter().try_for_each(|(position, entry)| {
+            // the replayed command's position is returned by `append()`
+            self.lock().last_position = *position;
+            process(entry).map_err(|error| CommandJournalError::ReplayError {
+                position: *position,
+                error,
+                path: self.path.to_owned(),
+            })
+        });
+        self.replaying.store(false, Ordering::SeqCst);
+        {
+            let mut state = self.lock();
+            state.last_position = state
+                .entries
+                .last()
+                .map_or(0, |(position, _)| *position)
+                .max(app_state_position);
+        }
+        result.map(|_| entries.len())
+    }
+
+    /// removes the commands up to the position, which are contained in the persisted app state
+    pub fn compact(&self, snapshot_position: u64) -> Result<(), CommandJournalError> {
+        let mut state = self.lock();
+        if state
+            .entries
+            .first()
+            .is_none_or(|(position, _)| *position > snapshot_position)
+        {
+            return Ok(());
+        }
+        state
+            .entries
+            .retain(|(position, _)| *position > snapshot_position);
+        let content = state
+            .entries
+            .iter()
+            .map(|(position, entry)| journal_line(*position, entry))
+            .collect::<String>();
+        let file = write_temp_file(&self.path, content.as_bytes())
+            .and_then(|temp_path| replace_with_temp_file(&temp_path, &self.path))
+            .and_then(|_| OpenOptions::new().append(true).open(&self.path))
+            .map_err(|error| self.io_error(error))?;
+        state.file = file;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::{CommandJournal, CommandJournalError};
+    use crate::{test_mocks::test_app_config, AppConfig};
+
+    fn journal_path(app_config: &impl AppConfig) -> String {
+        format!("{}.journal", app_config.borrow_app_state_url())
+    }
+
+    fn replayed(journal: &CommandJournal, after: u64) -> Vec<String> {
+        let mut replayed = vec![];
+        journal
+            .replay(after, |entry| {
+                replayed.push(entry.to_string());
+                Ok(())
+            })
+            .unwrap();
+        replayed
+    }
+
+    #[test]
+    fn append_and_replay_after_snapshot() {
+        let app_config = test_app_config("journal_replay", "app_state.json");
+        let journal = CommandJournal::open(&app_config, 100).unwrap();
+        let entry = journal.serialize_entry("MyModel", &"first").unwrap();
+        assert_eq!(r#"{"MyModel":"first"}"#, entry);
+        assert_eq!(1, journal.append(&entry).unwrap());
+        assert_eq!(2, journal.append(r#"{"MyModel":"second"}"#).unwrap());
+
+        let reopened = CommandJournal::open(&app_config, 100).unwrap();
+        assert_eq!(vec![r#"{"MyModel":"second"}"#], replayed(&reopened, 1));
+        assert_eq!(3, reopened.append(r#"{"MyModel":"third"}"#).unwrap());
+    }
+    #[test]
+    fn truncate_corrupt_tail() {
+        let app_config = test_app_config("journal_corrupt_tail", "app_state.json");
+        let journal = CommandJournal::open(&app_config, 100).unwrap();
+        journal.append(r#"{"MyModel":"first"}"#).unwrap();
+        // a crash while appending the second command
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(journal_path(&app_config))
+            .unwrap()
+            .write_all(br#"2 0000 {"MyMo"#)
+            .unwrap();
+
+        let reopened = CommandJournal::open(&app_config, 100).unwrap();
+        assert_eq!(vec![r#"{"MyModel":"first"}"#], replayed(&reopened, 0));
+        assert_eq!(2, reopened.append(r#"{"MyModel":"second"}"#).unwrap());
+        assert_eq!(
+            2,
+            std::fs::read_to_string(journal_path(&app_config))
+                .unwrap()
+                .lines()
+                .count()
+        );
+    }
+    #[test]
+    fn fail_corrupt_line_followed_by_valid_ones() {
+        let app_config = test_app_config("journal_corrupt_middle", "app_state.json");
+        let journal = CommandJournal::open(&app_config, 100).unwrap();
+        journal.append(r#"{"MyModel":"first"}"#).unwrap();
+        journal.append(r#"{"MyModel":"second"}"#).unwrap();
+        journal.append(r#"{"MyModel":"third"}"#).unwrap();
+        // a bit flip in the second command
+        let content = std::fs::read_to_string(journal_path(&app_config)).unwrap();
+        std::fs::write(
+            journal_path(&app_config),
+            content.replace("second", "secund"),
+        )
+        .unwrap();
+
+        let error = CommandJournal::open(&app_config, 100).unwrap_err();
+        assert!(matches!(
+            error,
+            CommandJournalError::CorruptEntry { line: 2, .. }
+        ));
+        // the valid commands are kept
+        assert_eq!(
+            3,
+            std::fs::read_to_string(journal_path(&app_config))
+                .unwrap()
+                .lines()
+                .count()
+        );
+    }
+    #[test]
+    fn compact_after_snapshot() {
+        let app_config = test_app_config("journal_compact", "app_state.json");
+        let journal = CommandJournal::open(&app_config, 2).unwrap();
+        journal.append(r#"{"MyModel":"first"}"#).unwrap();
+        assert!(!journal.needs_snapshot());
+        journal.append(r#"{"MyModel":"second"}"#).unwrap();
+        assert!(journal.needs_snapshot());
+
+        journal.compact(2).unwrap();
+        assert!(!journal.needs_snapshot());
+        assert_eq!(
+            "",
+            std::fs::read_to_string(journal_path(&app_config)).unwrap()
+        );
+        // positions continue after the snapshot's
+        let reopened = CommandJournal::open(&app_config, 2).unwrap();
+        assert!(replayed(&reopened, 2).is_empty());
+        assert_eq!(3, reopened.append(r#"{"MyModel":"third"}"#).unwrap());
+    }
+    #[test]
+    fn append_nothing_while_replaying() {
+        let app_config = test_app_config("journal_replaying", "app_state.json");
+        let journal = CommandJournal::open(&app_config, 100).unwrap();
+        journal.append(r#"{"MyModel":"first"}"#).unwrap();
+        journal
+            .replay(0, |entry| {
+                assert_eq!(Ok(1), journal.append(entry).map_err(|_| ()));
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(2, journal.append(r#"{"MyModel":"second"}"#).unwrap());
+    }
+}
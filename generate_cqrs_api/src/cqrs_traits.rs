@@ -0,0 +1,69 @@
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::Migration;
+
+pub trait CqrsModel: std::marker::Sized + Default + Serialize + DeserializeOwned {
+    /// persisted with the model, if above 1. Set it with `#[cqrs_model(version = 2, migrations(migrate_v1_to_v2))]`
+    const SCHEMA_VERSION: u32 = 1;
+    /// `MIGRATIONS[0]` migrates the persisted model from version 1 to 2, `MIGRATIONS[1]` from 2 to 3 and so on
+    const MIGRATIONS: &'static [Migration] = &[];
+    /// checked by the generated commands after they changed the model, before it is persisted.
+    /// Only called, if implemented in the model's file passed to `generate_api`
+    /// @returns Err(the violated rule), which rolls the command back
+    fn check_invariants(&self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+pub trait CqrsModelLock<CqrsModel>:
+    std::marker::Sized + Clone + Serialize + DeserializeOwned
+{
+    fn for_model(model: CqrsModel) -> Self;
+}
+
+/// gives the generated undo/redo (option `undo`) and invariant checks access to the model inside its lock. `#[cqrs_lock]` implements it.
+pub trait SwappableModelLock<CqrsModel> {
+    fn read_model<R>(&self, read: impl FnOnce(&CqrsModel) -> R) -> R;
+    /// @returns the replaced model
+    fn replace_model(&self, model: CqrsModel) -> CqrsModel;
+    /// held by the generated calls, which read or replace the model around the lock's function (invariant checks and undo),
+    /// so that no other call of the model runs in between. Keep a `CommandsLock` in the lock, shared by its clones like the model.
+    fn lock_commands(&self) -> MutexGuard<'_, ()>;
+}
+
+/// the mutex behind `SwappableModelLock::lock_commands()`. Its clones share the mutex.
+#[derive(Debug, Default, Clone)]
+pub struct CommandsLock(Arc<Mutex<()>>);
+
+impl CommandsLock {
+    pub fn lock(&self) -> MutexGuard<'_, ()> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// implement it for every model lock, to use the generated undo/redo (option `undo`)
+pub trait UndoableModelLock<CqrsModel>: SwappableModelLock<CqrsModel> {
+    /// the model's effect enum, e.g. `MyModelEffect`
+    type Effect;
+    /// the effects the shell needs to re-render the model, after a command was undone or redone
+    fn undo_effects(&self) -> Vec<Self::Effect>;
+}
+
+/// implemented by the generated Query and Command enums
+pub trait Cqrs: std::fmt::Debug {
+    /// the generated `Effect` enum
+    type Effect;
+    /// the generated `ProcessingError` enum
+    type Error;
+    fn process(self) -> Result<Vec<Self::Effect>, Self::Error>;
+}
+
+/// implemented by the generated Command enums with the option `preview`
+pub trait PreviewableCqrs: Cqrs {
+    /// processes the command on a copy of its model, e.g. to show the user what a destructive command would do.
+    /// Neither the app state nor its persistence are touched.
+    /// @returns (if the command would change the state, its effects)
+    fn preview(self) -> Result<(bool, Vec<Self::Effect>), Self::Error>;
+}
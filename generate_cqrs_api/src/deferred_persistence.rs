@@ -0,0 +1,261 @@
+use std::{
+    sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError},
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+type ErrorHandler<E> = Arc<dyn Fn(E) + Send + Sync>;
+type Persist<E> = Box<dyn Fn() -> Result<(), E> + Send + Sync>;
+
+/// persists the app state on a background thread, collecting all changes
+/// made within the debounce window into a single write.
+/// Used by the generated code for persistence = "debounced", as the lifecycle's field `deferred_persistence`.
+pub struct DeferredPersistence<E> {
+    shared: Arc<SharedPersistence<E>>,
+}
+
+/// shared with the background worker
+struct SharedPersistence<E> {
+    debounce: Duration,
+    persist: Persist<E>,
+    state: Mutex<DeferredPersistenceState<E>>,
+    wake_up: Condvar,
+}
+
+struct DeferredPersistenceState<E> {
+    pending: bool,
+    stopped: bool,
+    worker: Option<JoinHandle<()>>,
+    error_handler: Option<ErrorHandler<E>>,
+    last_error: Option<E>,
+}
+
+impl<E> std::fmt::Debug for DeferredPersistence<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DeferredPersistence")
+            .field("debounce", &self.shared.debounce)
+            .field("pending", &self.shared.lock().pending)
+            .finish()
+    }
+}
+
+impl<E> SharedPersistence<E> {
+    fn lock(&self) -> MutexGuard<'_, DeferredPersistenceState<E>> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn run(&self) {
+        let mut state = self.lock();
+        loop {
+            while !state.pending && !state.stopped {
+                state = self
+                    .wake_up
+                    .wait(state)
+                    .unwrap_or_else(PoisonError::into_inner);
+            }
+            // collect all changes made within the debounce window
+            let deadline = Instant::now() + self.debounce;
+            while !state.stopped {
+                let now = Instant::now();
+                if now >= deadline {
+                    break;
+                }
+                state = self
+                    .wake_up
+                    .wait_timeout(state, deadline - now)
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .0;
+            }
+            // pending changes are persisted by flush()
+            if state.stopped {
+                return;
+            }
+            state.pending = false;
+            drop(state);
+            let result = (self.persist)();
+            state = self.lock();
+            if let Err(error) = result {
+                match state.error_handler.clone() {
+                    Some(error_handler) => {
+                        drop(state);
+                        error_handler(error);
+                        state = self.lock();
+                    }
+                    None => state.last_error = Some(error),
+                }
+            }
+        }
+    }
+}
+
+impl<E: Send + 'static> DeferredPersistence<E> {
+    /// @param persist: persists the app state, like the `persist_instance()` of a lifecycle it holds weakly.
+    /// It runs on the background worker, which must not keep the lifecycle alive.
+    pub fn new(
+        debounce: Duration,
+        persist: impl Fn() -> Result<(), E> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            shared: Arc::new(SharedPersistence {
+                debounce,
+                persist: Box::new(persist),
+                state: Mutex::new(DeferredPersistenceState {
+                    pending: false,
+                    stopped: false,
+                    worker: None,
+                    error_handler: None,
+                    last_error: None,
+                }),
+                wake_up: Condvar::new(),
+            }),
+        }
+    }
+
+    /// errors of persisting in the background can't be returned by `Cqrs::process()`.
+    /// They are passed to this handler - or, if none is registered, returned by `flush()`.
+    pub fn set_error_handler(&self, handler: impl Fn(E) + Send + Sync + 'static) {
+        self.shared.lock().error_handler = Some(Arc::new(handler));
+    }
+
+    /// requests persisting the app state, starting the background worker on first use
+    pub fn schedule(&self) {
+        let mut state = self.shared.lock();
+        state.pending = true;
+        if state.worker.is_none() && !state.stopped {
+            let shared = Arc::clone(&self.shared);
+            state.worker = Some(std::thread::spawn(move || shared.run()));
+        }
+        self.shared.wake_up.notify_all();
+    }
+
+    /// stops the background worker and persists pending changes synchronously.
+    /// Returns the last error of persisting in the background, if no handler consumed it.
+    pub fn flush(&self) -> Result<(), E> {
+        let shared = &self.shared;
+        let worker = {
+            let mut state = shared.lock();
+            state.stopped = true;
+            shared.wake_up.notify_all();
+            state.worker.take()
+        };
+        if let Some(worker) = worker {
+            // a panicking worker left the state pending, which is persisted below
+            let _ = worker.join();
+        }
+        let (pending, last_error) = {
+            let mut state = shared.lock();
+            // changes after the flush start a new worker
+            state.stopped = false;
+            (std::mem::take(&mut state.pending), state.last_error.take())
+        };
+        if pending {
+            (shared.persist)()?;
+        }
+        match last_error {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<E> Drop for DeferredPersistence<E> {
+    /// stops the background worker without persisting - call `flush()` before dropping a lifecycle with pending changes.
+    /// It isn't joined, as the worker may drop the lifecycle itself.
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.stopped = true;
+        state.worker.take();
+        self.shared.wake_up.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            mpsc, Arc,
+        },
+        time::{Duration, Instant},
+    };
+
+    use super::DeferredPersistence;
+
+    /// long enough for the assertions right after scheduling to run within it
+    const LONG_DEBOUNCE: Duration = Duration::from_secs(2);
+
+    /// polls the condition until it holds, failing after a deadline way beyond the debounce
+    fn wait_until(condition: impl Fn() -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !condition() {
+            assert!(
+                Instant::now() < deadline,
+                "timed out waiting for the worker"
+            );
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    /// reports being dropped, along with the persist fn capturing it
+    struct DropSignal(mpsc::Sender<()>);
+
+    impl Drop for DropSignal {
+        fn drop(&mut self) {
+            let _ = self.0.send(());
+        }
+    }
+
+    #[test]
+    fn schedule_collects_changes_and_flush_persists_pending() {
+        let persist_calls = Arc::new(AtomicUsize::new(0));
+        let counted_calls = Arc::clone(&persist_calls);
+        let deferred_persistence = DeferredPersistence::<String>::new(LONG_DEBOUNCE, move || {
+            counted_calls.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        });
+        for _ in 0..5 {
+            deferred_persistence.schedule();
+        }
+        assert_eq!(0, persist_calls.load(Ordering::SeqCst));
+        wait_until(|| persist_calls.load(Ordering::SeqCst) > 0);
+        assert_eq!(1, persist_calls.load(Ordering::SeqCst));
+
+        deferred_persistence.schedule();
+        deferred_persistence.flush().unwrap();
+        assert_eq!(2, persist_calls.load(Ordering::SeqCst));
+        // nothing pending
+        deferred_persistence.flush().unwrap();
+        assert_eq!(2, persist_calls.load(Ordering::SeqCst));
+    }
+    #[test]
+    fn flush_returns_background_error() {
+        let persist_calls = Arc::new(AtomicUsize::new(0));
+        let counted_calls = Arc::clone(&persist_calls);
+        let deferred_persistence = DeferredPersistence::new(Duration::from_millis(10), move || {
+            counted_calls.fetch_add(1, Ordering::SeqCst);
+            Err("disk full".to_string())
+        });
+        deferred_persistence.schedule();
+        wait_until(|| persist_calls.load(Ordering::SeqCst) > 0);
+        // joins the worker, which keeps the error
+        assert_eq!(Err("disk full".to_string()), deferred_persistence.flush());
+        assert_eq!(1, persist_calls.load(Ordering::SeqCst));
+    }
+    #[test]
+    fn drop_stops_the_worker() {
+        let persist_calls = Arc::new(AtomicUsize::new(0));
+        let counted_calls = Arc::clone(&persist_calls);
+        let (dropped_sender, dropped) = mpsc::channel();
+        let drop_signal = DropSignal(dropped_sender);
+        let deferred_persistence = DeferredPersistence::<String>::new(LONG_DEBOUNCE, move || {
+            let _ = &drop_signal;
+            counted_calls.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        });
+        deferred_persistence.schedule();
+        drop(deferred_persistence);
+        // the persist fn is dropped, once the stopped worker let go of it
+        dropped.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!(0, persist_calls.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,104 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Mutex, MutexGuard, PoisonError,
+};
+
+use crate::ModelId;
+
+/// marks an `AppState` - and which of its models - as changed since it was persisted the last time.
+/// Skip it when serializing, like `#[serde(skip)] dirty_flag: DirtyFlag`.
+#[derive(Debug, Default)]
+pub struct DirtyFlag {
+    dirty: AtomicBool,
+    dirty_models: Mutex<Vec<ModelId>>,
+    persisting: Mutex<()>,
+}
+
+impl DirtyFlag {
+    pub fn is_dirty(&self) -> bool {
+        self.dirty.load(Ordering::SeqCst)
+    }
+    pub fn mark_dirty(&self) {
+        self.dirty.store(true, Ordering::SeqCst);
+    }
+    pub fn mark_model_dirty(&self, model: ModelId) {
+        let mut dirty_models = self
+            .dirty_models
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if !dirty_models.contains(&model) {
+            dirty_models.push(model);
+        }
+        self.mark_dirty();
+    }
+    pub fn dirty_models(&self) -> Vec<ModelId> {
+        self.dirty_models
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+    /// returns the dirty models and marks them persisted in one step,
+    /// so that models marked dirty meanwhile aren't lost. Re-mark them, if persisting fails.
+    pub fn take_dirty_models(&self) -> Vec<ModelId> {
+        let mut dirty_models = self
+            .dirty_models
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        self.dirty.store(false, Ordering::SeqCst);
+        std::mem::take(&mut *dirty_models)
+    }
+    /// held while the taken models are persisted, so that the state's persists run one after the other
+    pub fn lock_persisting(&self) -> MutexGuard<'_, ()> {
+        self.persisting
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+    pub fn mark_persisted(&self) {
+        self.dirty_models
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+        self.dirty.store(false, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DirtyFlag;
+    use crate::ModelId;
+
+    #[test]
+    fn mark_dirty_and_persisted() {
+        let dirty_flag = DirtyFlag::default();
+        assert!(!dirty_flag.is_dirty());
+        dirty_flag.mark_dirty();
+        assert!(dirty_flag.is_dirty());
+        dirty_flag.mark_persisted();
+        assert!(!dirty_flag.is_dirty());
+    }
+    #[test]
+    fn mark_models_dirty() {
+        let dirty_flag = DirtyFlag::default();
+        dirty_flag.mark_model_dirty(ModelId("todos"));
+        dirty_flag.mark_model_dirty(ModelId("categories"));
+        dirty_flag.mark_model_dirty(ModelId("todos"));
+        assert!(dirty_flag.is_dirty());
+        assert_eq!(
+            vec![ModelId("todos"), ModelId("categories")],
+            dirty_flag.dirty_models()
+        );
+        dirty_flag.mark_persisted();
+        assert!(dirty_flag.dirty_models().is_empty());
+    }
+    #[test]
+    fn take_dirty_models() {
+        let dirty_flag = DirtyFlag::default();
+        dirty_flag.mark_model_dirty(ModelId("todos"));
+        assert_eq!(vec![ModelId("todos")], dirty_flag.take_dirty_models());
+        assert!(!dirty_flag.is_dirty());
+        // marked dirty while persisting
+        dirty_flag.mark_model_dirty(ModelId("categories"));
+        assert_eq!(vec![ModelId("categories")], dirty_flag.take_dirty_models());
+        assert!(dirty_flag.take_dirty_models().is_empty());
+    }
+}
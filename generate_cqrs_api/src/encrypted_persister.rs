@@ -0,0 +1,271 @@
+use chacha20poly1305::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    deserialize_detecting_format, AppConfig, AppState, AppStatePersistError, AppStatePersister,
+    FormatError, InitialisationResult, NotPersistedError, StateFormat,
+};
+
+/// the length of the nonce, which is stored in front of the encrypted state
+const NONCE_LENGTH: usize = 12;
+
+/// encrypts the app state with ChaCha20-Poly1305 and the key of `AppConfig::app_state_encryption_key()`,
+/// before it is persisted by the wrapped persister, like `EncryptedAppStatePersister<FileAppStatePersister>`.
+/// The authentication tag is verified on loading, so that a changed or corrupted state is detected.
+pub struct EncryptedAppStatePersister<P: AppStatePersister> {
+    persister: P,
+    cipher: ChaCha20Poly1305,
+    format: StateFormat,
+    url: String,
+}
+
+impl<P: AppStatePersister + std::fmt::Debug> std::fmt::Debug for EncryptedAppStatePersister<P> {
+    // leaves out the cipher, not to leak the key
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EncryptedAppStatePersister")
+            .field("persister", &self.persister)
+            .field("format", &self.format)
+            .field("url", &self.url)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum EncryptedAppStatePersisterError<E: std::error::Error> {
+    #[error(transparent)]
+    PersisterError(E),
+    #[error("No key to encrypt the app state in '{0}', return it by AppConfig::app_state_encryption_key()")]
+    MissingKey(String),
+    #[error("Can't decrypt the app state in '{0}', the key is wrong or the state was altered")]
+    DecryptionFailed(String),
+    #[error("Can't (de)serialize the app state in '{url}': {error}")]
+    SerializationError { error: FormatError, url: String },
+}
+
+impl<PE: NotPersistedError, E: AppStatePersistError<PE>> AppStatePersistError<PE>
+    for EncryptedAppStatePersisterError<E>
+{
+    fn to_processing_error(&self) -> PE {
+        match self {
+            EncryptedAppStatePersisterError::PersisterError(error) => error.to_processing_error(),
+            EncryptedAppStatePersisterError::MissingKey(url)
+            | EncryptedAppStatePersisterError::DecryptionFailed(url)
+            | EncryptedAppStatePersisterError::SerializationError { url, .. } => {
+                PE::not_persisted(self.to_string(), url.to_owned())
+            }
+        }
+    }
+    fn is_not_found(&self) -> bool {
+        match self {
+            EncryptedAppStatePersisterError::PersisterError(error) => error.is_not_found(),
+            _ => false,
+        }
+    }
+}
+
+/// what the wrapped persister stores: the nonce followed by the encrypted state, hex encoded
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct EncryptedAppState {
+    encrypted: String,
+}
+
+impl AppState for EncryptedAppState {
+    fn new<AC: AppConfig>(_app_config: &AC) -> Self {
+        Self::default()
+    }
+    // dirty tracking is done by the app state, which is encrypted
+    fn dirty_flag_value(&self) -> bool {
+        true
+    }
+    fn mark_dirty(&self) {}
+    fn mark_persisted(&self) {}
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(hex.get(index..index + 2)?, 16).ok())
+        .collect()
+}
+
+impl<P: AppStatePersister> EncryptedAppStatePersister<P> {
+    fn serialization_error(&self, error: FormatError) -> EncryptedAppStatePersisterError<P::Error> {
+        EncryptedAppStatePersisterError::SerializationError {
+            error,
+            url: self.url.to_owned(),
+        }
+    }
+}
+
+impl<P: AppStatePersister> AppStatePersister for EncryptedAppStatePersister<P> {
+    type Error = EncryptedAppStatePersisterError<P::Error>;
+    fn new<AC: AppConfig>(app_config: &AC) -> Result<Self, Self::Error> {
+        let url = app_config.borrow_app_state_url().to_string();
+        let key = app_config
+            .app_state_encryption_key()
+            .ok_or_else(|| EncryptedAppStatePersisterError::MissingKey(url.to_owned()))?;
+        Ok(EncryptedAppStatePersister {
+            persister: P::new(app_config)
+                .map_err(EncryptedAppStatePersisterError::PersisterError)?,
+            cipher: ChaCha20Poly1305::new(Key::from_slice(&key)),
+            format: app_config.app_state_format(),
+            url,
+        })
+    }
+
+    fn persist_app_state<AS: AppState + std::fmt::Debug>(
+        &self,
+        state: &AS,
+    ) -> Result<(), Self::Error> {
+        let serialized = self
+            .format
+            .serialize(state)
+            .map_err(|error| self.serialization_error(error))?;
+        // a nonce must never be reused with the same key
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let encrypted = self
+            .cipher
+            .encrypt(&nonce, serialized.as_slice())
+            .map_err(|error| self.serialization_error(error.to_string().into()))?;
+        self.persister
+            .persist_app_state(&EncryptedAppState {
+                encrypted: to_hex(&[nonce.as_slice(), &encrypted].concat()),
+            })
+            .map_err(EncryptedAppStatePersisterError::PersisterError)
+    }
+
+    fn load_app_state<AC: AppConfig, AS: AppState>(&self) -> Result<AS, Self::Error> {
+        let encrypted_app_state = self
+            .persister
+            .load_app_state::<AC, EncryptedAppState>()
+            .map_err(EncryptedAppStatePersisterError::PersisterError)?;
+        let decryption_failed =
+            || EncryptedAppStatePersisterError::DecryptionFailed(self.url.to_owned());
+        let encrypted = from_hex(&encrypted_app_state.encrypted)
+            .filter(|encrypted| encrypted.len() >= NONCE_LENGTH)
+            .ok_or_else(decryption_failed)?;
+        let (nonce, encrypted) = encrypted.split_at(NONCE_LENGTH);
+        let decrypted = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), encrypted)
+            .map_err(|_| decryption_failed())?;
+        deserialize_detecting_format(&decrypted).map_err(|error| self.serialization_error(error))
+    }
+
+    fn initialisation_result(&self) -> InitialisationResult {
+        self.persister.initialisation_result()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EncryptedAppStatePersister, EncryptedAppStatePersisterError};
+    use crate::{
+        test_mocks::{test_app_config, TestAppConfig, TestAppState, TestProcessingError},
+        AppConfig, AppStatePersistError, AppStatePersister, FileAppStatePersister,
+    };
+
+    fn encrypted_test_app_config(test_name: &str) -> TestAppConfig {
+        let mut app_config = test_app_config(test_name, "app_state.encrypted");
+        app_config.encryption_key = Some([7; 32]);
+        app_config
+    }
+
+    #[test]
+    fn persist_and_load_encrypted_app_state() {
+        let app_config = encrypted_test_app_config("encrypted_persist_and_load");
+        let persister =
+            EncryptedAppStatePersister::<FileAppStatePersister>::new(&app_config).unwrap();
+        let app_state = TestAppState {
+            items: vec!["secret item".to_string()],
+            ..Default::default()
+        };
+        persister.persist_app_state(&app_state).unwrap();
+        assert!(!std::fs::read_to_string(app_config.borrow_app_state_url())
+            .unwrap()
+            .contains("secret item"));
+
+        let loaded = persister
+            .load_app_state::<TestAppConfig, TestAppState>()
+            .unwrap();
+        assert_eq!(vec!["secret item".to_string()], loaded.items);
+    }
+    #[test]
+    fn fail_decrypting_with_wrong_key() {
+        let mut app_config = encrypted_test_app_config("encrypted_wrong_key");
+        EncryptedAppStatePersister::<FileAppStatePersister>::new(&app_config)
+            .unwrap()
+            .persist_app_state(&TestAppState::default())
+            .unwrap();
+
+        app_config.encryption_key = Some([8; 32]);
+        let error = EncryptedAppStatePersister::<FileAppStatePersister>::new(&app_config)
+            .unwrap()
+            .load_app_state::<TestAppConfig, TestAppState>()
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            EncryptedAppStatePersisterError::DecryptionFailed(_)
+        ));
+        assert!(!AppStatePersistError::<TestProcessingError>::is_not_found(
+            &error
+        ));
+    }
+    #[test]
+    fn fail_decrypting_altered_app_state() {
+        let app_config = encrypted_test_app_config("encrypted_altered");
+        let persister =
+            EncryptedAppStatePersister::<FileAppStatePersister>::new(&app_config).unwrap();
+        persister
+            .persist_app_state(&TestAppState::default())
+            .unwrap();
+        let content = std::fs::read_to_string(app_config.borrow_app_state_url()).unwrap();
+        // flip the last hex digit of the encrypted state
+        let altered_end = if content.ends_with("0\"}") {
+            "1\"}"
+        } else {
+            "0\"}"
+        };
+        std::fs::write(
+            app_config.borrow_app_state_url(),
+            format!("{}{altered_end}", &content[..content.len() - 3]),
+        )
+        .unwrap();
+        assert!(matches!(
+            persister.load_app_state::<TestAppConfig, TestAppState>(),
+            Err(EncryptedAppStatePersisterError::DecryptionFailed(_))
+        ));
+    }
+    #[test]
+    fn missing_app_state_is_not_found() {
+        let error = EncryptedAppStatePersister::<FileAppStatePersister>::new(
+            &encrypted_test_app_config("encrypted_missing"),
+        )
+        .unwrap()
+        .load_app_state::<TestAppConfig, TestAppState>()
+        .unwrap_err();
+        assert!(AppStatePersistError::<TestProcessingError>::is_not_found(
+            &error
+        ));
+    }
+    #[test]
+    fn fail_without_key() {
+        assert!(matches!(
+            EncryptedAppStatePersister::<FileAppStatePersister>::new(&test_app_config(
+                "encrypted_no_key",
+                "app_state.encrypted"
+            )),
+            Err(EncryptedAppStatePersisterError::MissingKey(_))
+        ));
+    }
+}
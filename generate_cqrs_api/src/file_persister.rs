@@ -0,0 +1,291 @@
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, PoisonError,
+    },
+};
+
+use crate::{
+    deserialize_detecting_format, AppConfig, AppState, AppStatePersistError, AppStatePersister,
+    FormatError, NotPersistedError, StateFormat,
+};
+
+/// persists the app state into the file at `AppConfig::borrow_app_state_url()`,
+/// in the format of `AppConfig::app_state_format()` (JSON by default).
+/// Writes to a temp file first, which is synced and renamed over the previous state,
+/// so that a crash never leaves a half-written state behind.
+#[derive(Debug)]
+pub struct FileAppStatePersister {
+    path: PathBuf,
+    format: StateFormat,
+    /// held while a state is serialized, written and renamed, so that concurrent persists don't interleave
+    writing: Mutex<()>,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum FileAppStatePersisterError {
+    #[error("No app state file found in '{0}'")]
+    FileNotFound(PathBuf),
+    #[error("Can't access the app state file '{path}': {error}")]
+    IoError { error: io::Error, path: PathBuf },
+    #[error("Can't (de)serialize the app state in '{path}': {error}")]
+    SerializationError { error: FormatError, path: PathBuf },
+}
+
+impl FileAppStatePersisterError {
+    fn path(&self) -> &Path {
+        match self {
+            FileAppStatePersisterError::FileNotFound(path)
+            | FileAppStatePersisterError::IoError { path, .. }
+            | FileAppStatePersisterError::SerializationError { path, .. } => path,
+        }
+    }
+}
+
+impl<PE: NotPersistedError> AppStatePersistError<PE> for FileAppStatePersisterError {
+    fn to_processing_error(&self) -> PE {
+        PE::not_persisted(self.to_string(), self.path().to_string_lossy().to_string())
+    }
+    fn is_not_found(&self) -> bool {
+        matches!(self, FileAppStatePersisterError::FileNotFound(_))
+    }
+}
+
+impl FileAppStatePersister {
+    fn io_error(&self, error: io::Error) -> FileAppStatePersisterError {
+        FileAppStatePersisterError::IoError {
+            error,
+            path: self.path.to_owned(),
+        }
+    }
+
+    fn create_parent_dirs(&self) -> Result<(), FileAppStatePersisterError> {
+        create_parent_dirs(&self.path).map_err(|error| self.io_error(error))
+    }
+}
+
+pub(crate) fn create_parent_dirs(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => fs::create_dir_all(parent),
+        _ => Ok(()),
+    }
+}
+
+/// numbers the temp files of this process
+static TEMP_FILE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// the temp file is placed next to the written file, as renaming only is atomic on the same file system.
+/// Its name is unique, like `app_state.json.1234.5.tmp`, so that concurrent writes never share a temp file.
+pub(crate) fn temp_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_owned();
+    file_name.push(format!(
+        ".{}.{}.tmp",
+        std::process::id(),
+        TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    path.with_file_name(file_name)
+}
+
+/// writes the content to a new temp file, which is synced, or removed if writing fails
+pub(crate) fn write_temp_file(path: &Path, content: &[u8]) -> io::Result<PathBuf> {
+    let temp_path = temp_path(path);
+    let mut temp_file = File::create_new(&temp_path)?;
+    temp_file
+        .write_all(content)
+        .and_then(|_| temp_file.sync_all())
+        .inspect_err(|_| {
+            let _ = fs::remove_file(&temp_path);
+        })?;
+    Ok(temp_path)
+}
+
+/// renames the synced temp file over the file at `path`
+pub(crate) fn replace_with_temp_file(temp_path: &Path, path: &Path) -> io::Result<()> {
+    fs::rename(temp_path, path).inspect_err(|_| {
+        let _ = fs::remove_file(temp_path);
+    })?;
+    // persist the rename itself
+    #[cfg(unix)]
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        File::open(parent)?.sync_all()?;
+    }
+    Ok(())
+}
+
+impl AppStatePersister for FileAppStatePersister {
+    type Error = FileAppStatePersisterError;
+    fn new<AC: AppConfig>(app_config: &AC) -> Result<Self, Self::Error> {
+        let persister = FileAppStatePersister {
+            path: PathBuf::from(app_config.borrow_app_state_url()),
+            format: app_config.app_state_format(),
+            writing: Mutex::new(()),
+        };
+        persister.create_parent_dirs()?;
+        Ok(persister)
+    }
+
+    fn persist_app_state<AS: AppState + std::fmt::Debug>(
+        &self,
+        state: &AS,
+    ) -> Result<(), Self::Error> {
+        let _writing = self.writing.lock().unwrap_or_else(PoisonError::into_inner);
+        let serialized = self.format.serialize(state).map_err(|error| {
+            FileAppStatePersisterError::SerializationError {
+                error,
+                path: self.path.to_owned(),
+            }
+        })?;
+        self.create_parent_dirs()?;
+        write_temp_file(&self.path, &serialized)
+            .and_then(|temp_path| replace_with_temp_file(&temp_path, &self.path))
+            .map_err(|error| self.io_error(error))
+    }
+
+    fn load_app_state<AC: AppConfig, AS: AppState>(&self) -> Result<AS, Self::Error> {
+        let content = fs::read(&self.path).map_err(|error| match error.kind() {
+            io::ErrorKind::NotFound => {
+                FileAppStatePersisterError::FileNotFound(self.path.to_owned())
+            }
+            _ => self.io_error(error),
+        })?;
+        deserialize_detecting_format(&content).map_err(|error| {
+            FileAppStatePersisterError::SerializationError {
+                error,
+                path: self.path.to_owned(),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, sync::Arc};
+
+    use super::{FileAppStatePersister, FileAppStatePersisterError};
+    use crate::{
+        test_mocks::{test_app_config, TestAppConfig, TestAppState, TestProcessingError},
+        AppConfig, AppStatePersistError, AppStatePersister, StateFormat,
+    };
+
+    #[test]
+    fn persist_and_load_app_state() {
+        let app_config = test_app_config("persist_and_load", "app_state.json");
+        let persister = FileAppStatePersister::new(&app_config).unwrap();
+        let app_state = TestAppState {
+            items: vec!["first item".to_string()],
+            ..Default::default()
+        };
+        persister.persist_app_state(&app_state).unwrap();
+        // overwrite the previous state
+        persister.persist_app_state(&app_state).unwrap();
+
+        let loaded = persister
+            .load_app_state::<TestAppConfig, TestAppState>()
+            .unwrap();
+        assert_eq!(vec!["first item".to_string()], loaded.items);
+        assert_eq!(
+            "#cqrs:json\n{\"items\":[\"first item\"]}",
+            std::fs::read_to_string(app_config.borrow_app_state_url()).unwrap()
+        );
+        assert_no_temp_files(&persister);
+    }
+    fn assert_no_temp_files(persister: &FileAppStatePersister) {
+        let temp_files = std::fs::read_dir(persister.path.parent().unwrap())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .filter(|file_name| file_name.ends_with(".tmp"))
+            .collect::<Vec<String>>();
+        assert!(temp_files.is_empty(), "{temp_files:?}");
+    }
+    #[test]
+    fn persist_concurrently() {
+        let app_config = test_app_config("persist_concurrently", "app_state.json");
+        let persister = Arc::new(FileAppStatePersister::new(&app_config).unwrap());
+        let threads = (0..8)
+            .map(|thread| {
+                let persister = persister.clone();
+                std::thread::spawn(move || {
+                    for item in 0..10 {
+                        let app_state = TestAppState {
+                            items: vec![format!("item {thread}/{item}")],
+                            ..Default::default()
+                        };
+                        persister.persist_app_state(&app_state).unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        let loaded = persister
+            .load_app_state::<TestAppConfig, TestAppState>()
+            .unwrap();
+        assert_eq!(1, loaded.items.len());
+        assert_no_temp_files(&persister);
+    }
+    #[test]
+    fn switch_format_keeps_app_state() {
+        let mut app_config = test_app_config("switch_format", "app_state.json");
+        let app_state = TestAppState {
+            items: vec!["first item".to_string()],
+            ..Default::default()
+        };
+        FileAppStatePersister::new(&app_config)
+            .unwrap()
+            .persist_app_state(&app_state)
+            .unwrap();
+
+        app_config.format = StateFormat::PrettyJson;
+        let persister = FileAppStatePersister::new(&app_config).unwrap();
+        let loaded = persister
+            .load_app_state::<TestAppConfig, TestAppState>()
+            .unwrap();
+        assert_eq!(vec!["first item".to_string()], loaded.items);
+        persister.persist_app_state(&loaded).unwrap();
+        assert!(std::fs::read_to_string(app_config.borrow_app_state_url())
+            .unwrap()
+            .starts_with("#cqrs:json-pretty\n{\n"));
+    }
+    #[test]
+    fn load_missing_app_state_is_not_found() {
+        let app_config = test_app_config("missing", "app_state.json");
+        let persister = FileAppStatePersister::new(&app_config).unwrap();
+        let error = persister
+            .load_app_state::<TestAppConfig, TestAppState>()
+            .unwrap_err();
+        assert!(matches!(error, FileAppStatePersisterError::FileNotFound(_)));
+        assert!(AppStatePersistError::<TestProcessingError>::is_not_found(
+            &error
+        ));
+        assert_eq!(
+            TestProcessingError(
+                error.to_string(),
+                app_config.borrow_app_state_url().to_string()
+            ),
+            AppStatePersistError::<TestProcessingError>::to_processing_error(&error)
+        );
+    }
+    #[test]
+    fn load_corrupt_app_state_fails() {
+        let app_config = test_app_config("corrupt", "app_state.json");
+        let persister = FileAppStatePersister::new(&app_config).unwrap();
+        std::fs::write(PathBuf::from(app_config.borrow_app_state_url()), "{").unwrap();
+        let error = persister
+            .load_app_state::<TestAppConfig, TestAppState>()
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            FileAppStatePersisterError::SerializationError { .. }
+        ));
+        assert!(!AppStatePersistError::<TestProcessingError>::is_not_found(
+            &error
+        ));
+    }
+}
@@ -0,0 +1,272 @@
+use std::sync::{Arc, PoisonError, RwLock};
+
+use serde::Serialize;
+use serde_json::Value;
+
+/// a CQRS call, as passed to the interceptors
+#[derive(Debug, Clone, PartialEq)]
+pub struct InterceptedCall {
+    /// the model's name, like "MyModel"
+    pub model: &'static str,
+    /// the command's or query's variant, like "AddItem"
+    pub variant: &'static str,
+    /// the variant's serialized arguments, `Value::Null` if it has none
+    pub arguments: Value,
+}
+
+impl InterceptedCall {
+    /// @param cqrs: the serializable command or query, like `MyModelCommand::AddItem("text")`, whose arguments are `"text"`
+    pub fn new(model: &'static str, variant: &'static str, cqrs: &impl Serialize) -> Self {
+        // serde tags the variants externally, unit variants are serialized as their name only
+        let arguments = match serde_json::to_value(cqrs) {
+            Ok(Value::Object(mut variant_n_arguments)) => {
+                variant_n_arguments.remove(variant).unwrap_or_default()
+            }
+            _ => Value::Null,
+        };
+        InterceptedCall {
+            model,
+            variant,
+            arguments,
+        }
+    }
+}
+
+/// cross-cutting behaviour (like logging, auditing or authorization) around every CQRS call (option `interceptors`).
+/// `Effect` and `Error` are the generated `Effect` and `ProcessingError`.
+pub trait Interceptor<Effect, Error>: Send + Sync {
+    /// called before the call is processed.
+    /// @returns Err to reject the call, before the model's lock is called
+    fn before(&self, _call: &InterceptedCall) -> Result<(), Error> {
+        Ok(())
+    }
+    /// called with the outcome of every call, which wasn't rejected
+    fn after(&self, _call: &InterceptedCall, _result: Result<&[Effect], &Error>) {}
+}
+
+type RegisteredInterceptors<Effect, Error> = Arc<[Arc<dyn Interceptor<Effect, Error>>]>;
+
+/// the interceptors registered at the lifecycle, run in the order of their registration.
+/// They are run on a snapshot taken without holding the lock, so an interceptor can register others.
+pub struct Interceptors<Effect, Error> {
+    interceptors: RwLock<RegisteredInterceptors<Effect, Error>>,
+}
+
+impl<Effect, Error> Default for Interceptors<Effect, Error> {
+    fn default() -> Self {
+        Interceptors {
+            interceptors: RwLock::new(Arc::new([])),
+        }
+    }
+}
+
+impl<Effect, Error> std::fmt::Debug for Interceptors<Effect, Error> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Interceptors")
+            .field("count", &self.len())
+            .finish()
+    }
+}
+
+impl<Effect, Error> Interceptors<Effect, Error> {
+    pub fn register(&self, interceptor: impl Interceptor<Effect, Error> + 'static) {
+        let mut interceptors = self
+            .interceptors
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        let mut registered = interceptors.to_vec();
+        registered.push(Arc::new(interceptor));
+        *interceptors = registered.into();
+    }
+
+    /// the currently registered interceptors, released from the lock before they are called
+    fn snapshot(&self) -> RegisteredInterceptors<Effect, Error> {
+        Arc::clone(
+            &self
+                .interceptors
+                .read()
+                .unwrap_or_else(PoisonError::into_inner),
+        )
+    }
+
+    pub fn len(&self) -> usize {
+        self.snapshot().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// runs every interceptor's `before()`, stopping at the first rejection
+    pub fn before(&self, call: &InterceptedCall) -> Result<(), Error> {
+        self.snapshot()
+            .iter()
+            .try_for_each(|interceptor| interceptor.before(call))
+    }
+
+    pub fn after(&self, call: &InterceptedCall, result: Result<&[Effect], &Error>) {
+        for interceptor in self.snapshot().iter() {
+            interceptor.after(call, result);
+        }
+    }
+
+    /// runs the interceptors around `process`, which processes the command or query - used by the generated `process_with()`.
+    /// The arguments are only serialized into the `InterceptedCall`, if interceptors are registered.
+    /// Interceptors registered meanwhile run from the next call on.
+    pub fn intercept<C: Serialize>(
+        &self,
+        model: &'static str,
+        variant: &'static str,
+        cqrs: C,
+        process: impl FnOnce(C) -> Result<Vec<Effect>, Error>,
+    ) -> Result<Vec<Effect>, Error> {
+        let interceptors = self.snapshot();
+        if interceptors.is_empty() {
+            return process(cqrs);
+        }
+        let call = InterceptedCall::new(model, variant, &cqrs);
+        interceptors
+            .iter()
+            .try_for_each(|interceptor| interceptor.before(&call))?;
+        let result = process(cqrs);
+        for interceptor in interceptors.iter() {
+            interceptor.after(&call, result.as_deref());
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    };
+
+    use serde::{Serialize, Serializer};
+    use serde_json::json;
+
+    use super::{InterceptedCall, Interceptor, Interceptors};
+
+    #[derive(Serialize)]
+    enum MyModelCommand {
+        AddItem(String, usize),
+        CleanList,
+    }
+
+    #[test]
+    fn serialize_the_arguments() {
+        let call = InterceptedCall::new(
+            "MyModel",
+            "AddItem",
+            &MyModelCommand::AddItem("item".to_string(), 2),
+        );
+        assert_eq!(json!(["item", 2]), call.arguments);
+        let call = InterceptedCall::new("MyModel", "CleanList", &MyModelCommand::CleanList);
+        assert_eq!(json!(null), call.arguments);
+    }
+
+    struct RejectingInterceptor;
+
+    impl Interceptor<String, String> for RejectingInterceptor {
+        fn before(&self, call: &InterceptedCall) -> Result<(), String> {
+            match call.variant {
+                "CleanList" => Err("not allowed".to_string()),
+                _ => Ok(()),
+            }
+        }
+    }
+
+    struct RecordingInterceptor(Arc<Mutex<Vec<String>>>);
+
+    impl Interceptor<String, String> for RecordingInterceptor {
+        fn after(&self, call: &InterceptedCall, result: Result<&[String], &String>) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("{}::{} {:?}", call.model, call.variant, result));
+        }
+    }
+
+    #[test]
+    fn run_interceptors_in_order() {
+        let recorded = Arc::new(Mutex::new(Vec::new()));
+        let interceptors = Interceptors::<String, String>::default();
+        interceptors.register(RejectingInterceptor);
+        interceptors.register(RecordingInterceptor(recorded.clone()));
+        assert_eq!(2, interceptors.len());
+
+        let clean_list = InterceptedCall::new("MyModel", "CleanList", &MyModelCommand::CleanList);
+        assert_eq!(
+            Err("not allowed".to_string()),
+            interceptors.before(&clean_list)
+        );
+        let add_item = InterceptedCall::new(
+            "MyModel",
+            "AddItem",
+            &MyModelCommand::AddItem("item".to_string(), 2),
+        );
+        assert_eq!(Ok(()), interceptors.before(&add_item));
+        interceptors.after(&add_item, Ok(&["rendered".to_string()]));
+        assert_eq!(
+            vec![r#"MyModel::AddItem Ok(["rendered"])"#.to_string()],
+            *recorded.lock().unwrap()
+        );
+    }
+    /// registers another interceptor, while it is called
+    struct RegisteringInterceptor(Arc<Interceptors<String, String>>);
+
+    impl Interceptor<String, String> for RegisteringInterceptor {
+        fn before(&self, _call: &InterceptedCall) -> Result<(), String> {
+            self.0.register(RejectingInterceptor);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn register_interceptors_from_an_interceptor() {
+        let interceptors = Arc::new(Interceptors::<String, String>::default());
+        interceptors.register(RegisteringInterceptor(Arc::clone(&interceptors)));
+        let clean_list = InterceptedCall::new("MyModel", "CleanList", &MyModelCommand::CleanList);
+        // the interceptor registered meanwhile runs from the next call on
+        assert_eq!(Ok(()), interceptors.before(&clean_list));
+        assert_eq!(2, interceptors.len());
+        assert_eq!(
+            Err("not allowed".to_string()),
+            interceptors.before(&clean_list)
+        );
+    }
+
+    /// counts how often the call's arguments are serialized
+    struct CountedCall<'a>(&'a AtomicUsize);
+
+    impl Serialize for CountedCall<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            serializer.serialize_unit()
+        }
+    }
+
+    #[test]
+    fn serialize_the_arguments_only_for_registered_interceptors() {
+        let serialized = AtomicUsize::new(0);
+        let interceptors = Interceptors::<String, String>::default();
+        let process = |_| Ok(vec!["rendered".to_string()]);
+        assert_eq!(
+            Ok(vec!["rendered".to_string()]),
+            interceptors.intercept("MyModel", "AddItem", CountedCall(&serialized), process)
+        );
+        assert_eq!(0, serialized.load(Ordering::SeqCst));
+
+        let recorded = Arc::new(Mutex::new(Vec::new()));
+        interceptors.register(RecordingInterceptor(recorded.clone()));
+        interceptors
+            .intercept("MyModel", "AddItem", CountedCall(&serialized), process)
+            .unwrap();
+        assert_eq!(1, serialized.load(Ordering::SeqCst));
+        assert_eq!(
+            vec![r#"MyModel::AddItem Ok(["rendered"])"#.to_string()],
+            *recorded.lock().unwrap()
+        );
+    }
+}
@@ -0,0 +1,56 @@
+//! The traits and helpers used by the code, which `generate_cqrs_api_macro::generate_api` generates.
+//! They are generic over the generated `ProcessingError` and `Effect` enums,
+//! so that several lifecycles (and crates) can share them.
+mod api_traits;
+mod authorization;
+mod backup_persister;
+mod command_journal;
+mod cqrs_traits;
+mod deferred_persistence;
+mod dirty_flag;
+#[cfg(feature = "encryption")]
+mod encrypted_persister;
+mod file_persister;
+mod interceptor;
+mod metrics;
+mod schema_version;
+#[cfg(feature = "sqlite")]
+mod sqlite_persister;
+mod state_format;
+#[cfg(test)]
+mod test_mocks;
+mod undo_history;
+mod validation;
+
+pub use api_traits::{
+    AppConfig, AppState, AppStatePersistError, AppStatePersister, InitialisationResult, Lifecycle,
+    ModelId, NotPersistedError,
+};
+pub use authorization::{Authorization, Authorizer};
+pub use backup_persister::{BackupAppStatePersister, BackupAppStatePersisterError};
+pub use command_journal::{CommandJournal, CommandJournalError, JournalPosition};
+pub use cqrs_traits::{
+    CommandsLock, Cqrs, CqrsModel, CqrsModelLock, PreviewableCqrs, SwappableModelLock,
+    UndoableModelLock,
+};
+pub use deferred_persistence::DeferredPersistence;
+pub use dirty_flag::DirtyFlag;
+#[cfg(feature = "encryption")]
+pub use encrypted_persister::{EncryptedAppStatePersister, EncryptedAppStatePersisterError};
+pub use file_persister::{FileAppStatePersister, FileAppStatePersisterError};
+pub use interceptor::{InterceptedCall, Interceptor, Interceptors};
+pub use metrics::{
+    CallMetrics, LatencyHistogram, MetricsRegistry, MetricsSnapshot, PersistenceMetrics,
+    LATENCY_BUCKETS_US,
+};
+pub use schema_version::{deserialize_versioned, migrate, serialize_versioned, Migration};
+/// used by the code generated for `#[cqrs_model]`
+pub use serde_json;
+#[cfg(feature = "sqlite")]
+pub use sqlite_persister::{SqliteAppStatePersister, SqliteAppStatePersisterError};
+pub use state_format::{deserialize_detecting_format, FormatError, StateFormat};
+/// used by the code generated with the feature `tracing`
+#[cfg(feature = "tracing")]
+pub use tracing;
+pub use undo_history::UndoHistory;
+pub use validation::{check_length, check_range, ValidatedLength};
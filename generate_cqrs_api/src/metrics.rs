@@ -0,0 +1,191 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Mutex, MutexGuard, PoisonError},
+    time::Duration,
+};
+
+/// the upper bounds (inclusive, in microseconds) of the latency histograms' buckets.
+/// Slower calls are counted in an additional last bucket.
+pub const LATENCY_BUCKETS_US: &[u64] = &[100, 1_000, 10_000, 100_000, 1_000_000];
+
+/// the distribution of durations, see `LATENCY_BUCKETS_US`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LatencyHistogram {
+    /// `bucket_counts[i]` counts the durations up to `LATENCY_BUCKETS_US[i]`, the last one the slower ones
+    pub bucket_counts: Vec<u64>,
+    pub total_us: u64,
+    pub max_us: u64,
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        LatencyHistogram {
+            bucket_counts: vec![0; LATENCY_BUCKETS_US.len() + 1],
+            total_us: 0,
+            max_us: 0,
+        }
+    }
+}
+
+impl LatencyHistogram {
+    fn record(&mut self, duration: Duration) {
+        let duration_us = u64::try_from(duration.as_micros()).unwrap_or(u64::MAX);
+        let bucket = LATENCY_BUCKETS_US
+            .iter()
+            .position(|bound| duration_us <= *bound)
+            .unwrap_or(LATENCY_BUCKETS_US.len());
+        self.bucket_counts[bucket] += 1;
+        self.total_us = self.total_us.saturating_add(duration_us);
+        self.max_us = self.max_us.max(duration_us);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.bucket_counts.iter().sum()
+    }
+
+    pub fn mean_us(&self) -> u64 {
+        self.total_us.checked_div(self.count()).unwrap_or(0)
+    }
+}
+
+/// the metrics of one `*Command` or `*Query` variant
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CallMetrics {
+    pub model: String,
+    pub variant: String,
+    pub count: u64,
+    pub error_count: u64,
+    pub latency: LatencyHistogram,
+}
+
+/// the metrics of persisting the app state
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PersistenceMetrics {
+    pub count: u64,
+    pub error_count: u64,
+    pub latency: LatencyHistogram,
+}
+
+/// a copy of the collected metrics, e.g. for a diagnostics page
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct MetricsSnapshot {
+    /// ordered by model and variant
+    pub calls: Vec<CallMetrics>,
+    pub persistence: PersistenceMetrics,
+}
+
+impl MetricsSnapshot {
+    /// @returns the calls with the highest mean latency first, to spot slow commands
+    pub fn slowest_calls(&self) -> Vec<&CallMetrics> {
+        let mut calls = self.calls.iter().collect::<Vec<&CallMetrics>>();
+        calls.sort_by_key(|call| std::cmp::Reverse(call.latency.mean_us()));
+        calls
+    }
+}
+
+/// collects the metrics of the generated CQRS calls and of persisting the app state (option `metrics`)
+#[derive(Debug, Default)]
+pub struct MetricsRegistry {
+    metrics: Mutex<Metrics>,
+}
+
+#[derive(Debug, Default)]
+struct Metrics {
+    /// keyed by (model, variant)
+    calls: BTreeMap<(&'static str, &'static str), CallMetrics>,
+    persistence: PersistenceMetrics,
+}
+
+impl MetricsRegistry {
+    fn lock(&self) -> MutexGuard<'_, Metrics> {
+        self.metrics.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn record_call(
+        &self,
+        model: &'static str,
+        variant: &'static str,
+        duration: Duration,
+        failed: bool,
+    ) {
+        let mut metrics = self.lock();
+        let call = metrics
+            .calls
+            .entry((model, variant))
+            .or_insert_with(|| CallMetrics {
+                model: model.to_string(),
+                variant: variant.to_string(),
+                ..CallMetrics::default()
+            });
+        call.count += 1;
+        call.error_count += u64::from(failed);
+        call.latency.record(duration);
+    }
+
+    pub fn record_persistence(&self, duration: Duration, failed: bool) {
+        let persistence = &mut self.lock().persistence;
+        persistence.count += 1;
+        persistence.error_count += u64::from(failed);
+        persistence.latency.record(duration);
+    }
+
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        let metrics = self.lock();
+        MetricsSnapshot {
+            calls: metrics.calls.values().cloned().collect(),
+            persistence: metrics.persistence.clone(),
+        }
+    }
+
+    pub fn reset(&self) {
+        *self.lock() = Metrics::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{LatencyHistogram, MetricsRegistry};
+
+    #[test]
+    fn record_calls_per_variant() {
+        let registry = MetricsRegistry::default();
+        registry.record_call("MyModel", "AddItem", Duration::from_micros(50), false);
+        registry.record_call("MyModel", "AddItem", Duration::from_micros(5_000), true);
+        registry.record_call("MyModel", "CleanList", Duration::from_secs(2), false);
+        registry.record_persistence(Duration::from_micros(300), false);
+
+        let snapshot = registry.snapshot();
+        assert_eq!(2, snapshot.calls.len());
+        let add_item = &snapshot.calls[0];
+        assert_eq!(
+            ("MyModel", "AddItem", 2, 1),
+            (
+                add_item.model.as_str(),
+                add_item.variant.as_str(),
+                add_item.count,
+                add_item.error_count
+            )
+        );
+        assert_eq!(
+            LatencyHistogram {
+                bucket_counts: vec![1, 0, 1, 0, 0, 0],
+                total_us: 5_050,
+                max_us: 5_000,
+            },
+            add_item.latency
+        );
+        assert_eq!(2_525, add_item.latency.mean_us());
+        assert_eq!(
+            vec![0, 0, 0, 0, 0, 1],
+            snapshot.calls[1].latency.bucket_counts
+        );
+        assert_eq!("CleanList", snapshot.slowest_calls()[0].variant);
+        assert_eq!(1, snapshot.persistence.count);
+        assert_eq!(1, snapshot.persistence.latency.bucket_counts[1]);
+
+        registry.reset();
+        assert!(registry.snapshot().calls.is_empty());
+    }
+}
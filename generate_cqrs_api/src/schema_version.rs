@@ -0,0 +1,250 @@
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+
+use crate::{state_format::is_self_describing, CqrsModel};
+
+/// migrates a persisted model from one schema version to the next, like `migrate_v1_to_v2`
+pub type Migration = fn(Value) -> Value;
+
+/// no field of a serialized struct can be named like this, so an unversioned model is never taken for a versioned one
+const SCHEMA_VERSION_KEY: &str = "#schema_version";
+const MODEL_KEY: &str = "model";
+
+#[derive(Serialize)]
+struct VersionedModel<'a, M> {
+    #[serde(rename = "#schema_version")]
+    schema_version: u32,
+    model: &'a M,
+}
+
+/// serializes the model with its `CqrsModel::SCHEMA_VERSION`, like `{"#schema_version":2,"model":{..}}`.
+/// Models in version 1 are serialized as they are.
+/// Fails for later versions with `StateFormat::Bincode`, as they couldn't be migrated when loading.
+pub fn serialize_versioned<M: CqrsModel, S: Serializer>(
+    model: &M,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    if M::SCHEMA_VERSION <= 1 {
+        return model.serialize(serializer);
+    }
+    if !is_self_describing() {
+        return Err(serde::ser::Error::custom(not_self_describing_error::<M>()));
+    }
+    VersionedModel {
+        schema_version: M::SCHEMA_VERSION,
+        model,
+    }
+    .serialize(serializer)
+}
+
+/// deserializes a model serialized by `serialize_versioned()`, running the `CqrsModel::MIGRATIONS`
+/// from the persisted version to the current one first. A model without a version is in version 1.
+/// Migrating needs a self-describing format (like JSON), models in version 1 can use any format.
+pub fn deserialize_versioned<'de, M: CqrsModel, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<M, D::Error> {
+    if M::SCHEMA_VERSION <= 1 {
+        return M::deserialize(deserializer);
+    }
+    if !is_self_describing() {
+        return Err(D::Error::custom(not_self_describing_error::<M>()));
+    }
+    migrate::<M>(Value::deserialize(deserializer)?).map_err(D::Error::custom)
+}
+
+fn not_self_describing_error<M: CqrsModel>() -> String {
+    format!(
+        "The model has the schema version {}, which needs a self-describing format to be migrated - persist it as JSON, CBOR or MessagePack, not as Bincode",
+        M::SCHEMA_VERSION
+    )
+}
+
+/// migrates the persisted model to the current schema version and deserializes it
+pub fn migrate<M: CqrsModel>(persisted: Value) -> Result<M, String> {
+    let (persisted_version, mut model) = split_schema_version(persisted);
+    if persisted_version == 0 {
+        return Err(
+            "The persisted model has the schema version 0, but versions start at 1".to_string(),
+        );
+    }
+    if persisted_version > M::SCHEMA_VERSION {
+        return Err(format!(
+            "The persisted model has the schema version {persisted_version}, which is newer than the current version {}",
+            M::SCHEMA_VERSION
+        ));
+    }
+    for version in persisted_version..M::SCHEMA_VERSION {
+        let migration = M::MIGRATIONS.get(version as usize - 1).ok_or_else(|| {
+            format!(
+                "No migration from schema version {version} to {}",
+                version + 1
+            )
+        })?;
+        model = migration(model);
+    }
+    serde_json::from_value(model).map_err(|error| error.to_string())
+}
+
+/// @returns (the schema version, the model)
+fn split_schema_version(persisted: Value) -> (u32, Value) {
+    match persisted {
+        Value::Object(mut versioned)
+            if versioned.len() == 2
+                && versioned.contains_key(MODEL_KEY)
+                && versioned.get(SCHEMA_VERSION_KEY).is_some_and(Value::is_u64) =>
+        {
+            let schema_version = versioned
+                .get(SCHEMA_VERSION_KEY)
+                .and_then(Value::as_u64)
+                .expect("checked above") as u32;
+            let model = versioned.remove(MODEL_KEY).expect("checked above");
+            (schema_version, model)
+        }
+        unversioned => (1, unversioned),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+    use serde_json::{json, Value};
+
+    use super::{
+        deserialize_versioned, migrate, serialize_versioned, split_schema_version, Migration,
+    };
+    use crate::CqrsModel;
+
+    #[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+    struct TodoList {
+        todos: Vec<Todo>,
+    }
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Todo {
+        text: String,
+        done: bool,
+    }
+    impl CqrsModel for TodoList {
+        const SCHEMA_VERSION: u32 = 3;
+        const MIGRATIONS: &'static [Migration] = &[migrate_v1_to_v2, migrate_v2_to_v3];
+    }
+
+    /// v1: {"items": ["text"]} => v2: {"todos": ["text"]}
+    fn migrate_v1_to_v2(mut model: Value) -> Value {
+        let items = model["items"].take();
+        json!({ "todos": items })
+    }
+    /// v2: {"todos": ["text"]} => v3: {"todos": [{"text": "text", "done": false}]}
+    fn migrate_v2_to_v3(mut model: Value) -> Value {
+        let todos = model["todos"]
+            .take()
+            .as_array()
+            .map(|todos| {
+                todos
+                    .iter()
+                    .map(|text| json!({ "text": text, "done": false }))
+                    .collect::<Vec<Value>>()
+            })
+            .unwrap_or_default();
+        json!({ "todos": todos })
+    }
+
+    fn migrated_todo_list() -> TodoList {
+        TodoList {
+            todos: vec![Todo {
+                text: "first item".to_string(),
+                done: false,
+            }],
+        }
+    }
+
+    #[test]
+    fn serialize_with_schema_version() {
+        let serialized =
+            serialize_versioned(&migrated_todo_list(), serde_json::value::Serializer).unwrap();
+        assert_eq!(
+            json!({"#schema_version": 3, "model": {"todos": [{"text": "first item", "done": false}]}}),
+            serialized
+        );
+        assert_eq!(
+            migrated_todo_list(),
+            deserialize_versioned::<TodoList, _>(serialized).unwrap()
+        );
+    }
+    #[test]
+    fn migrate_unversioned_model() {
+        assert_eq!(
+            Ok(migrated_todo_list()),
+            migrate::<TodoList>(json!({"items": ["first item"]}))
+        );
+    }
+    #[test]
+    fn migrate_from_version_2() {
+        assert_eq!(
+            Ok(migrated_todo_list()),
+            migrate::<TodoList>(json!({"#schema_version": 2, "model": {"todos": ["first item"]}}))
+        );
+    }
+    #[cfg(feature = "bincode")]
+    #[test]
+    fn reject_bincode_for_versioned_models() {
+        use serde::{Deserializer, Serializer};
+
+        use crate::{deserialize_detecting_format, StateFormat};
+
+        #[derive(Debug)]
+        struct VersionedTodoList(TodoList);
+        impl Serialize for VersionedTodoList {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serialize_versioned(&self.0, serializer)
+            }
+        }
+        impl<'de> Deserialize<'de> for VersionedTodoList {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                deserialize_versioned(deserializer).map(VersionedTodoList)
+            }
+        }
+
+        let expected_error = "The model has the schema version 3, which needs a self-describing format to be migrated - persist it as JSON, CBOR or MessagePack, not as Bincode";
+        let todo_list = VersionedTodoList(migrated_todo_list());
+        assert_eq!(
+            expected_error,
+            StateFormat::Bincode
+                .serialize(&todo_list)
+                .unwrap_err()
+                .to_string()
+        );
+        let error = deserialize_detecting_format::<VersionedTodoList>(b"#cqrs:bincode\n\x03")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains(expected_error), "{error}");
+        // other formats are still self-describing
+        let serialized = StateFormat::Json.serialize(&todo_list).unwrap();
+        assert_eq!(
+            migrated_todo_list(),
+            deserialize_detecting_format::<VersionedTodoList>(&serialized)
+                .unwrap()
+                .0
+        );
+    }
+    #[test]
+    fn keep_unversioned_models_looking_like_versioned_ones() {
+        let unversioned = json!({"schema_version": 2, "model": "my model"});
+        assert_eq!((1, unversioned.clone()), split_schema_version(unversioned));
+    }
+    #[test]
+    fn fail_schema_version_0() {
+        assert_eq!(
+            Err(
+                "The persisted model has the schema version 0, but versions start at 1".to_string()
+            ),
+            migrate::<TodoList>(json!({"#schema_version": 0, "model": {"todos": []}}))
+        );
+    }
+    #[test]
+    fn fail_newer_schema_version() {
+        assert_eq!(
+            Err("The persisted model has the schema version 4, which is newer than the current version 3".to_string()),
+            migrate::<TodoList>(json!({"#schema_version": 4, "model": {"todos": []}}))
+        );
+    }
+}
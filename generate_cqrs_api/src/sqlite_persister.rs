@@ -0,0 +1,430 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Mutex, PoisonError},
+};
+
+use rusqlite::{params, Connection};
+use serde_json::{Map, Value};
+
+use crate::{
+    AppConfig, AppState, AppStatePersistError, AppStatePersister, ModelId, NotPersistedError,
+};
+
+/// persists the app state into the SQLite file at `AppConfig::borrow_app_state_url()`.
+/// Each field of the `AppState` (each `CqrsModelLock`) is stored as JSON in its own row,
+/// only models that changed since the last write (or the given dirty models) are written - inside a transaction,
+/// Given models are serialized on their own with `AppState::serialize_model()`, if the app state supports it (like the generated one).
+/// so that a crash never leaves a half-written state behind.
+#[derive(Debug)]
+pub struct SqliteAppStatePersister {
+    path: PathBuf,
+    connection: Mutex<Connection>,
+    /// the models as they were written (or loaded) the last time
+    persisted_models: Mutex<HashMap<String, String>>,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum SqliteAppStatePersisterError {
+    #[error("No app state found in '{0}'")]
+    NotFound(PathBuf),
+    #[error("Can't access the app state database '{path}': {error}")]
+    DatabaseError {
+        error: rusqlite::Error,
+        path: PathBuf,
+    },
+    #[error("Can't create the directory of the app state database '{path}': {error}")]
+    IoError {
+        error: std::io::Error,
+        path: PathBuf,
+    },
+    #[error("Can't (de)serialize the app state in '{path}': {error}")]
+    SerializationError {
+        error: serde_json::Error,
+        path: PathBuf,
+    },
+    #[error("The app state needs to be a struct, to store its fields as models in '{0}'")]
+    NotAStruct(PathBuf),
+}
+
+impl SqliteAppStatePersisterError {
+    fn path(&self) -> &Path {
+        match self {
+            SqliteAppStatePersisterError::NotFound(path)
+            | SqliteAppStatePersisterError::DatabaseError { path, .. }
+            | SqliteAppStatePersisterError::IoError { path, .. }
+            | SqliteAppStatePersisterError::SerializationError { path, .. }
+            | SqliteAppStatePersisterError::NotAStruct(path) => path,
+        }
+    }
+}
+
+impl<PE: NotPersistedError> AppStatePersistError<PE> for SqliteAppStatePersisterError {
+    fn to_processing_error(&self) -> PE {
+        PE::not_persisted(self.to_string(), self.path().to_string_lossy().to_string())
+    }
+    fn is_not_found(&self) -> bool {
+        matches!(self, SqliteAppStatePersisterError::NotFound(_))
+    }
+}
+
+impl SqliteAppStatePersister {
+    fn database_error(&self, error: rusqlite::Error) -> SqliteAppStatePersisterError {
+        SqliteAppStatePersisterError::DatabaseError {
+            error,
+            path: self.path.to_owned(),
+        }
+    }
+    fn serialization_error(&self, error: serde_json::Error) -> SqliteAppStatePersisterError {
+        SqliteAppStatePersisterError::SerializationError {
+            error,
+            path: self.path.to_owned(),
+        }
+    }
+
+    /// serializes the given models on their own - or the whole state, if no models are given
+    /// or the state can't serialize one of them separately
+    fn serialize_models<AS: AppState>(
+        &self,
+        state: &AS,
+        models: &[ModelId],
+    ) -> Result<Vec<(String, Value)>, SqliteAppStatePersisterError> {
+        let separately = models
+            .iter()
+            .map(|model| {
+                state
+                    .serialize_model(*model, serde_json::value::Serializer)
+                    .map(|serialized| {
+                        serialized.map(|serialized| (model.0.to_string(), serialized))
+                    })
+            })
+            .collect::<Option<Result<Vec<(String, Value)>, serde_json::Error>>>();
+        match separately {
+            Some(serialized_models) if !models.is_empty() => {
+                serialized_models.map_err(|error| self.serialization_error(error))
+            }
+            _ => match serde_json::to_value(state)
+                .map_err(|error| self.serialization_error(error))?
+            {
+                Value::Object(serialized_models) => Ok(serialized_models
+                    .into_iter()
+                    .filter(|(name, _)| {
+                        models.is_empty() || models.iter().any(|model| model.0 == name)
+                    })
+                    .collect()),
+                _ => Err(SqliteAppStatePersisterError::NotAStruct(
+                    self.path.to_owned(),
+                )),
+            },
+        }
+    }
+}
+
+impl AppStatePersister for SqliteAppStatePersister {
+    type Error = SqliteAppStatePersisterError;
+    fn new<AC: AppConfig>(app_config: &AC) -> Result<Self, Self::Error> {
+        let path = PathBuf::from(app_config.borrow_app_state_url());
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            std::fs::create_dir_all(parent).map_err(|error| {
+                SqliteAppStatePersisterError::IoError {
+                    error,
+                    path: path.to_owned(),
+                }
+            })?;
+        }
+        let database_error = |error| SqliteAppStatePersisterError::DatabaseError {
+            error,
+            path: path.to_owned(),
+        };
+        let connection = Connection::open(&path).map_err(database_error)?;
+        connection
+            .execute(
+                "CREATE TABLE IF NOT EXISTS models (name TEXT PRIMARY KEY, state TEXT NOT NULL)",
+                [],
+            )
+            .map_err(database_error)?;
+        Ok(SqliteAppStatePersister {
+            path,
+            connection: Mutex::new(connection),
+            persisted_models: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn persist_app_state<AS: AppState + std::fmt::Debug>(
+        &self,
+        state: &AS,
+    ) -> Result<(), Self::Error> {
+        self.persist_models(state, &[])
+    }
+
+    /// writes the given models - or all models, which changed since the last write
+    fn persist_models<AS: AppState + std::fmt::Debug>(
+        &self,
+        state: &AS,
+        models: &[ModelId],
+    ) -> Result<(), Self::Error> {
+        // serialized under the connection's lock, so that an older serialization is never committed after a newer one
+        let mut connection = self
+            .connection
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let mut persisted_models = self
+            .persisted_models
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let serialized_models = self.serialize_models(state, models)?;
+        let changed_models = serialized_models
+            .into_iter()
+            .map(|(name, model)| (name, model.to_string()))
+            .filter(|(name, model)| persisted_models.get(name) != Some(model))
+            .collect::<Vec<(String, String)>>();
+        if changed_models.is_empty() {
+            return Ok(());
+        }
+        let transaction = connection
+            .transaction()
+            .map_err(|error| self.database_error(error))?;
+        for (name, model) in &changed_models {
+            transaction
+                .execute(
+                    "INSERT INTO models (name, state) VALUES (?1, ?2)
+                     ON CONFLICT(name) DO UPDATE SET state = excluded.state",
+                    params![name, model],
+                )
+                .map_err(|error| self.database_error(error))?;
+        }
+        transaction
+            .commit()
+            .map_err(|error| self.database_error(error))?;
+        persisted_models.extend(changed_models);
+        Ok(())
+    }
+
+    fn load_app_state<AC: AppConfig, AS: AppState>(&self) -> Result<AS, Self::Error> {
+        let connection = self
+            .connection
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let mut statement = connection
+            .prepare("SELECT name, state FROM models")
+            .map_err(|error| self.database_error(error))?;
+        let rows = statement
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })
+            .and_then(|rows| rows.collect::<Result<Vec<(String, String)>, _>>())
+            .map_err(|error| self.database_error(error))?;
+        if rows.is_empty() {
+            return Err(SqliteAppStatePersisterError::NotFound(self.path.to_owned()));
+        }
+        let mut models = Map::new();
+        for (name, model) in &rows {
+            models.insert(
+                name.to_owned(),
+                serde_json::from_str(model).map_err(|error| self.serialization_error(error))?,
+            );
+        }
+        let app_state = serde_json::from_value(Value::Object(models))
+            .map_err(|error| self.serialization_error(error))?;
+        *self
+            .persisted_models
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = rows.into_iter().collect();
+        Ok(app_state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::RwLock;
+
+    use rusqlite::Connection;
+    use serde::{ser::Error, Deserialize, Serialize, Serializer};
+
+    use super::{SqliteAppStatePersister, SqliteAppStatePersisterError};
+    use crate::{
+        test_mocks::{test_app_config, TestAppConfig, TestAppState, TestProcessingError},
+        AppConfig, AppState, AppStatePersistError, AppStatePersister, ModelId,
+    };
+
+    /// serializable by its models only, to see that the whole state isn't serialized
+    #[derive(Debug, Default, Deserialize)]
+    struct ModelWiseAppState {
+        items: Vec<String>,
+    }
+    impl Serialize for ModelWiseAppState {
+        fn serialize<S: Serializer>(&self, _serializer: S) -> Result<S::Ok, S::Error> {
+            Err(S::Error::custom("the whole state was serialized"))
+        }
+    }
+    impl AppState for ModelWiseAppState {
+        fn new<AC: AppConfig>(_app_config: &AC) -> Self {
+            Self::default()
+        }
+        fn dirty_flag_value(&self) -> bool {
+            false
+        }
+        fn mark_dirty(&self) {}
+        fn mark_persisted(&self) {}
+        fn serialize_model<S: Serializer>(
+            &self,
+            model: ModelId,
+            serializer: S,
+        ) -> Option<Result<S::Ok, S::Error>> {
+            match model.0 {
+                "items" => Some(self.items.serialize(serializer)),
+                _ => None,
+            }
+        }
+    }
+
+    /// changed by several threads, like the models of a lifecycle
+    #[derive(Debug, Default, Serialize, Deserialize)]
+    struct SharedAppState {
+        items: RwLock<Vec<String>>,
+    }
+    impl AppState for SharedAppState {
+        fn new<AC: AppConfig>(_app_config: &AC) -> Self {
+            Self::default()
+        }
+        fn dirty_flag_value(&self) -> bool {
+            false
+        }
+        fn mark_dirty(&self) {}
+        fn mark_persisted(&self) {}
+    }
+
+    fn get_model_row(app_config: &TestAppConfig, name: &str) -> String {
+        Connection::open(app_config.borrow_app_state_url())
+            .unwrap()
+            .query_row("SELECT state FROM models WHERE name = ?1", [name], |row| {
+                row.get(0)
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn persist_and_load_app_state() {
+        let app_config = test_app_config("sqlite_persist_and_load", "app_state.sqlite");
+        let persister = SqliteAppStatePersister::new(&app_config).unwrap();
+        let app_state = TestAppState {
+            items: vec!["first item".to_string()],
+            notes: vec!["a note".to_string()],
+            ..Default::default()
+        };
+        persister.persist_app_state(&app_state).unwrap();
+        assert_eq!(r#"["first item"]"#, get_model_row(&app_config, "items"));
+        assert_eq!(r#"["a note"]"#, get_model_row(&app_config, "notes"));
+
+        let loaded = SqliteAppStatePersister::new(&app_config)
+            .unwrap()
+            .load_app_state::<TestAppConfig, TestAppState>()
+            .unwrap();
+        assert_eq!(vec!["first item".to_string()], loaded.items);
+        assert_eq!(vec!["a note".to_string()], loaded.notes);
+    }
+    #[test]
+    fn write_changed_models_only() {
+        let app_config = test_app_config("sqlite_changed_only", "app_state.sqlite");
+        let persister = SqliteAppStatePersister::new(&app_config).unwrap();
+        let mut app_state = TestAppState {
+            items: vec!["first item".to_string()],
+            notes: vec!["a note".to_string()],
+            ..Default::default()
+        };
+        persister.persist_app_state(&app_state).unwrap();
+        // changed behind the persister's back, to see if the row is written again
+        Connection::open(app_config.borrow_app_state_url())
+            .unwrap()
+            .execute("UPDATE models SET state = '[]' WHERE name = 'notes'", [])
+            .unwrap();
+
+        app_state.items.push("second item".to_string());
+        persister.persist_app_state(&app_state).unwrap();
+        assert_eq!(
+            r#"["first item","second item"]"#,
+            get_model_row(&app_config, "items")
+        );
+        assert_eq!("[]", get_model_row(&app_config, "notes"));
+    }
+    #[test]
+    fn persist_given_models_only() {
+        let app_config = test_app_config("sqlite_given_models", "app_state.sqlite");
+        let persister = SqliteAppStatePersister::new(&app_config).unwrap();
+        let mut app_state = TestAppState {
+            items: vec!["first item".to_string()],
+            notes: vec!["a note".to_string()],
+            ..Default::default()
+        };
+        persister.persist_app_state(&app_state).unwrap();
+
+        app_state.items.push("second item".to_string());
+        app_state.notes.push("not persisted".to_string());
+        persister
+            .persist_models(&app_state, &[ModelId("items")])
+            .unwrap();
+        assert_eq!(
+            r#"["first item","second item"]"#,
+            get_model_row(&app_config, "items")
+        );
+        assert_eq!(r#"["a note"]"#, get_model_row(&app_config, "notes"));
+    }
+    #[test]
+    fn serialize_given_models_only() {
+        let app_config = test_app_config("sqlite_serialize_models", "app_state.sqlite");
+        let persister = SqliteAppStatePersister::new(&app_config).unwrap();
+        let app_state = ModelWiseAppState {
+            items: vec!["first item".to_string()],
+        };
+        persister
+            .persist_models(&app_state, &[ModelId("items")])
+            .unwrap();
+        assert_eq!(r#"["first item"]"#, get_model_row(&app_config, "items"));
+        // unknown models are serialized with the whole state
+        assert!(matches!(
+            persister.persist_models(&app_state, &[ModelId("items"), ModelId("notes")]),
+            Err(SqliteAppStatePersisterError::SerializationError { .. })
+        ));
+    }
+    #[test]
+    fn never_commit_an_older_serialization() {
+        let app_config = test_app_config("sqlite_concurrently", "app_state.sqlite");
+        let persister = SqliteAppStatePersister::new(&app_config).unwrap();
+        let app_state = SharedAppState::default();
+        std::thread::scope(|scope| {
+            for thread in 0..8 {
+                let (persister, app_state) = (&persister, &app_state);
+                scope.spawn(move || {
+                    for item in 0..10 {
+                        app_state
+                            .items
+                            .write()
+                            .unwrap()
+                            .push(format!("item {thread}/{item}"));
+                        persister.persist_app_state(app_state).unwrap();
+                    }
+                });
+            }
+        });
+        let loaded = SqliteAppStatePersister::new(&app_config)
+            .unwrap()
+            .load_app_state::<TestAppConfig, SharedAppState>()
+            .unwrap();
+        assert_eq!(80, loaded.items.read().unwrap().len());
+    }
+    #[test]
+    fn load_empty_database_is_not_found() {
+        let app_config = test_app_config("sqlite_empty", "app_state.sqlite");
+        let error = SqliteAppStatePersister::new(&app_config)
+            .unwrap()
+            .load_app_state::<TestAppConfig, TestAppState>()
+            .unwrap_err();
+        assert!(matches!(error, SqliteAppStatePersisterError::NotFound(_)));
+        assert!(AppStatePersistError::<TestProcessingError>::is_not_found(
+            &error
+        ));
+    }
+}
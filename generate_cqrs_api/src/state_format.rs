@@ -0,0 +1,218 @@
+use serde::{de::DeserializeOwned, Serialize};
+
+/// starts the header line, like `#cqrs:json`, which is written in front of the persisted state.
+/// It tells the format when loading, so that a project can switch formats without losing existing data.
+const HEADER_PREFIX: &[u8] = b"#cqrs:";
+
+pub type FormatError = Box<dyn std::error::Error + Send + Sync>;
+
+#[cfg(feature = "bincode")]
+thread_local! {
+    /// false, while the state is (de)serialized with a format, which can't deserialize without knowing the types
+    static SELF_DESCRIBING: std::cell::Cell<bool> = const { std::cell::Cell::new(true) };
+}
+
+/// false, while the state is (de)serialized as Bincode, which can't be deserialized into a `serde_json::Value`.
+/// Checked by `serialize_versioned()` and `deserialize_versioned()`, as migrating needs the value.
+pub(crate) fn is_self_describing() -> bool {
+    #[cfg(feature = "bincode")]
+    return SELF_DESCRIBING.with(std::cell::Cell::get);
+    #[cfg(not(feature = "bincode"))]
+    true
+}
+
+/// restores the previous `SELF_DESCRIBING` when dropped - also if (de)serializing panicked
+#[cfg(feature = "bincode")]
+struct SelfDescribingGuard(bool);
+
+#[cfg(feature = "bincode")]
+impl Drop for SelfDescribingGuard {
+    fn drop(&mut self) {
+        SELF_DESCRIBING.with(|self_describing| self_describing.set(self.0));
+    }
+}
+
+#[cfg(feature = "bincode")]
+fn without_self_description<R>(bincode: impl FnOnce() -> R) -> R {
+    let _guard =
+        SelfDescribingGuard(SELF_DESCRIBING.with(|self_describing| self_describing.replace(false)));
+    bincode()
+}
+
+/// the format the app state is persisted in, see `AppConfig::app_state_format()`.
+/// The binary formats need their cargo feature (`bincode`, `cbor`, `msgpack`).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum StateFormat {
+    #[default]
+    Json,
+    PrettyJson,
+    #[cfg(feature = "bincode")]
+    Bincode,
+    #[cfg(feature = "cbor")]
+    Cbor,
+    #[cfg(feature = "msgpack")]
+    MessagePack,
+}
+
+impl StateFormat {
+    pub fn name(&self) -> &'static str {
+        match self {
+            StateFormat::Json => "json",
+            StateFormat::PrettyJson => "json-pretty",
+            #[cfg(feature = "bincode")]
+            StateFormat::Bincode => "bincode",
+            #[cfg(feature = "cbor")]
+            StateFormat::Cbor => "cbor",
+            #[cfg(feature = "msgpack")]
+            StateFormat::MessagePack => "msgpack",
+        }
+    }
+
+    /// @returns None, if the format is unknown or its cargo feature is not enabled
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "json" => Some(StateFormat::Json),
+            "json-pretty" => Some(StateFormat::PrettyJson),
+            #[cfg(feature = "bincode")]
+            "bincode" => Some(StateFormat::Bincode),
+            #[cfg(feature = "cbor")]
+            "cbor" => Some(StateFormat::Cbor),
+            #[cfg(feature = "msgpack")]
+            "msgpack" => Some(StateFormat::MessagePack),
+            _ => None,
+        }
+    }
+
+    /// serializes the state, preceded by the header
+    pub fn serialize<T: Serialize>(&self, state: &T) -> Result<Vec<u8>, FormatError> {
+        let mut serialized = [HEADER_PREFIX, self.name().as_bytes(), b"\n"].concat();
+        match self {
+            StateFormat::Json => serde_json::to_writer(&mut serialized, state)?,
+            StateFormat::PrettyJson => serde_json::to_writer_pretty(&mut serialized, state)?,
+            #[cfg(feature = "bincode")]
+            StateFormat::Bincode => {
+                without_self_description(|| bincode::serialize_into(&mut serialized, state))?
+            }
+            #[cfg(feature = "cbor")]
+            StateFormat::Cbor => ciborium::into_writer(state, &mut serialized)?,
+            #[cfg(feature = "msgpack")]
+            StateFormat::MessagePack => rmp_serde::encode::write_named(&mut serialized, state)?,
+        }
+        Ok(serialized)
+    }
+
+    fn deserialize_content<T: DeserializeOwned>(&self, content: &[u8]) -> Result<T, FormatError> {
+        Ok(match self {
+            StateFormat::Json | StateFormat::PrettyJson => serde_json::from_slice(content)?,
+            #[cfg(feature = "bincode")]
+            StateFormat::Bincode => without_self_description(|| bincode::deserialize(content))?,
+            #[cfg(feature = "cbor")]
+            StateFormat::Cbor => ciborium::from_reader(content)?,
+            #[cfg(feature = "msgpack")]
+            StateFormat::MessagePack => rmp_serde::from_slice(content)?,
+        })
+    }
+}
+
+/// deserializes the state in the format named by its header.
+/// Content without a header is read as JSON, as written before the header was introduced.
+pub fn deserialize_detecting_format<T: DeserializeOwned>(content: &[u8]) -> Result<T, FormatError> {
+    let Some(headed_content) = content.strip_prefix(HEADER_PREFIX) else {
+        return StateFormat::Json.deserialize_content(content);
+    };
+    let header_end = headed_content
+        .iter()
+        .position(|byte| *byte == b'\n')
+        .ok_or("The app state's header is not terminated by a newline")?;
+    let name = String::from_utf8_lossy(&headed_content[..header_end]);
+    let format = StateFormat::from_name(&name).ok_or_else(|| {
+        format!("The app state's format '{name}' is unknown or its cargo feature is not enabled")
+    })?;
+    format.deserialize_content(&headed_content[header_end + 1..])
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::{deserialize_detecting_format, StateFormat};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct TestState {
+        items: Vec<String>,
+        count: u32,
+    }
+
+    fn test_state() -> TestState {
+        TestState {
+            items: vec!["first item".to_string()],
+            count: 1,
+        }
+    }
+
+    fn all_formats() -> Vec<StateFormat> {
+        vec![
+            StateFormat::Json,
+            StateFormat::PrettyJson,
+            #[cfg(feature = "bincode")]
+            StateFormat::Bincode,
+            #[cfg(feature = "cbor")]
+            StateFormat::Cbor,
+            #[cfg(feature = "msgpack")]
+            StateFormat::MessagePack,
+        ]
+    }
+
+    #[test]
+    fn roundtrip_all_formats() {
+        for format in all_formats() {
+            let serialized = format.serialize(&test_state()).unwrap();
+            assert!(serialized.starts_with(format!("#cqrs:{}\n", format.name()).as_bytes()));
+            assert_eq!(
+                test_state(),
+                deserialize_detecting_format::<TestState>(&serialized).unwrap(),
+                "format {}",
+                format.name()
+            );
+            assert_eq!(Some(format), StateFormat::from_name(format.name()));
+        }
+    }
+    #[test]
+    fn json_header() {
+        assert_eq!(
+            "#cqrs:json\n{\"items\":[\"first item\"],\"count\":1}",
+            String::from_utf8(StateFormat::Json.serialize(&test_state()).unwrap()).unwrap()
+        );
+    }
+    #[test]
+    fn deserialize_without_header_as_json() {
+        assert_eq!(
+            test_state(),
+            deserialize_detecting_format::<TestState>(br#"{"items":["first item"],"count":1}"#)
+                .unwrap()
+        );
+    }
+    #[test]
+    fn fail_unknown_format() {
+        assert_eq!(
+            "The app state's format 'yaml' is unknown or its cargo feature is not enabled",
+            deserialize_detecting_format::<TestState>(b"#cqrs:yaml\nitems: []")
+                .unwrap_err()
+                .to_string()
+        );
+    }
+    #[cfg(feature = "bincode")]
+    #[test]
+    fn restore_self_describing_after_a_panic() {
+        use super::{is_self_describing, without_self_description};
+
+        let panicked = std::panic::catch_unwind(|| {
+            without_self_description(|| {
+                assert!(!is_self_describing());
+                panic!("serializing failed");
+            })
+        });
+        assert!(panicked.is_err());
+        assert!(is_self_describing());
+    }
+}
@@ -0,0 +1,76 @@
+//! the app config, app state and processing error the persisters are tested with
+use serde::{Deserialize, Serialize};
+
+use crate::{AppConfig, AppState, DirtyFlag, NotPersistedError, StateFormat};
+
+#[derive(Debug, Default)]
+pub(crate) struct TestAppConfig {
+    pub(crate) url: String,
+    pub(crate) format: StateFormat,
+    #[cfg(feature = "encryption")]
+    pub(crate) encryption_key: Option<[u8; 32]>,
+}
+impl AppConfig for TestAppConfig {
+    fn new(url: Option<String>) -> Self {
+        Self {
+            url: url.expect("tests pass an url"),
+            ..Default::default()
+        }
+    }
+    fn borrow_app_state_url(&self) -> &str {
+        &self.url
+    }
+    fn app_state_format(&self) -> StateFormat {
+        self.format
+    }
+    #[cfg(feature = "encryption")]
+    fn app_state_encryption_key(&self) -> Option<[u8; 32]> {
+        self.encryption_key
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct TestAppState {
+    pub(crate) items: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) notes: Vec<String>,
+    #[serde(skip)]
+    pub(crate) dirty_flag: DirtyFlag,
+}
+impl AppState for TestAppState {
+    fn new<AC: AppConfig>(_app_config: &AC) -> Self {
+        Self::default()
+    }
+    fn dirty_flag_value(&self) -> bool {
+        self.dirty_flag.is_dirty()
+    }
+    fn mark_dirty(&self) {
+        self.dirty_flag.mark_dirty();
+    }
+    fn mark_persisted(&self) {
+        self.dirty_flag.mark_persisted();
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct TestProcessingError(pub(crate) String, pub(crate) String);
+impl NotPersistedError for TestProcessingError {
+    fn not_persisted(error: String, url: String) -> Self {
+        TestProcessingError(error, url)
+    }
+}
+
+/// an app config pointing to `file_name` in an empty temp directory
+pub(crate) fn test_app_config(test_name: &str, file_name: &str) -> TestAppConfig {
+    let dir = std::env::temp_dir().join(format!(
+        "generate_cqrs_api_{}_{test_name}",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_dir_all(&dir);
+    TestAppConfig::new(Some(
+        dir.join("nested")
+            .join(file_name)
+            .to_string_lossy()
+            .to_string(),
+    ))
+}
@@ -0,0 +1,141 @@
+use std::{
+    collections::VecDeque,
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+/// the bounded history of model states used by the generated undo/redo (option `undo`).
+/// Before a state changing command the generated code records the model's prior state, `S` is the generated `ModelSnapshot`.
+/// Undoing swaps the recorded state back into its model and keeps the replaced one for redoing.
+#[derive(Debug)]
+pub struct UndoHistory<S> {
+    limit: usize,
+    stacks: Mutex<UndoStacks<S>>,
+}
+
+#[derive(Debug)]
+struct UndoStacks<S> {
+    /// the newest snapshot is at the back
+    undo: VecDeque<S>,
+    redo: Vec<S>,
+}
+
+impl<S> UndoHistory<S> {
+    /// @param limit: how many commands can be undone, older ones are dropped
+    pub fn new(limit: usize) -> Self {
+        UndoHistory {
+            limit: limit.max(1),
+            stacks: Mutex::new(UndoStacks {
+                undo: VecDeque::new(),
+                redo: Vec::new(),
+            }),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, UndoStacks<S>> {
+        self.stacks.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// records the state before a command changed it. A new command can't be redone after, so the redo history is cleared.
+    pub fn record(&self, snapshot: S) {
+        let mut stacks = self.lock();
+        if stacks.undo.len() == self.limit {
+            stacks.undo.pop_front();
+        }
+        stacks.undo.push_back(snapshot);
+        stacks.redo.clear();
+    }
+
+    /// passes the newest recorded state to `restore`, which swaps it into its model
+    /// and returns the replaced state (kept for `redo()`) along with its result.
+    /// @returns None, if there is nothing to undo
+    pub fn undo<R>(&self, restore: impl FnOnce(S) -> (S, R)) -> Option<R> {
+        let mut stacks = self.lock();
+        let snapshot = stacks.undo.pop_back()?;
+        let (replaced, result) = restore(snapshot);
+        stacks.redo.push(replaced);
+        Some(result)
+    }
+
+    /// like `undo()`, for the state last replaced by `undo()`
+    pub fn redo<R>(&self, restore: impl FnOnce(S) -> (S, R)) -> Option<R> {
+        let mut stacks = self.lock();
+        let snapshot = stacks.redo.pop()?;
+        let (replaced, result) = restore(snapshot);
+        stacks.undo.push_back(replaced);
+        Some(result)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.lock().undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.lock().redo.is_empty()
+    }
+
+    pub fn clear(&self) {
+        let mut stacks = self.lock();
+        stacks.undo.clear();
+        stacks.redo.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::UndoHistory;
+
+    /// swaps the snapshot into the model, returning the model's previous value
+    fn swap_into(model: &mut u32) -> impl FnOnce(u32) -> (u32, u32) + '_ {
+        |snapshot| {
+            let replaced = std::mem::replace(model, snapshot);
+            (replaced, snapshot)
+        }
+    }
+
+    #[test]
+    fn undo_and_redo() {
+        let history = UndoHistory::new(10);
+        let mut model = 0;
+        for value in 1..=3 {
+            history.record(model);
+            model = value;
+        }
+        assert_eq!(Some(2), history.undo(swap_into(&mut model)));
+        assert_eq!(Some(1), history.undo(swap_into(&mut model)));
+        assert_eq!(Some(2), history.redo(swap_into(&mut model)));
+        assert_eq!(2, model);
+        assert!(history.can_undo());
+        assert!(history.can_redo());
+    }
+    #[test]
+    fn nothing_to_undo() {
+        let history = UndoHistory::<u32>::new(10);
+        let mut model = 0;
+        assert_eq!(None, history.undo(swap_into(&mut model)));
+        assert_eq!(None, history.redo(swap_into(&mut model)));
+    }
+    #[test]
+    fn drop_oldest_beyond_limit() {
+        let history = UndoHistory::new(2);
+        let mut model = 0;
+        for value in 1..=3 {
+            history.record(model);
+            model = value;
+        }
+        assert_eq!(Some(2), history.undo(swap_into(&mut model)));
+        assert_eq!(Some(1), history.undo(swap_into(&mut model)));
+        assert_eq!(None, history.undo(swap_into(&mut model)));
+        assert_eq!(1, model);
+    }
+    #[test]
+    fn new_command_clears_redo() {
+        let history = UndoHistory::new(10);
+        let mut model = 0;
+        history.record(model);
+        model = 1;
+        history.undo(swap_into(&mut model));
+        history.record(model);
+        assert!(!history.can_redo());
+        assert_eq!(None, history.redo(swap_into(&mut model)));
+    }
+}
@@ -0,0 +1,123 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt::Display,
+};
+
+/// the length checked by `#[validate(length(min = 1, max = 200))]`
+pub trait ValidatedLength {
+    fn validated_length(&self) -> usize;
+}
+
+impl ValidatedLength for str {
+    /// counts the characters, not the bytes
+    fn validated_length(&self) -> usize {
+        self.chars().count()
+    }
+}
+
+impl ValidatedLength for String {
+    fn validated_length(&self) -> usize {
+        self.as_str().validated_length()
+    }
+}
+
+impl<T> ValidatedLength for [T] {
+    fn validated_length(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<T> ValidatedLength for Vec<T> {
+    fn validated_length(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<T, S> ValidatedLength for HashSet<T, S> {
+    fn validated_length(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<K, V, S> ValidatedLength for HashMap<K, V, S> {
+    fn validated_length(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<T> ValidatedLength for BTreeSet<T> {
+    fn validated_length(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<K, V> ValidatedLength for BTreeMap<K, V> {
+    fn validated_length(&self) -> usize {
+        self.len()
+    }
+}
+
+/// checks `#[validate(range(min = 1, max = 10))]`, the bounds are inclusive
+/// @returns the reason, why the value is invalid
+pub fn check_range<T: PartialOrd + Display>(
+    value: &T,
+    min: Option<T>,
+    max: Option<T>,
+) -> Result<(), String> {
+    match (min, max) {
+        (Some(min), _) if *value < min => Err(format!("{value} is less than the minimum {min}")),
+        (_, Some(max)) if *value > max => Err(format!("{value} is greater than the maximum {max}")),
+        _ => Ok(()),
+    }
+}
+
+/// checks `#[validate(length(min = 1, max = 200))]`, the bounds are inclusive
+/// @returns the reason, why the value is invalid
+pub fn check_length<T: ValidatedLength + ?Sized>(
+    value: &T,
+    min: Option<usize>,
+    max: Option<usize>,
+) -> Result<(), String> {
+    let length = value.validated_length();
+    match (min, max) {
+        (Some(min), _) if length < min => Err(format!(
+            "the length {length} is less than the minimum {min}"
+        )),
+        (_, Some(max)) if length > max => Err(format!(
+            "the length {length} is greater than the maximum {max}"
+        )),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_length, check_range};
+
+    #[test]
+    fn check_inclusive_range() {
+        assert_eq!(Ok(()), check_range(&1, Some(1), Some(3)));
+        assert_eq!(Ok(()), check_range(&3, Some(1), Some(3)));
+        assert_eq!(Ok(()), check_range(&-5, None, Some(3)));
+        assert_eq!(
+            Err("0 is less than the minimum 1".to_string()),
+            check_range(&0, Some(1), None)
+        );
+        assert_eq!(
+            Err("3.5 is greater than the maximum 3".to_string()),
+            check_range(&3.5, None, Some(3.0))
+        );
+    }
+    #[test]
+    fn check_length_of_chars_and_collections() {
+        assert_eq!(Ok(()), check_length("äöü", None, Some(3)));
+        assert_eq!(
+            Err("the length 0 is less than the minimum 1".to_string()),
+            check_length(&String::new(), Some(1), None)
+        );
+        assert_eq!(
+            Err("the length 3 is greater than the maximum 2".to_string()),
+            check_length(&vec![1, 2, 3], Some(1), Some(2))
+        );
+    }
+}
@@ -0,0 +1,313 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{
+    parse::{Parse, ParseStream},
+    parse2,
+    punctuated::Punctuated,
+    Attribute, Expr, ExprLit, FnArg, Ident, ImplItemFn, Lit, LitStr, Meta, Pat, Path, Result,
+    Token,
+};
+
+const USAGE: &str = r#"Use like #[cqrs(requires = "admin")]
+    requires = "<the role the Authorizer has to grant, to call the command or query>" (optional)"#;
+
+const VALIDATE_USAGE: &str = r#"Use like #[validate(range(min = 1), length(max = 200), with = check_fn)]
+    range(min = <value>, max = <value>), the inclusive bounds of the argument,
+    length(min = <number>, max = <number>), the inclusive bounds of the argument's length,
+    with = <a fn(&Argument) -> Result<(), String>>"#;
+
+/// the arguments passed to the cqrs macro, like
+/// #[cqrs(requires = "admin")]
+struct CqrsArgs {
+    requires: Option<LitStr>,
+}
+
+impl Parse for CqrsArgs {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let mut requires = None;
+        for meta in Punctuated::<Meta, Token![,]>::parse_terminated(input)? {
+            match &meta {
+                Meta::NameValue(name_value) if name_value.path.is_ident("requires") => {
+                    requires = match &name_value.value {
+                        Expr::Lit(ExprLit {
+                            lit: Lit::Str(role),
+                            ..
+                        }) if !role.value().is_empty() => Some(role.to_owned()),
+                        _ => {
+                            return Err(syn::Error::new_spanned(
+                                &name_value.value,
+                                "Expected the role as string, like 'requires = \"admin\"'",
+                            ))
+                        }
+                    }
+                }
+                _ => return Err(syn::Error::new_spanned(meta, USAGE)),
+            }
+        }
+        Ok(CqrsArgs { requires })
+    }
+}
+
+/// a rule of `#[validate(...)]` on an argument of a CQRS function
+pub(crate) enum ValidationRule {
+    /// `range(min = 1, max = 10)`, either bound can be omitted
+    Range {
+        min: Option<Expr>,
+        max: Option<Expr>,
+    },
+    /// `length(min = 1, max = 200)`, either bound can be omitted
+    Length {
+        min: Option<Expr>,
+        max: Option<Expr>,
+    },
+    /// `with = check_fn`, a `fn(&Argument) -> Result<(), String>`
+    With(Path),
+}
+
+/// the rules of an argument annotated with `#[validate(...)]`
+pub(crate) struct ArgumentValidation {
+    pub(crate) argument: Ident,
+    pub(crate) rules: Vec<ValidationRule>,
+}
+
+/// the attributes of a CQRS function: `#[cqrs(requires = "admin")]` and its arguments' `#[validate(...)]`
+#[derive(Default)]
+pub(crate) struct CqrsFnAttributes {
+    pub(crate) required_role: Option<LitStr>,
+    pub(crate) validations: Vec<ArgumentValidation>,
+}
+
+/// checks the arguments of `#[cqrs(...)]` on a lock's function and of `#[validate(...)]` on the function's arguments.
+/// Returns the function without the `#[validate(...)]` attributes, the generated `process()` reads them from the model's file.
+pub fn generate_cqrs_attribute_impl(
+    item: TokenStream,
+    macro_args: TokenStream,
+) -> Result<TokenStream> {
+    parse2::<CqrsArgs>(macro_args)?;
+    let mut function = parse2::<ImplItemFn>(item)?;
+    get_argument_validations(&function)?;
+    for input in function.sig.inputs.iter_mut() {
+        if let FnArg::Typed(pat_type) = input {
+            pat_type
+                .attrs
+                .retain(|attr| !attr.path().is_ident("validate"));
+        }
+    }
+    Ok(quote! { #function })
+}
+
+/// @returns the role of `#[cqrs(requires = "admin")]` and the rules of the arguments' `#[validate(...)]`.
+/// Invalid attributes are ignored, as `#[cqrs]` reports them.
+pub(crate) fn get_cqrs_fn_attributes(function: &ImplItemFn) -> CqrsFnAttributes {
+    CqrsFnAttributes {
+        required_role: function
+            .attrs
+            .iter()
+            .find(|attr| attr.path().is_ident("cqrs"))
+            .and_then(|attr| match &attr.meta {
+                // `#[cqrs]` without arguments
+                Meta::Path(_) => None,
+                _ => attr.parse_args::<CqrsArgs>().ok()?.requires,
+            }),
+        validations: get_argument_validations(function).unwrap_or_default(),
+    }
+}
+
+fn get_argument_validations(function: &ImplItemFn) -> Result<Vec<ArgumentValidation>> {
+    let mut validations = vec![];
+    for input in &function.sig.inputs {
+        let FnArg::Typed(pat_type) = input else {
+            continue;
+        };
+        let mut rules = vec![];
+        for attr in pat_type
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("validate"))
+        {
+            rules.extend(get_validation_rules(attr)?);
+        }
+        if rules.is_empty() {
+            continue;
+        }
+        let Pat::Ident(pat_ident) = &*pat_type.pat else {
+            return Err(syn::Error::new_spanned(
+                &pat_type.pat,
+                "#[validate(...)] needs a named argument",
+            ));
+        };
+        validations.push(ArgumentValidation {
+            argument: pat_ident.ident.to_owned(),
+            rules,
+        });
+    }
+    Ok(validations)
+}
+
+/// parses `#[validate(range(min = 1), length(max = 200), with = check_fn)]`
+fn get_validation_rules(attr: &Attribute) -> Result<Vec<ValidationRule>> {
+    attr.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)?
+        .iter()
+        .map(|meta| match meta {
+            Meta::List(list) if list.path.is_ident("range") || list.path.is_ident("length") => {
+                let (mut min, mut max) = (None, None);
+                for bound in
+                    list.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)?
+                {
+                    match bound {
+                        Meta::NameValue(name_value) if name_value.path.is_ident("min") => {
+                            min = Some(name_value.value)
+                        }
+                        Meta::NameValue(name_value) if name_value.path.is_ident("max") => {
+                            max = Some(name_value.value)
+                        }
+                        _ => return Err(syn::Error::new_spanned(bound, VALIDATE_USAGE)),
+                    }
+                }
+                if min.is_none() && max.is_none() {
+                    return Err(syn::Error::new_spanned(
+                        list,
+                        "Expected at least one bound, like 'range(min = 1)'",
+                    ));
+                }
+                Ok(if list.path.is_ident("range") {
+                    ValidationRule::Range { min, max }
+                } else {
+                    ValidationRule::Length { min, max }
+                })
+            }
+            Meta::NameValue(name_value) if name_value.path.is_ident("with") => {
+                match &name_value.value {
+                    Expr::Path(expr_path) => Ok(ValidationRule::With(expr_path.path.to_owned())),
+                    _ => Err(syn::Error::new_spanned(
+                        &name_value.value,
+                        "Expected the validating fn, like 'with = check_fn'",
+                    )),
+                }
+            }
+            _ => Err(syn::Error::new_spanned(meta, VALIDATE_USAGE)),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::quote;
+    use syn::parse_quote;
+
+    use super::{generate_cqrs_attribute_impl, get_cqrs_fn_attributes, ValidationRule};
+
+    #[test]
+    fn keep_the_function() {
+        let function = quote! {
+            pub(crate) fn clean_list(&self) -> Result<(bool, Vec<MyModelEffect>), MyModelError> {
+                Ok((true, vec![]))
+            }
+        };
+        let result =
+            generate_cqrs_attribute_impl(function.clone(), quote! { requires = "admin" }).unwrap();
+        assert_eq!(function.to_string(), result.to_string());
+    }
+    #[test]
+    fn remove_the_validate_attributes() {
+        let result = generate_cqrs_attribute_impl(
+            quote! {
+                pub(crate) fn add_item(
+                    &self,
+                    #[validate(length(min = 1, max = 200), with = no_tabs)] item: String,
+                    #[validate(range(min = 1))] position: usize,
+                ) -> Result<(bool, Vec<MyModelEffect>), MyModelError> {
+                    Ok((true, vec![]))
+                }
+            },
+            quote! {},
+        )
+        .unwrap();
+        let expected = quote! {
+            pub(crate) fn add_item(
+                &self,
+                item: String,
+                position: usize,
+            ) -> Result<(bool, Vec<MyModelEffect>), MyModelError> {
+                Ok((true, vec![]))
+            }
+        };
+        assert_eq!(expected.to_string(), result.to_string());
+    }
+    #[test]
+    fn get_the_attributes() {
+        let function = parse_quote! {
+            #[cqrs(requires = "admin")]
+            pub(crate) fn add_item(
+                &self,
+                #[validate(length(max = 200), with = no_tabs)] item: String,
+                position: usize,
+            ) -> Result<(bool, Vec<MyModelEffect>), MyModelError> {
+                Ok((true, vec![]))
+            }
+        };
+        let attributes = get_cqrs_fn_attributes(&function);
+        assert_eq!(
+            Some("admin".to_string()),
+            attributes.required_role.map(|role| role.value())
+        );
+        assert_eq!(1, attributes.validations.len());
+        let validation = &attributes.validations[0];
+        assert_eq!("item", validation.argument.to_string());
+        assert!(matches!(
+            validation.rules[..],
+            [
+                ValidationRule::Length {
+                    min: None,
+                    max: Some(_)
+                },
+                ValidationRule::With(_)
+            ]
+        ));
+        let function = parse_quote! {
+            #[cqrs]
+            pub(crate) fn clean_list(&self) -> Result<(bool, Vec<MyModelEffect>), MyModelError> {
+                Ok((true, vec![]))
+            }
+        };
+        let attributes = get_cqrs_fn_attributes(&function);
+        assert!(attributes.required_role.is_none());
+        assert!(attributes.validations.is_empty());
+    }
+    #[test]
+    fn fail_invalid_role() {
+        let function = quote! { fn clean_list(&self) {} };
+        assert!(
+            generate_cqrs_attribute_impl(function.clone(), quote! { role = "admin" })
+                .unwrap_err()
+                .to_string()
+                .starts_with("Use like #[cqrs(")
+        );
+        assert_eq!(
+            "Expected the role as string, like 'requires = \"admin\"'",
+            generate_cqrs_attribute_impl(function, quote! { requires = admin })
+                .unwrap_err()
+                .to_string()
+        );
+    }
+    #[test]
+    fn fail_invalid_validation() {
+        let fail = |function| {
+            generate_cqrs_attribute_impl(function, quote! {})
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(
+            "Expected at least one bound, like 'range(min = 1)'",
+            fail(quote! { fn remove_item(&self, #[validate(range())] position: usize) {} })
+        );
+        assert!(
+            fail(quote! { fn remove_item(&self, #[validate(positive)] position: usize) {} })
+                .starts_with("Use like #[validate(")
+        );
+        assert_eq!(
+            "Expected the validating fn, like 'with = check_fn'",
+            fail(quote! { fn remove_item(&self, #[validate(with = "check")] position: usize) {} })
+        );
+    }
+}
@@ -0,0 +1,386 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::{
+    parse::{Parse, ParseStream, Parser},
+    parse2,
+    punctuated::Punctuated,
+    Expr, Fields, GenericArgument, ItemStruct, Meta, Path, PathArguments, Result, Token, Type,
+};
+
+const USAGE: &str = r#"Use like #[cqrs_lock(model = MyModel, inner = RustAutoOpaque, custom_serde)]
+    model = <the CqrsModel>, not needed if the struct's field is like 'lock: RustAutoOpaque<MyModel>',
+    inner = <the lock type, default: RustAutoOpaque>, not needed if the struct has a field,
+    custom_serde (optional: don't generate Serialize and Deserialize)"#;
+
+/// the arguments passed to the cqrs_lock macro, like
+/// #[cqrs_lock(model = MyModel, inner = RustAutoOpaque, custom_serde)]
+#[derive(Default)]
+struct CqrsLockArgs {
+    model: Option<Path>,
+    inner: Option<Path>,
+    custom_serde: bool,
+}
+
+impl Parse for CqrsLockArgs {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let mut args = CqrsLockArgs::default();
+        for meta in Punctuated::<Meta, Token![,]>::parse_terminated(input)? {
+            match &meta {
+                Meta::Path(path) if path.is_ident("custom_serde") => args.custom_serde = true,
+                Meta::NameValue(name_value) if name_value.path.is_ident("model") => {
+                    args.model = Some(get_expr_path(&name_value.value)?)
+                }
+                Meta::NameValue(name_value) if name_value.path.is_ident("inner") => {
+                    args.inner = Some(get_expr_path(&name_value.value)?)
+                }
+                _ => return Err(syn::Error::new_spanned(meta, USAGE)),
+            }
+        }
+        Ok(args)
+    }
+}
+
+fn get_expr_path(expr: &Expr) -> Result<Path> {
+    match expr {
+        Expr::Path(expr_path) => Ok(expr_path.path.to_owned()),
+        _ => Err(syn::Error::new_spanned(
+            expr,
+            "Expected a type, like MyModel",
+        )),
+    }
+}
+
+/// generates the boilerplate of a CqrsModelLock:
+/// `impl CqrsModelLock`, `SwappableModelLock`, `Serialize`, `Deserialize` and the `From` conversions from and to the model.
+/// A struct without fields gets the field `lock: RustAutoOpaque<MyModel>`.
+/// Every lock gets the field `commands_lock`, returned by `SwappableModelLock::lock_commands()`.
+pub fn generate_cqrs_lock_impl(item: TokenStream, macro_args: TokenStream) -> Result<TokenStream> {
+    let args = parse2::<CqrsLockArgs>(macro_args)?;
+    let mut item_struct = parse2::<ItemStruct>(item)?;
+    if !item_struct.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &item_struct.generics,
+            "A cqrs_lock can't have generics!",
+        ));
+    }
+    let lock_ident = item_struct.ident.clone();
+
+    let (lock_field, model) = match &item_struct.fields {
+        Fields::Named(fields) if fields.named.len() == 1 => {
+            let field = fields.named.first().expect("checked above");
+            let model = match &args.model {
+                Some(model) => model.to_owned(),
+                None => get_inner_type_argument(&field.ty)?,
+            };
+            (field.ident.clone().expect("named field"), model)
+        }
+        Fields::Unit => (format_ident!("lock"), get_model_arg(&args)?),
+        Fields::Named(fields) if fields.named.is_empty() => {
+            (format_ident!("lock"), get_model_arg(&args)?)
+        }
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &item_struct,
+                "A cqrs_lock needs exactly one named field holding the model's lock, like 'lock: RustAutoOpaque<MyModel>' - or no field at all.",
+            ))
+        }
+    };
+    if item_struct.fields.is_empty() {
+        let inner = args
+            .inner
+            .clone()
+            .unwrap_or_else(|| syn::parse_quote!(RustAutoOpaque));
+        item_struct.semi_token = None;
+        item_struct.fields = Fields::Named(syn::parse_quote!({
+            pub(crate) #lock_field: #inner<#model>
+        }));
+    }
+    let inner_constructor = match &item_struct.fields {
+        Fields::Named(fields) => {
+            let field_type = &fields.named.first().expect("one field").ty;
+            get_type_constructor(field_type)?
+        }
+        _ => unreachable!("unit structs are given a named field above"),
+    };
+    if let Fields::Named(fields) = &mut item_struct.fields {
+        fields.named.push(syn::Field::parse_named.parse2(quote! {
+            /// shared by the lock's clones, like the model
+            commands_lock: generate_cqrs_api::CommandsLock
+        })?);
+    }
+
+    let generated_serde = if args.custom_serde {
+        quote! {}
+    } else {
+        quote! {
+            impl serde::Serialize for #lock_ident {
+                fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+                where
+                    S: serde::Serializer,
+                {
+                    generate_cqrs_api::serialize_versioned(&*self.#lock_field.blocking_read(), serializer)
+                }
+            }
+
+            impl<'de> serde::Deserialize<'de> for #lock_ident {
+                fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+                where
+                    D: serde::Deserializer<'de>,
+                {
+                    let model = generate_cqrs_api::deserialize_versioned::<#model, D>(deserializer)?;
+                    Ok(<Self as CqrsModelLock<#model>>::for_model(model))
+                }
+            }
+        }
+    };
+
+    Ok(quote! {
+        #item_struct
+
+        impl CqrsModelLock<#model> for #lock_ident {
+            fn for_model(model: #model) -> Self {
+                Self {
+                    #lock_field: #inner_constructor::new(model),
+                    commands_lock: Default::default(),
+                }
+            }
+        }
+
+        impl From<#model> for #lock_ident {
+            fn from(model: #model) -> Self {
+                <Self as CqrsModelLock<#model>>::for_model(model)
+            }
+        }
+
+        impl From<#lock_ident> for #model {
+            /// takes the model out of the lock, leaving the default model in other clones of the lock
+            fn from(lock: #lock_ident) -> Self {
+                std::mem::take(&mut *lock.#lock_field.blocking_write())
+            }
+        }
+
+        impl generate_cqrs_api::SwappableModelLock<#model> for #lock_ident {
+            fn read_model<R>(&self, read: impl FnOnce(&#model) -> R) -> R {
+                read(&self.#lock_field.blocking_read())
+            }
+            fn replace_model(&self, model: #model) -> #model {
+                std::mem::replace(&mut *self.#lock_field.blocking_write(), model)
+            }
+            fn lock_commands(&self) -> std::sync::MutexGuard<'_, ()> {
+                self.commands_lock.lock()
+            }
+        }
+
+        #generated_serde
+    })
+}
+
+fn get_model_arg(args: &CqrsLockArgs) -> Result<Path> {
+    args.model.clone().ok_or_else(|| {
+        syn::Error::new(
+            Span::call_site(),
+            "A cqrs_lock without a field needs the model, like #[cqrs_lock(model = MyModel)]",
+        )
+    })
+}
+
+/// gets MyModel from RustAutoOpaque<MyModel>
+fn get_inner_type_argument(tipe: &Type) -> Result<Path> {
+    let error = || {
+        syn::Error::new_spanned(
+            tipe,
+            "Can't get the model from the lock's type. Use a type like 'RustAutoOpaque<MyModel>' or set #[cqrs_lock(model = MyModel)]",
+        )
+    };
+    let Type::Path(type_path) = tipe else {
+        return Err(error());
+    };
+    let last_segment = type_path.path.segments.last().ok_or_else(error)?;
+    match &last_segment.arguments {
+        PathArguments::AngleBracketed(arguments) => match arguments.args.first() {
+            Some(GenericArgument::Type(Type::Path(model))) => Ok(model.path.to_owned()),
+            _ => Err(error()),
+        },
+        _ => Err(error()),
+    }
+}
+
+/// gets the path to call `::new()` on, e.g. RustAutoOpaque from RustAutoOpaque<MyModel>
+fn get_type_constructor(tipe: &Type) -> Result<Path> {
+    let Type::Path(type_path) = tipe else {
+        return Err(syn::Error::new_spanned(
+            tipe,
+            "The lock's type needs to be like 'RustAutoOpaque<MyModel>'",
+        ));
+    };
+    let mut constructor = type_path.path.to_owned();
+    if let Some(last_segment) = constructor.segments.last_mut() {
+        last_segment.arguments = PathArguments::None;
+    }
+    Ok(constructor)
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::quote;
+
+    use super::generate_cqrs_lock_impl;
+
+    #[test]
+    fn generate_cqrs_lock_from_field() {
+        let item = quote! {
+            #[derive(Debug, Clone, Default)]
+            pub struct MyModelLock {
+                pub(crate) lock: RustAutoOpaque<MyModel>,
+            }
+        };
+        let result = generate_cqrs_lock_impl(item, quote! {}).unwrap();
+        let expected = quote! {
+            #[derive(Debug, Clone, Default)]
+            pub struct MyModelLock {
+                pub(crate) lock: RustAutoOpaque<MyModel>,
+                /// shared by the lock's clones, like the model
+                commands_lock: generate_cqrs_api::CommandsLock
+            }
+
+            impl CqrsModelLock<MyModel> for MyModelLock {
+                fn for_model(model: MyModel) -> Self {
+                    Self {
+                        lock: RustAutoOpaque::new(model),
+                        commands_lock: Default::default(),
+                    }
+                }
+            }
+
+            impl From<MyModel> for MyModelLock {
+                fn from(model: MyModel) -> Self {
+                    <Self as CqrsModelLock<MyModel>>::for_model(model)
+                }
+            }
+
+            impl From<MyModelLock> for MyModel {
+                /// takes the model out of the lock, leaving the default model in other clones of the lock
+                fn from(lock: MyModelLock) -> Self {
+                    std::mem::take(&mut *lock.lock.blocking_write())
+                }
+            }
+
+            impl generate_cqrs_api::SwappableModelLock<MyModel> for MyModelLock {
+                fn read_model<R>(&self, read: impl FnOnce(&MyModel) -> R) -> R {
+                    read(&self.lock.blocking_read())
+                }
+                fn replace_model(&self, model: MyModel) -> MyModel {
+                    std::mem::replace(&mut *self.lock.blocking_write(), model)
+                }
+                fn lock_commands(&self) -> std::sync::MutexGuard<'_, ()> {
+                    self.commands_lock.lock()
+                }
+            }
+
+            impl serde::Serialize for MyModelLock {
+                fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+                where
+                    S: serde::Serializer,
+                {
+                    generate_cqrs_api::serialize_versioned(&*self.lock.blocking_read(), serializer)
+                }
+            }
+
+            impl<'de> serde::Deserialize<'de> for MyModelLock {
+                fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+                where
+                    D: serde::Deserializer<'de>,
+                {
+                    let model = generate_cqrs_api::deserialize_versioned::<MyModel, D>(deserializer)?;
+                    Ok(<Self as CqrsModelLock<MyModel>>::for_model(model))
+                }
+            }
+        };
+        assert_eq!(expected.to_string(), result.to_string());
+    }
+    #[test]
+    fn generate_cqrs_lock_field_with_custom_serde() {
+        let item = quote! {
+            #[derive(Debug, Clone, Default)]
+            pub struct MyModelLock;
+        };
+        let result = generate_cqrs_lock_impl(
+            item,
+            quote! {model = MyModel, inner = frb::RustAutoOpaque, custom_serde},
+        )
+        .unwrap();
+        let expected = quote! {
+            #[derive(Debug, Clone, Default)]
+            pub struct MyModelLock {
+                pub(crate) lock: frb::RustAutoOpaque<MyModel>,
+                /// shared by the lock's clones, like the model
+                commands_lock: generate_cqrs_api::CommandsLock
+            }
+
+            impl CqrsModelLock<MyModel> for MyModelLock {
+                fn for_model(model: MyModel) -> Self {
+                    Self {
+                        lock: frb::RustAutoOpaque::new(model),
+                        commands_lock: Default::default(),
+                    }
+                }
+            }
+
+            impl From<MyModel> for MyModelLock {
+                fn from(model: MyModel) -> Self {
+                    <Self as CqrsModelLock<MyModel>>::for_model(model)
+                }
+            }
+
+            impl From<MyModelLock> for MyModel {
+                /// takes the model out of the lock, leaving the default model in other clones of the lock
+                fn from(lock: MyModelLock) -> Self {
+                    std::mem::take(&mut *lock.lock.blocking_write())
+                }
+            }
+
+            impl generate_cqrs_api::SwappableModelLock<MyModel> for MyModelLock {
+                fn read_model<R>(&self, read: impl FnOnce(&MyModel) -> R) -> R {
+                    read(&self.lock.blocking_read())
+                }
+                fn replace_model(&self, model: MyModel) -> MyModel {
+                    std::mem::replace(&mut *self.lock.blocking_write(), model)
+                }
+                fn lock_commands(&self) -> std::sync::MutexGuard<'_, ()> {
+                    self.commands_lock.lock()
+                }
+            }
+        };
+        assert_eq!(expected.to_string(), result.to_string());
+    }
+    #[test]
+    fn fail_cqrs_lock_without_model() {
+        let error =
+            generate_cqrs_lock_impl(quote! { pub struct MyModelLock {} }, quote! {}).unwrap_err();
+        assert_eq!(
+            "A cqrs_lock without a field needs the model, like #[cqrs_lock(model = MyModel)]",
+            error.to_string()
+        );
+    }
+    #[test]
+    fn fail_cqrs_lock_with_two_fields() {
+        let item = quote! {
+            pub struct MyModelLock {
+                lock: RustAutoOpaque<MyModel>,
+                other: bool,
+            }
+        };
+        assert!(generate_cqrs_lock_impl(item, quote! {})
+            .unwrap_err()
+            .to_string()
+            .starts_with("A cqrs_lock needs exactly one named field"));
+    }
+    #[test]
+    fn fail_cqrs_lock_unknown_arg() {
+        let item = quote! { pub struct MyModelLock; };
+        assert!(generate_cqrs_lock_impl(item, quote! {serde = "custom"})
+            .unwrap_err()
+            .to_string()
+            .starts_with("Use like #[cqrs_lock("));
+    }
+}
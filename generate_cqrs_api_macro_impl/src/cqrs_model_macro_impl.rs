@@ -0,0 +1,154 @@
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::{
+    parse::{Parse, ParseStream},
+    parse2, parse_quote,
+    punctuated::Punctuated,
+    Expr, ExprLit, ItemImpl, Lit, Meta, Path, Result, Token,
+};
+
+const USAGE: &str = r#"Use like #[cqrs_model(version = 3, migrations(migrate_v1_to_v2, migrate_v2_to_v3))]
+    version = <the model's current schema version, default: 1>,
+    migrations(<one fn(serde_json::Value) -> serde_json::Value per version, migrating to the next one>)"#;
+
+/// the arguments passed to the cqrs_model macro, like
+/// #[cqrs_model(version = 2, migrations(migrate_v1_to_v2))]
+struct CqrsModelArgs {
+    version: u32,
+    migrations: Vec<Path>,
+}
+
+impl Parse for CqrsModelArgs {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let mut args = CqrsModelArgs {
+            version: 1,
+            migrations: vec![],
+        };
+        for meta in Punctuated::<Meta, Token![,]>::parse_terminated(input)? {
+            match &meta {
+                Meta::NameValue(name_value) if name_value.path.is_ident("version") => {
+                    args.version = match &name_value.value {
+                        Expr::Lit(ExprLit {
+                            lit: Lit::Int(version),
+                            ..
+                        }) => version.base10_parse::<u32>()?,
+                        _ => {
+                            return Err(syn::Error::new_spanned(
+                                &name_value.value,
+                                "Expected the version as number, like 'version = 2'",
+                            ))
+                        }
+                    }
+                }
+                Meta::List(list) if list.path.is_ident("migrations") => {
+                    args.migrations = list
+                        .parse_args_with(Punctuated::<Path, Token![,]>::parse_terminated)?
+                        .into_iter()
+                        .collect();
+                }
+                _ => return Err(syn::Error::new_spanned(meta, USAGE)),
+            }
+        }
+        Ok(args)
+    }
+}
+
+/// sets the schema version and the migrations of the annotated `impl CqrsModel`.
+/// `generate_api` checks the invariants of the models annotated with it, which implement `check_invariants()`.
+pub fn generate_cqrs_model_impl(item: TokenStream, macro_args: TokenStream) -> Result<TokenStream> {
+    let args = parse2::<CqrsModelArgs>(macro_args)?;
+    let mut item_impl = parse2::<ItemImpl>(item)?;
+    let is_cqrs_model_impl = item_impl.trait_.as_ref().is_some_and(|(_, trait_path, _)| {
+        trait_path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "CqrsModel")
+    });
+    if !is_cqrs_model_impl {
+        return Err(syn::Error::new_spanned(
+            &item_impl.self_ty,
+            "#[cqrs_model] has to be declared on an 'impl CqrsModel for'!",
+        ));
+    }
+    if args.version == 0 {
+        return Err(syn::Error::new(
+            Span::call_site(),
+            "The schema version starts with 1",
+        ));
+    }
+    if args.migrations.len() != args.version as usize - 1 {
+        return Err(syn::Error::new(
+            Span::call_site(),
+            format!(
+                "Version {} needs {} migration(s), one from each previous version to the next, but got {}",
+                args.version,
+                args.version - 1,
+                args.migrations.len()
+            ),
+        ));
+    }
+    let version = args.version;
+    let migrations = &args.migrations;
+    item_impl.items.push(parse_quote! {
+        const SCHEMA_VERSION: u32 = #version;
+    });
+    item_impl.items.push(parse_quote! {
+        const MIGRATIONS: &'static [generate_cqrs_api::Migration] = &[#(#migrations),*];
+    });
+    Ok(quote! { #item_impl })
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::quote;
+
+    use super::generate_cqrs_model_impl;
+
+    #[test]
+    fn generate_schema_version_and_migrations() {
+        let result = generate_cqrs_model_impl(
+            quote! { impl CqrsModel for MyModel {} },
+            quote! { version = 3, migrations(migrate_v1_to_v2, migrations::migrate_v2_to_v3) },
+        )
+        .unwrap();
+        let expected = quote! {
+            impl CqrsModel for MyModel {
+                const SCHEMA_VERSION: u32 = 3u32;
+                const MIGRATIONS: &'static [generate_cqrs_api::Migration] =
+                    &[migrate_v1_to_v2, migrations::migrate_v2_to_v3];
+            }
+        };
+        assert_eq!(expected.to_string(), result.to_string());
+    }
+    #[test]
+    fn fail_missing_migration() {
+        let error = generate_cqrs_model_impl(
+            quote! { impl CqrsModel for MyModel {} },
+            quote! { version = 3, migrations(migrate_v1_to_v2) },
+        )
+        .unwrap_err();
+        assert_eq!(
+            "Version 3 needs 2 migration(s), one from each previous version to the next, but got 1",
+            error.to_string()
+        );
+    }
+    #[test]
+    fn fail_on_other_impl() {
+        let error = generate_cqrs_model_impl(quote! { impl MyModel {} }, quote! { version = 1 })
+            .unwrap_err();
+        assert_eq!(
+            "#[cqrs_model] has to be declared on an 'impl CqrsModel for'!",
+            error.to_string()
+        );
+    }
+    #[test]
+    fn fail_unknown_arg() {
+        assert!(generate_cqrs_model_impl(
+            quote! { impl CqrsModel for MyModel {} },
+            quote! { schema = 2 }
+        )
+        .unwrap_err()
+        .to_string()
+        .starts_with("Use like #[cqrs_model("));
+    }
+}
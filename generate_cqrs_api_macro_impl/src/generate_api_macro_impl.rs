@@ -1,19 +1,30 @@
 use log::debug;
 
-use crate::generating::generate_cqrs_impl::generate_cqrs_impl;
+use crate::generating::generate_app_state::generate_app_state;
+use crate::generating::generate_authorization::{check_required_roles, generate_set_authorizer};
+use crate::generating::generate_cqrs_impl::{generate_any_cqrs_enums, generate_cqrs_impl};
+use crate::generating::generate_default_lifecycle::generate_default_lifecycle;
 use crate::generating::generate_effects_enum::generate_effects_enum;
 use crate::generating::generate_errors_enum::generate_errors_enum;
+use crate::generating::generate_interceptors::generate_register_interceptor;
+use crate::generating::generate_metrics::generate_metrics_snapshot;
+use crate::generating::generate_persistence::{
+    generate_deferred_persistence, inject_shutdown_flush,
+};
+use crate::generating::generate_undo::generate_undo;
 use crate::generating::generate_use_statement::generate_use_statement;
 use crate::generating::traits::api_traits::generate_api_traits;
 use crate::generating::traits::cqrs_traits::generate_cqrs_traits;
 
 use crate::parsing::extract_type::get_type_as_capital_ident;
-use crate::parsing::get_struct_by_trait::get_structs_by_traits;
+use crate::parsing::get_app_state_fields::get_app_state_fields;
+use crate::parsing::get_struct_by_trait::{get_struct_by_attribute, get_structs_by_traits};
 // use crate::parsing::get_use_statements::get_use_statements;
-use crate::parsing::read_rust_files::{read_rust_file_content, tokens_2_file_locations};
+use crate::parsing::macro_args::MacroArgs;
+use crate::parsing::read_rust_files::read_rust_file_content;
 use proc_macro2::TokenStream;
 use quote::quote;
-use syn::{parse2, Ident, ItemImpl, Result, Variant};
+use syn::{parse2, Ident, ItemImpl, PathArguments, Result, Variant};
 
 #[derive(Debug, PartialEq, Clone)]
 pub(crate) struct BasePath(pub(crate) String);
@@ -49,31 +60,50 @@ pub(crate) struct ModelNEffectsNErrors {
     pub(crate) error_ident: Ident,
 }
 
-pub fn generate_api_impl(item: TokenStream, file_paths: TokenStream) -> Result<TokenStream> {
+pub fn generate_api_impl(item: TokenStream, macro_args: TokenStream) -> Result<TokenStream> {
     log::info!("-------- Generating API --------");
     // check if it implements the Lifecycle trait
     // not parsing with syn::parse, to save time. Returning the unchanged input anyways, would need to clone() otherwise
-    if !item.to_string().contains("impl Lifecycle for") {
+    let item_string = item.to_string();
+    if !item_string.contains("impl Lifecycle for") && !item_string.contains("impl Lifecycle <") {
         panic!("The macro has to be declaired on an 'impl Lifecycle for'! (You can't use generics, as the singleton instance is to be stored as a static global variable.)");
     }
-    let file_locations = tokens_2_file_locations(file_paths)?;
-    if file_locations.is_empty() {
+    let macro_args = parse2::<MacroArgs>(macro_args)?;
+    if macro_args.file_paths.is_empty() {
         panic!("At least one model implementatoin struct has to be provided\nlike #[generate_api(\"domain/MyModel.rs\")]\nProvide multiple model implementations with #[generate_api(\"domain/MyModel.rs\", \"other_domain/MySecondModel.rs\")]");
     }
 
     let lifecycle_impl_ident: Ident = get_type_ident_from_impl(&item)?;
 
-    let parsed_files = read_rust_file_content(file_locations)?;
+    let parsed_files = read_rust_file_content(macro_args.file_paths.clone())?;
 
-    let generated_code = generate_code(lifecycle_impl_ident, parsed_files)?;
+    let generated_code = generate_code(lifecycle_impl_ident, parsed_files, &macro_args)?;
+    let item = add_lifecycle_error_type(item)?;
+    let (item, generated_lifecycle_code) = generate_default_lifecycle(item, &macro_args)?;
+    let item = inject_shutdown_flush(item, &macro_args)?;
 
     let output = quote! {
+        #generated_lifecycle_code
         #item
         #generated_code
     };
     Ok(output)
 }
 
+/// `impl Lifecycle for X` implements the generic `Lifecycle<ProcessingError>` of the generate_cqrs_api crate
+fn add_lifecycle_error_type(item: TokenStream) -> Result<TokenStream> {
+    let mut ast = parse2::<ItemImpl>(item)?;
+    if let Some((_, trait_path, _)) = &mut ast.trait_ {
+        if let Some(last_segment) = trait_path.segments.last_mut() {
+            if last_segment.arguments.is_empty() {
+                last_segment.arguments =
+                    PathArguments::AngleBracketed(syn::parse_quote!(<ProcessingError>));
+            }
+        }
+    }
+    Ok(quote! { #ast })
+}
+
 fn get_type_ident_from_impl(item: &TokenStream) -> Result<Ident> {
     let ast = parse2::<ItemImpl>(item.clone())?;
     get_type_as_capital_ident(&ast.self_ty)
@@ -82,10 +112,19 @@ fn get_type_ident_from_impl(item: &TokenStream) -> Result<Ident> {
 fn generate_code(
     lifecycle_impl_ident: Ident,
     parsed_files: Vec<ParsedFiles>,
+    macro_args: &MacroArgs,
 ) -> Result<TokenStream> {
     let models_parsed: Vec<ModelParsed> = parsed_files.into_iter().map(|parsed_file|{
         let ast = syn::parse_file(&parsed_file.source_code.0).unwrap_or_else(|_| panic!("cannot parse the code file {}", parsed_file.base_path.0));
-        let trait_impls = get_structs_by_traits(&ast, &["CqrsModel", "CqrsModelLock"]);
+        // a lock annotated with #[cqrs_lock] has its trait impl generated
+        let trait_impls = match get_struct_by_attribute(&ast, "cqrs_lock") {
+            Some(domain_model_lock_ident) => {
+                let mut trait_impls = get_structs_by_traits(&ast, &["CqrsModel"]);
+                trait_impls.insert("CqrsModelLock".to_string(), domain_model_lock_ident);
+                trait_impls
+            }
+            None => get_structs_by_traits(&ast, &["CqrsModel", "CqrsModelLock"]),
+        };
         let domain_model_ident = trait_impls
             .get("CqrsModel")
             .expect("Couldn't extract the domain model's name. One Struct needs to impl CqrsModel.");
@@ -96,13 +135,35 @@ fn generate_code(
         debug!("domain model lock name: {:#?}", domain_model_lock_ident);
         ModelParsed{base_path : parsed_file.base_path, ast, domain_model_ident: domain_model_ident.to_owned(), domain_model_lock_ident: domain_model_lock_ident.to_owned() }
     }).collect();
+    check_model_fields(macro_args, &models_parsed)?;
+    check_required_roles(macro_args, &models_parsed)?;
+    let macro_args = &resolve_app_state_fields(macro_args, &models_parsed)?;
     // take all imports, just in case they are used in the generated code (like RustAutoOpaque)
     // => not needed. If needed later, remove import to generated traits!
     // let use_statements = get_use_statements(&ast);
 
     let (models_n_effect, generated_effect_enum) = generate_effects_enum(models_parsed);
-    let (models_n_efects_n_errors, generated_error_enum) = generate_errors_enum(models_n_effect);
-    let generated_cqrs_fns = &generate_cqrs_impl(&lifecycle_impl_ident, &models_n_efects_n_errors);
+    let (models_n_efects_n_errors, generated_error_enum) =
+        generate_errors_enum(models_n_effect, macro_args);
+    let generated_cqrs_fns =
+        &generate_cqrs_impl(&lifecycle_impl_ident, &models_n_efects_n_errors, macro_args);
+    let generated_any_cqrs_enums =
+        generate_any_cqrs_enums(&lifecycle_impl_ident, &models_n_efects_n_errors, macro_args);
+    let generated_undo =
+        generate_undo(&lifecycle_impl_ident, &models_n_efects_n_errors, macro_args);
+    let generated_register_interceptor =
+        generate_register_interceptor(&lifecycle_impl_ident, macro_args);
+    let generated_metrics_snapshot = generate_metrics_snapshot(&lifecycle_impl_ident, macro_args);
+    let generated_set_authorizer = generate_set_authorizer(&lifecycle_impl_ident, macro_args);
+    let generated_deferred_persistence =
+        generate_deferred_persistence(&lifecycle_impl_ident, &macro_args.persistence);
+    let generated_app_state = generate_app_state(
+        &models_n_efects_n_errors
+            .iter()
+            .map(|model| (&model.domain_model_ident, &model.domain_model_lock_ident))
+            .collect::<Vec<(&Ident, &Ident)>>(),
+        macro_args,
+    );
     let generated_api_traits = generate_api_traits();
     let generated_cqrs_traits = generate_cqrs_traits();
 
@@ -120,6 +181,13 @@ fn generate_code(
         #generated_error_enum
         #generated_effect_enum
         #(#generated_cqrs_fns)*
+        #generated_any_cqrs_enums
+        #generated_undo
+        #generated_register_interceptor
+        #generated_metrics_snapshot
+        #generated_set_authorizer
+        #generated_deferred_persistence
+        #generated_app_state
     };
     debug!(
         "generated code:\n----------------------------------------------------------------------------------------\n{:}\n----------------------------------------------------------------------------------------\n",
@@ -128,11 +196,61 @@ fn generate_code(
     Ok(generated_code)
 }
 
+/// checks that every `field(MyModelLock = "...")` refers to a parsed model lock
+fn check_model_fields(macro_args: &MacroArgs, models_parsed: &[ModelParsed]) -> Result<()> {
+    let mut configured_locks = macro_args.model_fields.keys().collect::<Vec<&String>>();
+    configured_locks.sort();
+    for configured_lock in configured_locks {
+        if !models_parsed
+            .iter()
+            .any(|model| model.domain_model_lock_ident == configured_lock)
+        {
+            return Err(syn::Error::new(
+                proc_macro2::Span::call_site(),
+                format!(
+                    "field({configured_lock} = ...) doesn't match any model lock. Found: {:?}",
+                    models_parsed
+                        .iter()
+                        .map(|model| model.domain_model_lock_ident.to_string())
+                        .collect::<Vec<String>>()
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// looks up the AppState's fields holding the model locks, if `app_state_file` is given
+fn resolve_app_state_fields(
+    macro_args: &MacroArgs,
+    models_parsed: &[ModelParsed],
+) -> Result<MacroArgs> {
+    let Some(app_state_file) = &macro_args.app_state_file else {
+        return Ok(macro_args.clone());
+    };
+    let parsed_file = read_rust_file_content(vec![app_state_file.to_owned()])?.remove(0);
+    let ast = syn::parse_file(&parsed_file.source_code.0)?;
+    let domain_model_lock_idents = models_parsed
+        .iter()
+        .map(|model| model.domain_model_lock_ident.to_owned())
+        .collect::<Vec<Ident>>();
+    Ok(MacroArgs {
+        model_fields: get_app_state_fields(
+            &ast,
+            &domain_model_lock_idents,
+            &macro_args.model_fields,
+        )?,
+        ..macro_args.clone()
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{
-        generate_api_macro_impl::{generate_code, get_type_ident_from_impl},
-        parsing::read_rust_files::read_rust_file_content,
+        generate_api_macro_impl::{
+            add_lifecycle_error_type, generate_code, get_type_ident_from_impl,
+        },
+        parsing::{macro_args::MacroArgs, read_rust_files::read_rust_file_content},
     };
     use quote::{format_ident, quote};
 
@@ -246,74 +364,10 @@ mod tests {
         let expected = quote! {
             pub use crate::good_source_file::*;
             use serde::{Deserialize, Serialize};
-
-            pub trait Lifecycle {
-                #[doc = r" due to frb's current capabilities we cannot define function arguments as types."]
-                #[doc = r" for return types it works. Thus, Error is defined this way, while AppConfig is a generic parameter."]
-                type Error: AppStatePersistError;
-                #[doc = r" loads the app's state, which can be io-heavy"]
-                #[doc = r" get the instance with get_singleton(). Create the initial singleton with this function"]
-                fn initialise_with_app_config<AC: AppConfig + std::fmt::Debug>(
-                    app_config: AC,
-                ) -> Result<&'static Self, Self::Error>;
-                #[doc = r" frb doesn't support generics. Thus, we can call this concrete function."]
-                fn initialise(app_state_url: Option<String>) -> Result<(), Self::Error>;
-                #[doc = r" get the instance with get_singleton(). Create the initial singleton with Lifecycle::initialise()"]
-                #[doc = r" This cannot be called from Flutter, as frb cannot handle references. Thus, it is called internally (by CQRS::process(), Lifecycle::shutdown() and others)"]
-                fn get_singleton() -> &'static Self;
-                #[doc = r" persist the app state to the previously stored location"]
-                #[doc = r" as we cannot pass references to frb (see 'get_singleton') persist() and shutdown() have to get 'self' by calling get_singleton() on their own."]
-                fn persist() -> Result<(), ProcessingError>;
-                fn shutdown() -> Result<(), ProcessingError>;
-            }
-            pub trait AppConfig: Default {
-                #[doc = r" call to overwrite default values."]
-                #[doc = r" Doesn't trigger long initialization operations."]
-                fn new(url: Option<String>) -> Self;
-                #[doc = r" app state storage location"]
-                fn borrow_app_state_url(&self) -> &str;
-            }
-            #[doc = r" the app's state is not exposed external - it is guarded behind CQRS functions"]
-            pub(crate) trait AppState: Serialize + for<'a> Deserialize<'a> {
-                fn new<AC: AppConfig>(app_config: &AC) -> Self;
-                fn dirty_flag_value(&self) -> bool;
-                fn mark_dirty(&self);
-                fn mark_persisted(&self);
-            }
-            pub trait AppStatePersistError:
-                std::error::Error
-            {
-                #[doc = r" convert to ProcessingError::NotPersisted"]
-                fn to_processing_error(&self) -> ProcessingError;
-            }
-            pub(crate) trait AppStatePersister {
-                #[doc = r" prepares for persisting a new AppState. Not needed if the AppState is loaded!"]
-                type Error: AppStatePersistError;
-                fn new<AC: AppConfig>(app_config: &AC) -> Result<Self, Self::Error>
-                where
-                    Self: Sized;
-                #[doc = r" Persists the application state to storage."]
-                #[doc = r" Ensures that the `AppState` is stored in a durable way, regardless of the underlying mechanism."]
-                fn persist_app_state<AS: AppState + std::fmt::Debug>(
-                    &self,
-                    state: &AS,
-                ) -> Result<(), Self::Error>;
-                #[doc = r" Loads the application state."]
-                #[doc = r" Returns a result with the `AppState` if successful or an `InfrastructureError` otherwise."]
-                fn load_app_state<AC: AppConfig, AS: AppState>(&self) -> Result<AS, Self::Error>;
-            }
-            pub(crate) trait CqrsModel:
-                std::marker::Sized + Default + serde::Serialize + for<'de> serde::Deserialize<'de>
-            {
-            }
-            pub(crate) trait CqrsModelLock<CqrsModel>:
-                std::marker::Sized + Clone + serde::Serialize + for<'de> serde::Deserialize<'de>
-            {
-                fn for_model(model: CqrsModel) -> Self;
-            }
-            pub trait Cqrs: std::fmt::Debug {
-                fn process(self) -> Result<Vec<Effect>, ProcessingError>;
-            }
+            pub use generate_cqrs_api::{
+                AppConfig, AppState, AppStatePersistError, AppStatePersister, Lifecycle,
+            };
+            pub use generate_cqrs_api::{Cqrs, CqrsModel, CqrsModelLock};
             use crate::good_source_file::MyGoodProcessingError;
             #[derive(thiserror :: Error, Debug)]
             pub enum ProcessingError {
@@ -321,6 +375,14 @@ mod tests {
                 MyGoodProcessingError(MyGoodProcessingError),
                 #[error("Processing was fine, but state could not be persisted in url '{url}': {error}")]
                 NotPersisted { error: String, url: String },
+                #[error("The argument '{argument}' of {command} is invalid: {reason}")]
+                InvalidArgument { command: String, argument: String, reason: String },
+            }
+
+            impl generate_cqrs_api::NotPersistedError for ProcessingError {
+                fn not_persisted(error: String, url: String) -> Self {
+                    ProcessingError::NotPersisted { error, url }
+                }
             }
             pub enum Effect {
                 MyGoodDomainModelRenderItems(MyGoodDomainModelLock)
@@ -337,8 +399,17 @@ mod tests {
             }
 
             impl Cqrs for MyGoodDomainModelQuery {
+
+                type Effect = Effect;
+
+                type Error = ProcessingError;
                 fn process(self) -> Result<Vec<Effect>, ProcessingError> {
-                    let lifecycle = LifecycleImpl::get_singleton();
+                    self.process_with(LifecycleImpl::get_singleton())
+                }
+            }
+            impl MyGoodDomainModelQuery {
+                /// processes on the given lifecycle instead of the global singleton, e.g. to run isolated app states in tests
+                pub fn process_with(self, lifecycle: &LifecycleImpl) -> Result<Vec<Effect>, ProcessingError> {
                     let app_state = &lifecycle.app_state;
                     let my_good_domain_model_lock = &app_state.my_good_domain_model_lock;
                     let result = match self {
@@ -355,8 +426,16 @@ mod tests {
                 }
             }
             impl Cqrs for MyGoodDomainModelCommand {
+                type Effect = Effect;
+                type Error = ProcessingError;
                 fn process(self) -> Result<Vec<Effect>, ProcessingError> {
-                    let lifecycle = LifecycleImpl::get_singleton();
+                    self.process_with(LifecycleImpl::get_singleton())
+                }
+            }
+            impl MyGoodDomainModelCommand {
+                /// processes on the given lifecycle instead of the global singleton, e.g. to run isolated app states in tests
+                pub fn process_with(self, lifecycle: &LifecycleImpl) -> Result<Vec<Effect>, ProcessingError> {
+                    self.validate()?;
                     let app_state = &lifecycle.app_state;
                     let my_good_domain_model_lock = &app_state.my_good_domain_model_lock;
                     let (state_changed, result) = match self {
@@ -367,8 +446,8 @@ mod tests {
                     }
                     .map_err(ProcessingError::MyGoodProcessingError)?;
                     if state_changed {
-                        app_state.mark_dirty();
-                        LifecycleImpl::persist()?;
+                        app_state.mark_model_dirty(generate_cqrs_api::ModelId("my_good_domain_model_lock"));
+                        lifecycle.persist_instance()?;
                     }
                     Ok(result
                         .into_iter()
@@ -378,13 +457,32 @@ mod tests {
                         })
                         .collect())
                 }
+                /// checks the arguments' `#[validate(...)]` rules
+                fn validate(&self) -> Result<(), ProcessingError> {
+                    match self {
+                        MyGoodDomainModelCommand::RemoveItem(todo_pos) => {
+                            generate_cqrs_api::check_range(todo_pos, Some(1), None).map_err(|reason| ProcessingError::InvalidArgument {
+                                command: "MyGoodDomainModelCommand::RemoveItem".to_string(),
+                                argument: "todo_pos".to_string(),
+                                reason,
+                            })?;
+                        }
+                        _ => {}
+                    }
+                    Ok(())
+                }
             }
         };
         // let (use_statements, content) =
         let paths_n_codes =
             read_rust_file_content(vec!["../tests/good_source_file/mod.rs".to_string()])
                 .expect("Could not read test oracle file: ");
-        let result = generate_code(format_ident!("LifecycleImpl"), paths_n_codes).unwrap();
+        let result = generate_code(
+            format_ident!("LifecycleImpl"),
+            paths_n_codes,
+            &MacroArgs::default(),
+        )
+        .unwrap();
         assert_eq!(expected.to_string(), result.to_string());
     }
 
@@ -395,73 +493,10 @@ mod tests {
                     pub use crate::second_model_file::*;
 
                     use serde::{Deserialize, Serialize};
-                    pub trait Lifecycle {
-                        #[doc = r" due to frb's current capabilities we cannot define function arguments as types."]
-                        #[doc = r" for return types it works. Thus, Error is defined this way, while AppConfig is a generic parameter."]
-                        type Error: AppStatePersistError;
-                        #[doc = r" loads the app's state, which can be io-heavy"]
-                        #[doc = r" get the instance with get_singleton(). Create the initial singleton with this function"]
-                        fn initialise_with_app_config<AC: AppConfig + std::fmt::Debug>(
-                            app_config: AC,
-                        ) -> Result<&'static Self, Self::Error>;
-                        #[doc = r" frb doesn't support generics. Thus, we can call this concrete function."]
-                        fn initialise(app_state_url: Option<String>) -> Result<(), Self::Error>;
-                        #[doc = r" get the instance with get_singleton(). Create the initial singleton with Lifecycle::initialise()"]
-                        #[doc = r" This cannot be called from Flutter, as frb cannot handle references. Thus, it is called internally (by CQRS::process(), Lifecycle::shutdown() and others)"]
-                        fn get_singleton() -> &'static Self;
-                        #[doc = r" persist the app state to the previously stored location"]
-                        #[doc = r" as we cannot pass references to frb (see 'get_singleton') persist() and shutdown() have to get 'self' by calling get_singleton() on their own."]
-                        fn persist() -> Result<(), ProcessingError>;
-                        fn shutdown() -> Result<(), ProcessingError>;
-                    }
-                    pub trait AppConfig: Default {
-                        #[doc = r" call to overwrite default values."]
-                        #[doc = r" Doesn't trigger long initialization operations."]
-                        fn new(url: Option<String>) -> Self;
-                        #[doc = r" app state storage location"]
-                        fn borrow_app_state_url(&self) -> &str;
-                    }
-                    #[doc = r" the app's state is not exposed external - it is guarded behind CQRS functions"]
-                    pub(crate) trait AppState: Serialize + for<'a> Deserialize<'a> {
-                        fn new<AC: AppConfig>(app_config: &AC) -> Self;
-                        fn dirty_flag_value(&self) -> bool;
-                        fn mark_dirty(&self);
-                        fn mark_persisted(&self);
-                    }
-                    pub trait AppStatePersistError:
-                        std::error::Error
-                    {
-                        #[doc = r" convert to ProcessingError::NotPersisted"]
-                        fn to_processing_error(&self) -> ProcessingError;
-                    }
-                    pub(crate) trait AppStatePersister {
-                        #[doc = r" prepares for persisting a new AppState. Not needed if the AppState is loaded!"]
-                        type Error: AppStatePersistError;
-                        fn new<AC: AppConfig>(app_config: &AC) -> Result<Self, Self::Error>
-                        where
-                            Self: Sized;
-                        #[doc = r" Persists the application state to storage."]
-                        #[doc = r" Ensures that the `AppState` is stored in a durable way, regardless of the underlying mechanism."]
-                        fn persist_app_state<AS: AppState + std::fmt::Debug>(
-                            &self,
-                            state: &AS,
-                        ) -> Result<(), Self::Error>;
-                        #[doc = r" Loads the application state."]
-                        #[doc = r" Returns a result with the `AppState` if successful or an `InfrastructureError` otherwise."]
-                        fn load_app_state<AC: AppConfig, AS: AppState>(&self) -> Result<AS, Self::Error>;
-                    }
-                    pub(crate) trait CqrsModel:
-                        std::marker::Sized + Default + serde::Serialize + for<'de> serde::Deserialize<'de>
-                    {
-                    }
-                    pub(crate) trait CqrsModelLock<CqrsModel>:
-                        std::marker::Sized + Clone + serde::Serialize + for<'de> serde::Deserialize<'de>
-                    {
-                        fn for_model(model: CqrsModel) -> Self;
-                    }
-                    pub trait Cqrs: std::fmt::Debug {
-                        fn process(self) -> Result<Vec<Effect>, ProcessingError>;
-                    }
+                    pub use generate_cqrs_api::{
+                        AppConfig, AppState, AppStatePersistError, AppStatePersister, Lifecycle,
+                    };
+                    pub use generate_cqrs_api::{Cqrs, CqrsModel, CqrsModelLock};
                     use crate::good_source_file::MyGoodProcessingError;
                     use crate::second_model_file::MySecondDomainProcessingError;
                     #[derive(thiserror :: Error, Debug)]
@@ -472,6 +507,14 @@ mod tests {
                         MySecondDomainProcessingError(MySecondDomainProcessingError),
                         #[error("Processing was fine, but state could not be persisted in url '{url}': {error}")]
                         NotPersisted { error: String, url: String },
+                        #[error("The argument '{argument}' of {command} is invalid: {reason}")]
+                        InvalidArgument { command: String, argument: String, reason: String },
+                    }
+
+                    impl generate_cqrs_api::NotPersistedError for ProcessingError {
+                        fn not_persisted(error: String, url: String) -> Self {
+                            ProcessingError::NotPersisted { error, url }
+                        }
                     }
                     pub enum Effect {
                         MyGoodDomainModelRenderItems(MyGoodDomainModelLock),
@@ -490,8 +533,17 @@ mod tests {
                     }
 
                     impl Cqrs for MyGoodDomainModelQuery {
+
+                        type Effect = Effect;
+
+                        type Error = ProcessingError;
                         fn process(self) -> Result<Vec<Effect>, ProcessingError> {
-                            let lifecycle = LifecycleImpl::get_singleton();
+                            self.process_with(LifecycleImpl::get_singleton())
+                        }
+                    }
+                    impl MyGoodDomainModelQuery {
+                        /// processes on the given lifecycle instead of the global singleton, e.g. to run isolated app states in tests
+                        pub fn process_with(self, lifecycle: &LifecycleImpl) -> Result<Vec<Effect>, ProcessingError> {
                             let app_state = &lifecycle.app_state;
                             let my_good_domain_model_lock = &app_state.my_good_domain_model_lock;
                             let result = match self {
@@ -508,8 +560,16 @@ mod tests {
                         }
                     }
                     impl Cqrs for MyGoodDomainModelCommand {
+                        type Effect = Effect;
+                        type Error = ProcessingError;
                         fn process(self) -> Result<Vec<Effect>, ProcessingError> {
-                            let lifecycle = LifecycleImpl::get_singleton();
+                            self.process_with(LifecycleImpl::get_singleton())
+                        }
+                    }
+                    impl MyGoodDomainModelCommand {
+                        /// processes on the given lifecycle instead of the global singleton, e.g. to run isolated app states in tests
+                        pub fn process_with(self, lifecycle: &LifecycleImpl) -> Result<Vec<Effect>, ProcessingError> {
+                            self.validate()?;
                             let app_state = &lifecycle.app_state;
                             let my_good_domain_model_lock = &app_state.my_good_domain_model_lock;
                             let (state_changed, result) = match self {
@@ -520,8 +580,8 @@ mod tests {
                             }
                             .map_err(ProcessingError::MyGoodProcessingError)?;
                             if state_changed {
-                                app_state.mark_dirty();
-                                LifecycleImpl::persist()?;
+                                app_state.mark_model_dirty(generate_cqrs_api::ModelId("my_good_domain_model_lock"));
+                                lifecycle.persist_instance()?;
                             }
                             Ok(result
                                 .into_iter()
@@ -531,6 +591,20 @@ mod tests {
                                 })
                                 .collect())
                         }
+                        /// checks the arguments' `#[validate(...)]` rules
+                        fn validate(&self) -> Result<(), ProcessingError> {
+                            match self {
+                                MyGoodDomainModelCommand::RemoveItem(todo_pos) => {
+                                    generate_cqrs_api::check_range(todo_pos, Some(1), None).map_err(|reason| ProcessingError::InvalidArgument {
+                                        command: "MyGoodDomainModelCommand::RemoveItem".to_string(),
+                                        argument: "todo_pos".to_string(),
+                                        reason,
+                                    })?;
+                                }
+                                _ => {}
+                            }
+                            Ok(())
+                        }
                     }
                     #[derive(Debug)]
         pub enum MySecondDomainModelQuery {
@@ -543,8 +617,15 @@ mod tests {
             ReplaceItem(usize)
         }
         impl Cqrs for MySecondDomainModelQuery {
+            type Effect = Effect;
+            type Error = ProcessingError;
             fn process(self) -> Result<Vec<Effect>, ProcessingError> {
-                let lifecycle = LifecycleImpl::get_singleton();
+                self.process_with(LifecycleImpl::get_singleton())
+            }
+        }
+        impl MySecondDomainModelQuery {
+            /// processes on the given lifecycle instead of the global singleton, e.g. to run isolated app states in tests
+            pub fn process_with(self, lifecycle: &LifecycleImpl) -> Result<Vec<Effect>, ProcessingError> {
                 let app_state = &lifecycle.app_state;
                 let my_second_domain_model_lock = &app_state.my_second_domain_model_lock;
                 let result = match self {
@@ -562,8 +643,15 @@ mod tests {
             }
         }
         impl Cqrs for MySecondDomainModelCommand {
+            type Effect = Effect;
+            type Error = ProcessingError;
             fn process(self) -> Result<Vec<Effect>, ProcessingError> {
-                let lifecycle = LifecycleImpl::get_singleton();
+                self.process_with(LifecycleImpl::get_singleton())
+            }
+        }
+        impl MySecondDomainModelCommand {
+            /// processes on the given lifecycle instead of the global singleton, e.g. to run isolated app states in tests
+            pub fn process_with(self, lifecycle: &LifecycleImpl) -> Result<Vec<Effect>, ProcessingError> {
                 let app_state = &lifecycle.app_state;
                 let my_second_domain_model_lock = &app_state.my_second_domain_model_lock;
                 let (state_changed, result) = match self {
@@ -573,8 +661,8 @@ mod tests {
                 }
                 .map_err(ProcessingError::MySecondDomainProcessingError)?;
                 if state_changed {
-                    app_state.mark_dirty();
-                    LifecycleImpl::persist()?;
+                    app_state.mark_model_dirty(generate_cqrs_api::ModelId("my_second_domain_model_lock"));
+                    lifecycle.persist_instance()?;
                 }
                 Ok(result
                     .into_iter()
@@ -592,10 +680,51 @@ mod tests {
             "../tests/second_model_file/mod.rs".to_string(),
         ])
         .expect("Could not read test oracle file: ");
-        let result = generate_code(format_ident!("LifecycleImpl"), paths_n_codes).unwrap();
+        let result = generate_code(
+            format_ident!("LifecycleImpl"),
+            paths_n_codes,
+            &MacroArgs::default(),
+        )
+        .unwrap();
         assert_eq!(expected.to_string(), result.to_string());
     }
 
+    #[test]
+    fn fail_field_for_unknown_model_lock() {
+        let paths_n_codes =
+            read_rust_file_content(vec!["../tests/good_source_file/mod.rs".to_string()])
+                .expect("Could not read test oracle file: ");
+        let macro_args = syn::parse2::<MacroArgs>(quote! {
+            "tests/good_source_file/mod.rs",
+            field(MyTypoModelLock = "todos")
+        })
+        .unwrap();
+        let error =
+            generate_code(format_ident!("LifecycleImpl"), paths_n_codes, &macro_args).unwrap_err();
+        assert_eq!(
+            "field(MyTypoModelLock = ...) doesn't match any model lock. Found: [\"MyGoodDomainModelLock\"]",
+            error.to_string()
+        );
+    }
+    #[test]
+    fn fail_app_state_file_without_configured_field() {
+        let paths_n_codes =
+            read_rust_file_content(vec!["../tests/good_source_file/mod.rs".to_string()])
+                .expect("Could not read test oracle file: ");
+        let macro_args = syn::parse2::<MacroArgs>(quote! {
+            "tests/good_source_file/mod.rs",
+            app_state_file = "../tests/mocks/app_state_mock.rs",
+            field(MyGoodDomainModelLock = "todos")
+        })
+        .unwrap();
+        let error =
+            generate_code(format_ident!("LifecycleImpl"), paths_n_codes, &macro_args).unwrap_err();
+        assert_eq!(
+            "field(MyGoodDomainModelLock = \"todos\"), but the AppState struct 'AppStateImpl' has no field 'todos'!",
+            error.to_string()
+        );
+    }
+
     #[test]
     #[should_panic(
         expected = "At least one model implementatoin struct has to be provided\nlike #[generate_api(\"domain/MyModel.rs\")]"
@@ -607,6 +736,25 @@ mod tests {
         let _ = generate_api_impl(lifecycle_impl, quote! {});
     }
     #[test]
+    fn add_processing_error_to_lifecycle_impl() {
+        let result = add_lifecycle_error_type(quote! {
+            impl Lifecycle for LifecycleImpl {}
+        })
+        .unwrap();
+        assert_eq!(
+            quote! { impl Lifecycle<ProcessingError> for LifecycleImpl {} }.to_string(),
+            result.to_string()
+        );
+        let result = add_lifecycle_error_type(quote! {
+            impl generate_cqrs_api::Lifecycle<MyError> for LifecycleImpl {}
+        })
+        .unwrap();
+        assert_eq!(
+            quote! { impl generate_cqrs_api::Lifecycle<MyError> for LifecycleImpl {} }.to_string(),
+            result.to_string()
+        );
+    }
+    #[test]
     #[should_panic(expected = "The macro has to be declaired on an 'impl Lifecycle for'!")]
     fn test_gengenerate_api_impl_wrong_lifecycle_impl() {
         let lifecycle_not_trait_impl = quote! {
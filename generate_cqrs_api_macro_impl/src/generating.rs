@@ -1,5 +1,14 @@
+pub(crate) mod generate_app_state;
+pub(crate) mod generate_authorization;
 pub(crate) mod generate_cqrs_impl;
+pub(crate) mod generate_default_lifecycle;
 pub(crate) mod generate_effects_enum;
 pub(crate) mod generate_errors_enum;
+pub(crate) mod generate_interceptors;
+pub(crate) mod generate_metrics;
+pub(crate) mod generate_persistence;
+pub(crate) mod generate_tracing;
+pub(crate) mod generate_undo;
 pub(crate) mod generate_use_statement;
+pub(crate) mod generate_validation;
 pub(crate) mod traits;
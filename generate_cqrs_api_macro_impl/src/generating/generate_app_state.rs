@@ -0,0 +1,233 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::Ident;
+
+use crate::parsing::macro_args::{MacroArgs, PersistenceStrategy};
+
+/// generates the AppState struct, holding one lock per model and a dirty flag tracking the changed models,
+/// and its `impl AppState`, serializing each model separately by its `ModelId` (only for `generate_app_state`)
+/// @param models: (domain model, domain model lock) for each model
+pub(crate) fn generate_app_state(
+    models: &[(&Ident, &Ident)],
+    macro_args: &MacroArgs,
+) -> TokenStream {
+    let Some(app_state_ident) = &macro_args.generate_app_state else {
+        return quote! {};
+    };
+    let (model_fields, (domain_model_idents, domain_model_lock_idents)): (
+        Vec<Ident>,
+        (Vec<&Ident>, Vec<&Ident>),
+    ) = models
+        .iter()
+        .map(|(domain_model_ident, domain_model_lock_ident)| {
+            (
+                macro_args.model_field(domain_model_lock_ident),
+                (*domain_model_ident, *domain_model_lock_ident),
+            )
+        })
+        .unzip();
+    // the journal's position is persisted with the app state, to replay only the commands after it
+    let (journal_position_field, journal_position_init, journal_position_fns, journal_position_arm) =
+        match macro_args.persistence {
+            PersistenceStrategy::Journal { .. } => (
+                quote! {
+                    #[serde(default)]
+                    journal_position: generate_cqrs_api::JournalPosition,
+                },
+                quote! {
+                    journal_position: generate_cqrs_api::JournalPosition::default(),
+                },
+                quote! {
+                    fn journal_position(&self) -> u64 {
+                        self.journal_position.get()
+                    }
+                    fn set_journal_position(&self, position: u64) {
+                        self.journal_position.set(position);
+                        self.dirty_flag
+                            .mark_model_dirty(generate_cqrs_api::ModelId("journal_position"));
+                    }
+                },
+                quote! {
+                    "journal_position" => Some(serde::Serialize::serialize(&self.journal_position, serializer)),
+                },
+            ),
+            _ => (quote! {}, quote! {}, quote! {}, quote! {}),
+        };
+    let model_ids = model_fields
+        .iter()
+        .map(|model_field| model_field.to_string());
+    quote! {
+        #[derive(Debug, serde::Serialize, serde::Deserialize)]
+        pub(crate) struct #app_state_ident {
+            #(pub(crate) #model_fields: #domain_model_lock_idents,)*
+            #journal_position_field
+            #[serde(skip)]
+            dirty_flag: generate_cqrs_api::DirtyFlag,
+        }
+
+        impl AppState for #app_state_ident {
+            fn new<AC: AppConfig>(_app_config: &AC) -> Self {
+                Self {
+                    #(#model_fields: #domain_model_lock_idents::for_model(#domain_model_idents::default()),)*
+                    #journal_position_init
+                    dirty_flag: generate_cqrs_api::DirtyFlag::default(),
+                }
+            }
+            fn dirty_flag_value(&self) -> bool {
+                self.dirty_flag.is_dirty()
+            }
+            fn mark_dirty(&self) {
+                self.dirty_flag.mark_dirty();
+            }
+            fn mark_persisted(&self) {
+                self.dirty_flag.mark_persisted();
+            }
+            fn mark_model_dirty(&self, model: generate_cqrs_api::ModelId) {
+                self.dirty_flag.mark_model_dirty(model);
+            }
+            fn dirty_models(&self) -> Vec<generate_cqrs_api::ModelId> {
+                self.dirty_flag.dirty_models()
+            }
+            fn take_dirty_models(&self) -> Vec<generate_cqrs_api::ModelId> {
+                self.dirty_flag.take_dirty_models()
+            }
+            fn lock_persisting(&self) -> Option<std::sync::MutexGuard<'_, ()>> {
+                Some(self.dirty_flag.lock_persisting())
+            }
+            fn serialize_model<S: serde::Serializer>(
+                &self,
+                model: generate_cqrs_api::ModelId,
+                serializer: S,
+            ) -> Option<Result<S::Ok, S::Error>> {
+                match model.0 {
+                    #(#model_ids => Some(serde::Serialize::serialize(&self.#model_fields, serializer)),)*
+                    #journal_position_arm
+                    _ => None,
+                }
+            }
+            #journal_position_fns
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::{format_ident, quote};
+
+    use crate::{
+        generating::generate_app_state::generate_app_state, parsing::macro_args::MacroArgs,
+    };
+
+    #[test]
+    fn generate_app_state_only_when_configured() {
+        let model = format_ident!("MyGoodDomainModel");
+        let lock = format_ident!("MyGoodDomainModelLock");
+        assert!(generate_app_state(&[(&model, &lock)], &MacroArgs::default()).is_empty());
+    }
+    #[test]
+    fn generate_app_state_test() {
+        let model = format_ident!("MyGoodDomainModel");
+        let lock = format_ident!("MyGoodDomainModelLock");
+        let second_model = format_ident!("MySecondDomainModel");
+        let second_lock = format_ident!("MySecondDomainModelLock");
+        let macro_args = syn::parse2::<MacroArgs>(quote! {
+            "tests/good_source_file/mod.rs",
+            generate_app_state,
+            field(MySecondDomainModelLock = "seconds")
+        })
+        .unwrap();
+        let result = generate_app_state(
+            &[(&model, &lock), (&second_model, &second_lock)],
+            &macro_args,
+        );
+        let expected = quote! {
+            #[derive(Debug, serde::Serialize, serde::Deserialize)]
+            pub(crate) struct AppStateImpl {
+                pub(crate) my_good_domain_model_lock: MyGoodDomainModelLock,
+                pub(crate) seconds: MySecondDomainModelLock,
+                #[serde(skip)]
+                dirty_flag: generate_cqrs_api::DirtyFlag,
+            }
+
+            impl AppState for AppStateImpl {
+                fn new<AC: AppConfig>(_app_config: &AC) -> Self {
+                    Self {
+                        my_good_domain_model_lock: MyGoodDomainModelLock::for_model(MyGoodDomainModel::default()),
+                        seconds: MySecondDomainModelLock::for_model(MySecondDomainModel::default()),
+                        dirty_flag: generate_cqrs_api::DirtyFlag::default(),
+                    }
+                }
+                fn dirty_flag_value(&self) -> bool {
+                    self.dirty_flag.is_dirty()
+                }
+                fn mark_dirty(&self) {
+                    self.dirty_flag.mark_dirty();
+                }
+                fn mark_persisted(&self) {
+                    self.dirty_flag.mark_persisted();
+                }
+                fn mark_model_dirty(&self, model: generate_cqrs_api::ModelId) {
+                    self.dirty_flag.mark_model_dirty(model);
+                }
+                fn dirty_models(&self) -> Vec<generate_cqrs_api::ModelId> {
+                    self.dirty_flag.dirty_models()
+                }
+                fn take_dirty_models(&self) -> Vec<generate_cqrs_api::ModelId> {
+                    self.dirty_flag.take_dirty_models()
+                }
+                fn lock_persisting(&self) -> Option<std::sync::MutexGuard<'_, ()>> {
+                    Some(self.dirty_flag.lock_persisting())
+                }
+                fn serialize_model<S: serde::Serializer>(
+                    &self,
+                    model: generate_cqrs_api::ModelId,
+                    serializer: S,
+                ) -> Option<Result<S::Ok, S::Error>> {
+                    match model.0 {
+                        "my_good_domain_model_lock" => Some(serde::Serialize::serialize(&self.my_good_domain_model_lock, serializer)),
+                        "seconds" => Some(serde::Serialize::serialize(&self.seconds, serializer)),
+                        _ => None,
+                    }
+                }
+            }
+        };
+        assert_eq!(expected.to_string(), result.to_string());
+    }
+    #[test]
+    fn generate_journal_position() {
+        let model = format_ident!("MyGoodDomainModel");
+        let lock = format_ident!("MyGoodDomainModelLock");
+        let macro_args = syn::parse2::<MacroArgs>(quote! {
+            "tests/good_source_file/mod.rs",
+            generate_app_state,
+            persistence = "journal",
+            serializable_cqrs,
+            default_lifecycle(app_config = AppConfigImpl, persister = FilePersister)
+        })
+        .unwrap();
+        let result = generate_app_state(&[(&model, &lock)], &macro_args).to_string();
+        assert!(result.contains(
+            &quote! {
+                #[serde(default)]
+                journal_position: generate_cqrs_api::JournalPosition,
+            }
+            .to_string()
+        ));
+        assert!(result.contains(
+            &quote! {
+                fn set_journal_position(&self, position: u64) {
+                    self.journal_position.set(position);
+                    self.dirty_flag
+                        .mark_model_dirty(generate_cqrs_api::ModelId("journal_position"));
+                }
+            }
+            .to_string()
+        ));
+        assert!(result.contains(
+            &quote! {
+                "journal_position" => Some(serde::Serialize::serialize(&self.journal_position, serializer)),
+            }
+            .to_string()
+        ));
+    }
+}
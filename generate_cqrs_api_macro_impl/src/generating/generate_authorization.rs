@@ -0,0 +1,166 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Ident, ImplItem, Item, LitStr, Result};
+
+use crate::cqrs_attribute_macro_impl::get_cqrs_fn_attributes;
+use crate::generate_api_macro_impl::ModelParsed;
+use crate::parsing::macro_args::MacroArgs;
+
+/// generates the call of the lock's function, preceded by asking the lifecycle's `Authorization`,
+/// if the function requires a role with `#[cqrs(requires = "admin")]`
+/// @param command: the called variant, like "MyModelCommand::CleanList"
+/// @param trustable: if the caller has a flag `trusted`, skipping the check for commands replayed from the journal
+pub(crate) fn generate_authorized_call(
+    cqrs_call: TokenStream,
+    required_role: Option<&LitStr>,
+    command: &str,
+    trustable: bool,
+) -> TokenStream {
+    let Some(required_role) = required_role else {
+        return cqrs_call;
+    };
+    let trusted = trustable.then(|| quote! { !trusted && });
+    quote! {
+        {
+            if #trusted !lifecycle.authorization.is_authorized(#required_role, #command) {
+                return Err(ProcessingError::Unauthorized {
+                    command: #command.to_string(),
+                });
+            }
+            #cqrs_call
+        }
+    }
+}
+
+/// generates the lifecycle's `set_authorizer()` (only for `authorization`).
+/// The lifecycle needs the field `authorization: generate_cqrs_api::Authorization`.
+pub(crate) fn generate_set_authorizer(
+    lifecycle_impl_ident: &Ident,
+    macro_args: &MacroArgs,
+) -> TokenStream {
+    if !macro_args.authorization {
+        return quote! {};
+    }
+    quote! {
+        impl #lifecycle_impl_ident {
+            /// sets the authorizer deciding on the singleton's calls annotated with `#[cqrs(requires = "role")]`.
+            /// Set it at other lifecycles with `lifecycle.authorization.set_authorizer()`.
+            pub fn set_authorizer(authorizer: impl generate_cqrs_api::Authorizer + 'static) {
+                Self::get_singleton().authorization.set_authorizer(authorizer);
+            }
+        }
+    }
+}
+
+/// checks that `#[cqrs(requires = "...")]` is only used with the option `authorization`,
+/// as the roles wouldn't be checked otherwise
+pub(crate) fn check_required_roles(
+    macro_args: &MacroArgs,
+    models_parsed: &[ModelParsed],
+) -> Result<()> {
+    if macro_args.authorization {
+        return Ok(());
+    }
+    let annotated_fn = models_parsed
+        .iter()
+        .flat_map(|model| &model.ast.items)
+        .filter_map(|item| match item {
+            Item::Impl(item_impl) => Some(&item_impl.items),
+            _ => None,
+        })
+        .flatten()
+        .find_map(|impl_item| match impl_item {
+            ImplItem::Fn(function) if get_cqrs_fn_attributes(function).required_role.is_some() => {
+                Some(&function.sig.ident)
+            }
+            _ => None,
+        });
+    match annotated_fn {
+        Some(function) => Err(syn::Error::new(
+            proc_macro2::Span::call_site(),
+            format!("'{function}' is annotated with #[cqrs(requires = ...)], which needs the option 'authorization'"),
+        )),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::{format_ident, quote};
+    use syn::{parse2, parse_quote, LitStr};
+
+    use crate::{
+        generate_api_macro_impl::{BasePath, ModelParsed},
+        generating::generate_authorization::{check_required_roles, generate_authorized_call},
+        parsing::macro_args::MacroArgs,
+    };
+
+    #[test]
+    fn generate_authorization_check() {
+        let cqrs_call = quote! { my_model_lock.clean_list() };
+        let required_role: LitStr = parse_quote!("admin");
+        let result = generate_authorized_call(
+            cqrs_call.clone(),
+            Some(&required_role),
+            "MyModelCommand::CleanList",
+            false,
+        );
+        let expected = quote! {
+            {
+                if !lifecycle.authorization.is_authorized("admin", "MyModelCommand::CleanList") {
+                    return Err(ProcessingError::Unauthorized {
+                        command: "MyModelCommand::CleanList".to_string(),
+                    });
+                }
+                my_model_lock.clean_list()
+            }
+        };
+        assert_eq!(expected.to_string(), result.to_string());
+        let result = generate_authorized_call(
+            cqrs_call.clone(),
+            Some(&required_role),
+            "MyModelCommand::CleanList",
+            true,
+        );
+        let expected = quote! {
+            {
+                if !trusted && !lifecycle.authorization.is_authorized("admin", "MyModelCommand::CleanList") {
+                    return Err(ProcessingError::Unauthorized {
+                        command: "MyModelCommand::CleanList".to_string(),
+                    });
+                }
+                my_model_lock.clean_list()
+            }
+        };
+        assert_eq!(expected.to_string(), result.to_string());
+        let result =
+            generate_authorized_call(cqrs_call.clone(), None, "MyModelCommand::CleanList", true);
+        assert_eq!(cqrs_call.to_string(), result.to_string());
+    }
+    #[test]
+    fn fail_required_role_without_authorization() {
+        let models_parsed = vec![ModelParsed {
+            base_path: BasePath("crate::my_model".to_string()),
+            ast: parse_quote! {
+                impl MyModelLock {
+                    #[cqrs(requires = "admin")]
+                    pub(crate) fn clean_list(&self) -> Result<(bool, Vec<MyModelEffect>), MyModelError> {
+                        Ok((true, vec![]))
+                    }
+                }
+            },
+            domain_model_ident: format_ident!("MyModel"),
+            domain_model_lock_ident: format_ident!("MyModelLock"),
+        }];
+        let macro_args = parse2::<MacroArgs>(quote! {"tests/good_source_file/mod.rs"}).unwrap();
+        assert_eq!(
+            "'clean_list' is annotated with #[cqrs(requires = ...)], which needs the option 'authorization'",
+            check_required_roles(&macro_args, &models_parsed)
+                .unwrap_err()
+                .to_string()
+        );
+        let macro_args =
+            parse2::<MacroArgs>(quote! {"tests/good_source_file/mod.rs", authorization}).unwrap();
+        assert!(check_required_roles(&macro_args, &models_parsed).is_ok());
+    }
+}
@@ -11,15 +11,30 @@ use syn::Ident;
 use syn::ImplItemFn;
 use syn::Variant;
 
+use crate::cqrs_attribute_macro_impl::{get_cqrs_fn_attributes, CqrsFnAttributes};
 use crate::generate_api_macro_impl::ModelNEffectsNErrors;
+use crate::generating::generate_authorization::generate_authorized_call;
+use crate::generating::generate_interceptors::generate_intercepting_layer;
+use crate::generating::generate_metrics::generate_metrics_layer;
+use crate::generating::generate_persistence::{
+    generate_journal_entry_statement, generate_update_state_statement,
+};
+use crate::generating::generate_tracing::{generate_tracing_layer, generate_tracing_statements};
+use crate::generating::generate_undo::{
+    generate_record_undo_statement, generate_undo_snapshot_statement,
+};
+use crate::generating::generate_validation::generate_validation;
 use crate::parsing::extract_type::get_path;
 use crate::parsing::extract_type::get_type_as_capital_ident;
 use crate::parsing::extract_type::get_type_as_snake_case_ident;
 use crate::parsing::extract_type::get_type_as_tokens;
+use crate::parsing::get_struct_by_trait::{checks_invariants, is_swappable_lock};
+use crate::parsing::macro_args::{MacroArgs, PersistenceStrategy};
 
 pub(crate) fn generate_cqrs_impl(
     lifecycle_impl_ident: &Ident,
     models: &[ModelNEffectsNErrors],
+    macro_args: &MacroArgs,
 ) -> Vec<TokenStream> {
     models
         .iter()
@@ -29,6 +44,12 @@ pub(crate) fn generate_cqrs_impl(
             let effect_ident = &model.effect_ident;
             let effect_variants = &model.effect_variants;
             let error_ident = &model.error_ident;
+            let domain_model = (
+                domain_model_ident,
+                domain_model_lock_ident,
+                checks_invariants(&model.ast, domain_model_ident),
+                is_swappable_lock(&model.ast, domain_model_lock_ident),
+            );
 
             let (cqrs_queries, cqrs_commands) = get_cqrs_functions(
                 domain_model_lock_ident,
@@ -42,26 +63,36 @@ pub(crate) fn generate_cqrs_impl(
             let cqrs_queries_sig_idents = get_cqrs_fns_sig_idents(&cqrs_queries);
             let cqrs_commands_sig_idents = get_cqrs_fns_sig_idents(&cqrs_commands);
 
-            let generated_cqrs_query_enum =
-                generate_cqrs_query_enum(&cqrs_queries_sig_tipes, domain_model_ident);
-            let generated_cqrs_command_enum =
-                generate_cqrs_command_enum(&cqrs_commands_sig_tipes, domain_model_ident);
+            let generated_cqrs_query_enum = generate_cqrs_query_enum(
+                &cqrs_queries_sig_tipes,
+                domain_model_ident,
+                macro_args.serializable_cqrs,
+            );
+            let generated_cqrs_command_enum = generate_cqrs_command_enum(
+                &cqrs_commands_sig_tipes,
+                domain_model_ident,
+                macro_args.serializable_cqrs,
+            );
 
             let generated_cqrs_queries = generate_cqrs_functions(
-                lifecycle_impl_ident,
+                (lifecycle_impl_ident, macro_args),
                 "Query",
-                domain_model_ident,
-                domain_model_lock_ident,
-                &cqrs_queries_sig_idents,
+                domain_model,
+                (
+                    &cqrs_queries_sig_idents,
+                    &get_cqrs_fns_attributes(&cqrs_queries),
+                ),
                 (effect_ident, effect_variants),
                 error_ident,
             );
             let generated_cqrs_commands = generate_cqrs_functions(
-                lifecycle_impl_ident,
+                (lifecycle_impl_ident, macro_args),
                 "Command",
-                domain_model_ident,
-                domain_model_lock_ident,
-                &cqrs_commands_sig_idents,
+                domain_model,
+                (
+                    &cqrs_commands_sig_idents,
+                    &get_cqrs_fns_attributes(&cqrs_commands),
+                ),
                 (effect_ident, effect_variants),
                 error_ident,
             );
@@ -76,39 +107,458 @@ pub(crate) fn generate_cqrs_impl(
         .collect::<Vec<TokenStream>>()
 }
 
+/// the CQRS functions' names with their arguments' names, and their `#[cqrs]` and `#[validate]` attributes
+type CqrsFnCalls<'a> = (&'a [(Ident, Vec<Ident>)], &'a [CqrsFnAttributes]);
+/// the model's name, its lock's name, if the model checks invariants (see `checks_invariants()`)
+/// and if its lock implements `SwappableModelLock` (see `is_swappable_lock()`)
+type DomainModel<'a> = (&'a Ident, &'a Ident, bool, bool);
+
 fn generate_cqrs_functions(
-    lifecycle_impl_ident: &Ident,
+    lifecycle: (&Ident, &MacroArgs),
     cqrs_kind: &str,
-    domain_model_struct_ident: &Ident,
-    domain_model_lock_ident: &Ident,
-    cqrs_queries_sig_idents: &[(Ident, Vec<Ident>)],
+    domain_model: DomainModel,
+    cqrs_fns: CqrsFnCalls,
     effect: (&Ident, &[Variant]),
     processing_error: &Ident,
 ) -> TokenStream {
+    let (lifecycle_impl_ident, macro_args) = lifecycle;
+    let (domain_model_struct_ident, domain_model_lock_ident, checks_invariants, swappable_lock) =
+        domain_model;
+    let (cqrs_queries_sig_idents, cqrs_fns_attributes) = cqrs_fns;
     let enum_ident = format_ident!("{}{}", domain_model_struct_ident, cqrs_kind);
     let domain_model_lock_var = format_ident!(
         "{}",
         snake_case_with_sep(&domain_model_lock_ident.to_string(), "_")
     );
+    let app_state_field = macro_args.app_state_field();
+    let domain_model_lock_field = macro_args.model_field(domain_model_lock_ident);
 
     let lhs_cqrs_call = {
         let enum_variants =
             generate_cqrs_enum_variants_with_argument_idents(cqrs_queries_sig_idents);
-        enum_variants.into_iter().map(|variant| {
+        enum_variants
+            .into_iter()
+            .map(|variant| {
+                quote! {
+                    #enum_ident::#variant
+                }
+            })
+            .collect::<Vec<TokenStream>>()
+    };
+
+    // journaled commands are replayed with `trusted`, as they were authorized when they were processed
+    let journaled = cqrs_kind == "Command"
+        && matches!(macro_args.persistence, PersistenceStrategy::Journal { .. });
+    let trustable = journaled
+        && cqrs_fns_attributes
+            .iter()
+            .any(|attributes| attributes.required_role.is_some());
+    let generate_rhs_cqrs_call = |trustable: bool| {
+        cqrs_queries_sig_idents
+            .iter()
+            .zip(cqrs_fns_attributes)
+            .map(|((ident, args), attributes)| {
+                let fn_call = format_ident!("{}", snake_case_with_sep(&ident.to_string(), "_"));
+                let command = format!("{}::{}", enum_ident, get_cqrs_enum_variant_ident(ident));
+                generate_authorized_call(
+                    quote! {
+                        #domain_model_lock_var. #fn_call ( #(#args),*)
+                    },
+                    attributes.required_role.as_ref(),
+                    &command,
+                    trustable,
+                )
+            })
+            .collect::<Vec<TokenStream>>()
+    };
+    let rhs_cqrs_call = generate_rhs_cqrs_call(trustable);
+
+    let effects_match_statements =
+        generate_effects_match_statements(domain_model_struct_ident, effect);
+    let (validate_statement, validate_fn) =
+        generate_validation(&enum_ident, cqrs_queries_sig_idents, cqrs_fns_attributes);
+
+    let (invariants_snapshot_statement, check_invariants_statement) =
+        generate_invariants_statements(domain_model, &domain_model_lock_var);
+    // with undo, a command is recorded in the order it changed the model
+    let undoable = cqrs_kind == "Command" && macro_args.undo_limit.is_some();
+    // with tracing, a command's wait for the model's other commands is timed
+    let traced = cqrs_kind == "Command" && macro_args.tracing && swappable_lock;
+    let (commands_guard_statement, release_commands_statement) = generate_commands_guard_statements(
+        domain_model,
+        &domain_model_lock_var,
+        undoable || traced,
+    );
+    let (
+        tracing_before_locking,
+        tracing_after_locking,
+        tracing_after_processing,
+        tracing_after_persistence,
+    ) = generate_tracing_statements(macro_args, cqrs_kind, !commands_guard_statement.is_empty());
+    let commands_guard_statement = quote! {
+        #tracing_before_locking
+        #commands_guard_statement
+        #tracing_after_locking
+    };
+    let (before_command_statements, after_command_statements) = if cqrs_kind == "Command" {
+        let journal_entry_statement =
+            generate_journal_entry_statement(&macro_args.persistence, domain_model_struct_ident);
+        let undo_snapshot_statement = generate_undo_snapshot_statement(
+            macro_args,
+            domain_model_struct_ident,
+            domain_model_lock_ident,
+            &domain_model_lock_var,
+        );
+        let record_undo_statement = generate_record_undo_statement(macro_args);
+        let update_state_statement =
+            generate_update_state_statement(&macro_args.persistence, &domain_model_lock_field);
+        (
+            quote! {
+                #journal_entry_statement
+                #commands_guard_statement
+                #invariants_snapshot_statement
+                #undo_snapshot_statement
+            },
             quote! {
-                #enum_ident::#variant
+                #check_invariants_statement
+                #record_undo_statement
+                #release_commands_statement
+                #update_state_statement
+            },
+        )
+    } else {
+        (commands_guard_statement, quote! {})
+    };
+
+    let result_type = if cqrs_kind == "Command" {
+        quote! {(state_changed, result)}
+    } else {
+        quote! {result}
+    };
+
+    let domain_model_name = domain_model_struct_ident.to_string();
+    let check_preview_invariants_statement = checks_invariants.then(|| {
+        quote! {
+            if state_changed {
+                <#domain_model_lock_ident as generate_cqrs_api::SwappableModelLock<#domain_model_struct_ident>>::read_model(
+                    #domain_model_lock_var,
+                    <#domain_model_struct_ident as CqrsModel>::check_invariants,
+                )
+                .map_err(|rule| ProcessingError::InvariantViolated {
+                    model: #domain_model_name.to_string(),
+                    rule,
+                })?;
             }
-        })
+        }
+    });
+    // only with the option `preview`: a command runs on a copy of the model, a query doesn't change the state anyways
+    let (preview, preview_with) = if macro_args.preview && cqrs_kind == "Command" {
+        let rhs_cqrs_call = generate_rhs_cqrs_call(false);
+        let (preview_guard_statement, _) =
+            generate_commands_guard_statements(domain_model, &domain_model_lock_var, false);
+        (
+            quote! {
+                impl generate_cqrs_api::PreviewableCqrs for #enum_ident {
+                    fn preview(self) -> Result<(bool, Vec<Effect>), ProcessingError> {
+                        self.preview_with(#lifecycle_impl_ident::get_singleton())
+                    }
+                }
+            },
+            quote! {
+                /// processes on a copy of the model, leaving the app state untouched and unpersisted.
+                /// The effects refer to the copy.
+                pub fn preview_with(self, lifecycle: &#lifecycle_impl_ident) -> Result<(bool, Vec<Effect>), ProcessingError> {
+                    #validate_statement
+                    let app_state = &lifecycle.#app_state_field;
+                    let #domain_model_lock_var = &app_state.#domain_model_lock_field;
+                    #preview_guard_statement
+                    let #domain_model_lock_var = &<#domain_model_lock_ident as CqrsModelLock<#domain_model_struct_ident>>::for_model(
+                        <#domain_model_lock_ident as generate_cqrs_api::SwappableModelLock<#domain_model_struct_ident>>::read_model(
+                            #domain_model_lock_var,
+                            #domain_model_struct_ident::clone,
+                        ),
+                    );
+                    let (state_changed, result) = match self {
+                        #(#lhs_cqrs_call => #rhs_cqrs_call,)*
+                    }
+                    .map_err(ProcessingError::#processing_error)?;
+                    #check_preview_invariants_statement
+                    Ok((
+                        state_changed,
+                        result
+                            .into_iter()
+                            .map(|effect| match effect {
+                                #(#effects_match_statements)*
+                            })
+                            .collect(),
+                    ))
+                }
+            },
+        )
+    } else {
+        (quote! {}, quote! {})
     };
 
-    let rhs_cqrs_call = cqrs_queries_sig_idents.iter().map(|(ident, args)| {
-        let fn_call = format_ident!("{}", snake_case_with_sep(&ident.to_string(), "_"));
+    let (process_fn, processing_fn, process_layers) = generate_process_layers(
+        lifecycle,
+        cqrs_kind,
+        &enum_ident,
+        &domain_model_name,
+        cqrs_queries_sig_idents,
+    );
+    let (processing_signature, trusted_param, process_authorized, replay_with) = if trustable {
+        (
+            quote! {
+                /// processes the call, asking the lifecycle's authorization unless the command is `trusted`
+                fn process_authorized
+            },
+            quote! { , trusted: bool },
+            quote! {
+                #process_fn(self, lifecycle: &#lifecycle_impl_ident) -> Result<Vec<Effect>, ProcessingError> {
+                    self.process_authorized(lifecycle, false)
+                }
+            },
+            quote! { self.process_authorized(lifecycle, true) },
+        )
+    } else {
+        (
+            process_fn,
+            quote! {},
+            quote! {},
+            quote! { self.#processing_fn(lifecycle) },
+        )
+    };
+    // only for persistence = "journal"
+    let replay_with = journaled.then(|| {
         quote! {
-            #domain_model_lock_var. #fn_call ( #(#args),*)
+            /// replays the command from the journal, skipping the layers of `process_with()` and the authorization,
+            /// which ran when it was processed
+            fn replay_with(self, lifecycle: &#lifecycle_impl_ident) -> Result<Vec<Effect>, ProcessingError> {
+                #replay_with
+            }
         }
     });
 
-    let effects_match_statements = effect.1.iter().map(|variant| {
+    // generate final code
+    quote! {
+        impl Cqrs for #enum_ident{
+            type Effect = Effect;
+            type Error = ProcessingError;
+            fn process(self) -> Result<Vec<Effect>, ProcessingError> {
+                self.process_with(#lifecycle_impl_ident::get_singleton())
+            }
+        }
+        #preview
+        impl #enum_ident {
+            #processing_signature(self, lifecycle: &#lifecycle_impl_ident #trusted_param) -> Result<Vec<Effect>, ProcessingError> {
+                #validate_statement
+                let app_state = &lifecycle.#app_state_field;
+                let #domain_model_lock_var = &app_state.#domain_model_lock_field;
+                #before_command_statements
+                let #result_type = match self {
+                    #(#lhs_cqrs_call => #rhs_cqrs_call,)*
+                }
+                .map_err(ProcessingError::#processing_error)?;
+            #tracing_after_processing
+            #after_command_statements
+            #tracing_after_persistence
+            Ok(result
+                .into_iter()
+                .map(|effect| match effect {
+                    #(#effects_match_statements)*
+                })
+                .collect())
+            }
+            #process_authorized
+            #replay_with
+            #preview_with
+            #process_layers
+            #validate_fn
+        }
+    }
+}
+
+/// generates the statements of a `process_with()` layer, calling the given inner fn
+type LayerGenerator<'a> = Box<dyn Fn(&Ident) -> TokenStream + 'a>;
+
+/// generates the layers of `process_with()` wrapping the call's processing: the `tracing` span around the metrics around the interceptors
+/// (only for the feature `tracing` and the options `metrics` and `interceptors`), besides `variant_name()`, used by all of them.
+/// @returns (the signature of the fn processing the call, its name, the layers)
+fn generate_process_layers(
+    lifecycle: (&Ident, &MacroArgs),
+    cqrs_kind: &str,
+    enum_ident: &Ident,
+    domain_model_name: &str,
+    cqrs_sig_idents: &[(Ident, Vec<Ident>)],
+) -> (TokenStream, Ident, TokenStream) {
+    let (lifecycle_impl_ident, macro_args) = lifecycle;
+    let process_with_doc = " processes on the given lifecycle instead of the global singleton, e.g. to run isolated app states in tests";
+    if !macro_args.interceptors && !macro_args.metrics && !macro_args.tracing {
+        return (
+            quote! {
+                /// processes on the given lifecycle instead of the global singleton, e.g. to run isolated app states in tests
+                pub fn process_with
+            },
+            format_ident!("process_with"),
+            quote! {},
+        );
+    }
+    let process_with = format_ident!("process_with");
+    let generate_layer = |layer_fn: &Ident, doc: &str, layer: TokenStream| {
+        let signature = if *layer_fn == process_with {
+            let process_with_doc = format!("{process_with_doc}.");
+            quote! {
+                #[doc = #process_with_doc]
+                #[doc = #doc]
+                pub fn #layer_fn
+            }
+        } else {
+            quote! {
+                #[doc = #doc]
+                fn #layer_fn
+            }
+        };
+        quote! {
+            #signature(self, lifecycle: &#lifecycle_impl_ident) -> Result<Vec<Effect>, ProcessingError> {
+                #layer
+            }
+        }
+    };
+    // from the innermost to the outermost: (the layer's fn, unless it is the outermost, its doc, its statements calling the inner fn)
+    let mut enabled_layers: Vec<(&str, &str, LayerGenerator)> = Vec::new();
+    if macro_args.interceptors {
+        enabled_layers.push((
+            "process_intercepted",
+            " The lifecycle's interceptors run around the call and can reject it.",
+            Box::new(|processing_fn| generate_intercepting_layer(domain_model_name, processing_fn)),
+        ));
+    }
+    if macro_args.metrics {
+        enabled_layers.push((
+            "process_measured",
+            " The call's count, errors and latency are recorded in the lifecycle's metrics.",
+            Box::new(|processing_fn| generate_metrics_layer(domain_model_name, processing_fn)),
+        ));
+    }
+    if macro_args.tracing {
+        enabled_layers.push((
+            "process_traced",
+            " The call is traced in a `tracing` span, recording its timings and errors.",
+            Box::new(|processing_fn| {
+                generate_tracing_layer(domain_model_name, cqrs_kind, processing_fn)
+            }),
+        ));
+    }
+    let outermost_layer = enabled_layers.len() - 1;
+    let mut processing_fn = format_ident!("process_inner");
+    let layers = enabled_layers
+        .into_iter()
+        .enumerate()
+        .map(|(index, (layer_fn, doc, generate_statements))| {
+            let layer_fn = if index == outermost_layer {
+                process_with.clone()
+            } else {
+                format_ident!("{layer_fn}")
+            };
+            let layer = generate_layer(&layer_fn, doc, generate_statements(&processing_fn));
+            processing_fn = layer_fn;
+            layer
+        })
+        .collect::<Vec<TokenStream>>();
+    let variant_idents = cqrs_sig_idents
+        .iter()
+        .map(|(ident, _)| get_cqrs_enum_variant_ident(ident))
+        .collect::<Vec<Ident>>();
+    let variant_names = variant_idents.iter().map(Ident::to_string);
+    (
+        quote! {
+            /// processes the call, wrapped by `process_with()`
+            fn process_inner
+        },
+        format_ident!("process_inner"),
+        quote! {
+            #(#layers)*
+            /// the name of the called variant, like "AddItem"
+            pub fn variant_name(&self) -> &'static str {
+                match *self {
+                    #(#enum_ident::#variant_idents { .. } => #variant_names,)*
+                }
+            }
+        },
+    )
+}
+
+/// generates the statements locking and releasing the model's `SwappableModelLock::lock_commands()`.
+/// For models checking invariants, the lock keeps other calls from seeing the model between a command and its rollback.
+/// For undoable commands, it keeps other commands and the restores of `undo()` from changing the model between the undo snapshot and the recorded command.
+/// For traced commands, waiting for it is recorded as the wait for the model's lock.
+/// The lock is released before persisting, which may lock the journal's commands.
+/// @param locked: if the call needs the lock besides checking invariants
+/// @returns (the lock statement, the release statement)
+fn generate_commands_guard_statements(
+    domain_model: DomainModel,
+    domain_model_lock_var: &Ident,
+    locked: bool,
+) -> (TokenStream, TokenStream) {
+    let (domain_model_ident, domain_model_lock_ident, checks_invariants, _) = domain_model;
+    if !checks_invariants && !locked {
+        return (quote! {}, quote! {});
+    }
+    (
+        quote! {
+            let commands_guard = <#domain_model_lock_ident as generate_cqrs_api::SwappableModelLock<#domain_model_ident>>::lock_commands(#domain_model_lock_var);
+        },
+        quote! {
+            drop(commands_guard);
+        },
+    )
+}
+
+/// generates the statements copying the model before a command and rolling the command back,
+/// if it changed the model and violated `CqrsModel::check_invariants()` (only for models checking invariants).
+/// They run under the model's `SwappableModelLock::lock_commands()`, see `generate_commands_guard_statements()`.
+/// @returns (the statement copying the model, the statement after the command)
+fn generate_invariants_statements(
+    domain_model: DomainModel,
+    domain_model_lock_var: &Ident,
+) -> (TokenStream, TokenStream) {
+    let (domain_model_ident, domain_model_lock_ident, checks_invariants, _) = domain_model;
+    if !checks_invariants {
+        return (quote! {}, quote! {});
+    }
+    let domain_model_name = domain_model_ident.to_string();
+    let swappable_model_lock = quote! {
+        <#domain_model_lock_ident as generate_cqrs_api::SwappableModelLock<#domain_model_ident>>
+    };
+    (
+        quote! {
+            let invariants_snapshot =
+                #swappable_model_lock::read_model(#domain_model_lock_var, #domain_model_ident::clone);
+        },
+        quote! {
+            if state_changed {
+                if let Err(rule) = #swappable_model_lock::read_model(
+                    #domain_model_lock_var,
+                    <#domain_model_ident as CqrsModel>::check_invariants,
+                ) {
+                    #swappable_model_lock::replace_model(#domain_model_lock_var, invariants_snapshot);
+                    return Err(ProcessingError::InvariantViolated {
+                        model: #domain_model_name.to_string(),
+                        rule,
+                    });
+                }
+            }
+        },
+    )
+}
+
+/// generates the match arms converting the model's effects into the generated `Effect`,
+/// like `MyModelEffect::RenderItems(items) => Effect::MyModelRenderItems(items),`
+pub(crate) fn generate_effects_match_statements(
+    domain_model_struct_ident: &Ident,
+    effect: (&Ident, &[Variant]),
+) -> Vec<TokenStream> {
+    effect.1.iter().map(|variant| {
         // use the type as the ident, as enum variant payloads don't have a name
         let variant_fields_idents = match &variant.fields {
             Fields::Unit => {
@@ -143,69 +593,48 @@ fn generate_cqrs_functions(
                 #effect_ident::#lhs_ident ( #(#variant_fields_idents),* ) => Effect ::#rhs_ident ( #(#variant_fields_idents),* ),
             }
         }
-    });
-
-    let update_state_statement = if cqrs_kind == "Command" {
-        quote! {
-            if state_changed {
-                app_state.mark_dirty();
-                LifecycleImpl::persist()?;
-            }
-        }
-    } else {
-        quote! {}
-    };
-
-    let result_type = if cqrs_kind == "Command" {
-        quote! {(state_changed, result)}
-    } else {
-        quote! {result}
-    };
-
-    // generate final code
-    quote! {
-        impl Cqrs for #enum_ident{
-            fn process(self) -> Result<Vec<Effect>, ProcessingError> {
-                let lifecycle = #lifecycle_impl_ident::get_singleton();
-                let app_state = &lifecycle.app_state;
-                let #domain_model_lock_var = &app_state.#domain_model_lock_var;
-                let #result_type = match self {
-                    #(#lhs_cqrs_call => #rhs_cqrs_call,)*
-                }
-                .map_err(ProcessingError::#processing_error)?;
-            #update_state_statement
-            Ok(result
-                .into_iter()
-                .map(|effect| match effect {
-                    #(#effects_match_statements)*
-                })
-                .collect())
-            }
-        }
-    }
+    }).collect()
 }
 
 fn generate_cqrs_query_enum(
     cqrs_q_fns_sig_tipes: &[(Ident, Vec<TokenStream>)],
     domain_model_struct_ident: &Ident,
+    serializable: bool,
 ) -> TokenStream {
-    generate_cqrs_enum(cqrs_q_fns_sig_tipes, "Query", domain_model_struct_ident)
+    generate_cqrs_enum(
+        cqrs_q_fns_sig_tipes,
+        "Query",
+        domain_model_struct_ident,
+        serializable,
+    )
 }
 fn generate_cqrs_command_enum(
     cqrs_q_fns_sig_tipes: &[(Ident, Vec<TokenStream>)],
     domain_model_struct_ident: &Ident,
+    serializable: bool,
 ) -> TokenStream {
-    generate_cqrs_enum(cqrs_q_fns_sig_tipes, "Command", domain_model_struct_ident)
+    generate_cqrs_enum(
+        cqrs_q_fns_sig_tipes,
+        "Command",
+        domain_model_struct_ident,
+        serializable,
+    )
 }
 fn generate_cqrs_enum(
     cqrs_q_fns_sig_tipes: &[(Ident, Vec<TokenStream>)],
     cqrs_kind: &str,
     domain_model_struct_ident: &Ident,
+    serializable: bool,
 ) -> TokenStream {
     let enum_variants = generate_cqrs_enum_variants_with_argument_types(cqrs_q_fns_sig_tipes);
     let cqrs_ident = format_ident!("{domain_model_struct_ident}{cqrs_kind}");
+    let derives = if serializable {
+        quote! { #[derive(Debug, Serialize, Deserialize)] }
+    } else {
+        quote! { #[derive(Debug)] }
+    };
     let code = quote! {
-        #[derive(Debug)]
+        #derives
         pub enum #cqrs_ident {
             #(#enum_variants),*
         }
@@ -215,6 +644,89 @@ fn generate_cqrs_enum(
     code
 }
 
+/// generates `AnyCommand` and `AnyQuery` (for `serializable_cqrs`), wrapping every model's commands and queries.
+/// They are serialized externally tagged by the model's name, like `{"MyModel":{"AddItem":"text"}}`.
+pub(crate) fn generate_any_cqrs_enums(
+    lifecycle_impl_ident: &Ident,
+    models: &[ModelNEffectsNErrors],
+    macro_args: &MacroArgs,
+) -> TokenStream {
+    if !macro_args.serializable_cqrs {
+        return quote! {};
+    }
+    let domain_model_idents = models
+        .iter()
+        .map(|model| &model.domain_model_ident)
+        .collect::<Vec<&Ident>>();
+    let generate_any_enum = |cqrs_kind: &str, cqrs_plural: &str| {
+        let any_ident = format_ident!("Any{cqrs_kind}");
+        let cqrs_idents = domain_model_idents
+            .iter()
+            .map(|domain_model_ident| format_ident!("{domain_model_ident}{cqrs_kind}"))
+            .collect::<Vec<Ident>>();
+        let doc = format!(" every model's {cqrs_plural}, e.g. to log, queue or replay them");
+        let replay_with = (cqrs_kind == "Command"
+            && matches!(macro_args.persistence, PersistenceStrategy::Journal { .. }))
+        .then(|| {
+            quote! {
+                /// replays the command from the journal (only for persistence = "journal")
+                fn replay_with(self, lifecycle: &#lifecycle_impl_ident) -> Result<Vec<Effect>, ProcessingError> {
+                    match self {
+                        #(#any_ident::#domain_model_idents(cqrs) => cqrs.replay_with(lifecycle)),*
+                    }
+                }
+            }
+        });
+        let preview = (macro_args.preview && cqrs_kind == "Command").then(|| {
+            quote! {
+                impl generate_cqrs_api::PreviewableCqrs for #any_ident {
+                    fn preview(self) -> Result<(bool, Vec<Effect>), ProcessingError> {
+                        match self {
+                            #(#any_ident::#domain_model_idents(cqrs) => cqrs.preview()),*
+                        }
+                    }
+                }
+            }
+        });
+        quote! {
+            #[doc = #doc]
+            #[derive(Debug, Serialize, Deserialize)]
+            pub enum #any_ident {
+                #(#domain_model_idents(#cqrs_idents)),*
+            }
+            impl Cqrs for #any_ident {
+                type Effect = Effect;
+                type Error = ProcessingError;
+                fn process(self) -> Result<Vec<Effect>, ProcessingError> {
+                    self.process_with(#lifecycle_impl_ident::get_singleton())
+                }
+            }
+            #preview
+            impl #any_ident {
+                pub fn process_with(self, lifecycle: &#lifecycle_impl_ident) -> Result<Vec<Effect>, ProcessingError> {
+                    match self {
+                        #(#any_ident::#domain_model_idents(cqrs) => cqrs.process_with(lifecycle)),*
+                    }
+                }
+                #replay_with
+            }
+            #(
+                impl From<#cqrs_idents> for #any_ident {
+                    fn from(cqrs: #cqrs_idents) -> Self {
+                        #any_ident::#domain_model_idents(cqrs)
+                    }
+                }
+            )*
+        }
+    };
+    let any_command = generate_any_enum("Command", "commands");
+    let any_query = generate_any_enum("Query", "queries");
+    quote! {
+        #any_command
+        #any_query
+    }
+}
+
 fn generate_cqrs_enum_variants_with_argument_idents(
     cqrs_fns_sig_idents: &[(Ident, Vec<Ident>)],
 ) -> Vec<TokenStream> {
@@ -244,18 +756,7 @@ fn generate_cqrs_enum_variants(
     cqrs_fns_sig_tipes
         .iter()
         .map(|(ident, args)| {
-            // remove the prefix, if it is a variant of "command" or "query"
-            let ident_string = ident.to_string();
-            let cleaned_ident = if let Some(split_pos) = ident_string.find('_') {
-                match &ident_string[0..split_pos] {
-                    "command" | "com" | "query" => &ident_string[split_pos + 1..],
-                    _ => ident_string.as_str(),
-                }
-            } else {
-                ident_string.as_str()
-            };
-            let enum_variant = format_ident!("{}", pascal_case_with_sep(cleaned_ident, "_"));
-
+            let enum_variant = get_cqrs_enum_variant_ident(ident);
             if args.is_empty() {
                 quote! {#enum_variant}
             } else {
@@ -267,6 +768,21 @@ fn generate_cqrs_enum_variants(
         .collect::<Vec<TokenStream>>()
 }
 
+/// converts the CQRS function's name into its enum variant, like `command_clean_list` => `CleanList`
+pub(crate) fn get_cqrs_enum_variant_ident(fn_ident: &Ident) -> Ident {
+    // remove the prefix, if it is a variant of "command" or "query"
+    let ident_string = fn_ident.to_string();
+    let cleaned_ident = if let Some(split_pos) = ident_string.find('_') {
+        match &ident_string[0..split_pos] {
+            "command" | "com" | "query" => &ident_string[split_pos + 1..],
+            _ => ident_string.as_str(),
+        }
+    } else {
+        ident_string.as_str()
+    };
+    format_ident!("{}", pascal_case_with_sep(cleaned_ident, "_"))
+}
+
 /// extracts the signature of passed functions,
 /// returning the types of the arguments
 /// e.g.: foo(name: String, age: usize) => [foo [String, usize]]
@@ -311,6 +827,11 @@ fn get_cqrs_fns_sig_idents(cqrs_fns: &[ImplItemFn]) -> Vec<(Ident, Vec<Ident>)>
         .collect::<Vec<(Ident, Vec<Ident>)>>()
 }
 
+/// extracts the `#[cqrs(requires = "admin")]` and the arguments' `#[validate(...)]` of the passed functions
+fn get_cqrs_fns_attributes(cqrs_fns: &[ImplItemFn]) -> Vec<CqrsFnAttributes> {
+    cqrs_fns.iter().map(get_cqrs_fn_attributes).collect()
+}
+
 /// @returns tuple (CQRS Queries, CQRS Commands)
 fn get_cqrs_functions(
     domain_model_lock_ident: &Ident,
@@ -482,10 +1003,12 @@ mod tests {
     use crate::{
         generate_api_macro_impl::{BasePath, ModelNEffectsNErrors},
         generating::generate_cqrs_impl::{
+            generate_any_cqrs_enums, generate_commands_guard_statements,
             generate_cqrs_command_enum, generate_cqrs_functions, generate_cqrs_impl,
-            generate_cqrs_query_enum, get_cqrs_fns_sig_idents, get_cqrs_fns_sig_tipes,
-            get_cqrs_functions,
+            generate_cqrs_query_enum, generate_invariants_statements, get_cqrs_fns_attributes,
+            get_cqrs_fns_sig_idents, get_cqrs_fns_sig_tipes, get_cqrs_functions,
         },
+        parsing::macro_args::{MacroArgs, PersistenceStrategy},
     };
 
     const CODE: &str = r#"
@@ -848,10 +1371,12 @@ mod tests {
         let cqrs_q_enum = generate_cqrs_query_enum(
             &get_cqrs_fns_sig_tipes(&cqrs_q),
             &format_ident!("MyGoodDomainModel"),
+            false,
         );
         let cqrs_c_enum = generate_cqrs_command_enum(
             &get_cqrs_fns_sig_tipes(&cqrs_c),
             &format_ident!("MyGoodDomainModel"),
+            false,
         );
         let result = quote! {
             #cqrs_q_enum
@@ -888,10 +1413,12 @@ mod tests {
         let cqrs_q_enum = generate_cqrs_query_enum(
             &get_cqrs_fns_sig_tipes(&cqrs_q),
             &format_ident!("MyGoodDomainModel"),
+            false,
         );
         let cqrs_c_enum = generate_cqrs_command_enum(
             &get_cqrs_fns_sig_tipes(&cqrs_c),
             &format_ident!("MyGoodDomainModel"),
+            false,
         );
         let (cqrs_q_2, cqrs_c_2) = get_cqrs_functions(
             &format_ident!("MySecondDomainModelLock"),
@@ -902,10 +1429,12 @@ mod tests {
         let cqrs_q_enum_2 = generate_cqrs_query_enum(
             &get_cqrs_fns_sig_tipes(&cqrs_q_2),
             &format_ident!("MySecondDomainModel"),
+            false,
         );
         let cqrs_c_enum_2 = generate_cqrs_command_enum(
             &get_cqrs_fns_sig_tipes(&cqrs_c_2),
             &format_ident!("MySecondDomainModel"),
+            false,
         );
         let result = quote! {
             #cqrs_q_enum
@@ -1099,20 +1628,34 @@ mod tests {
             .collect();
         let lifecycle_impl_ident: Ident = format_ident!("LifecycleImpl");
         let cqrs_queries = generate_cqrs_functions(
-            &lifecycle_impl_ident,
+            (&lifecycle_impl_ident, &MacroArgs::default()),
             "Query",
-            &domain_model_struct_ident,
-            &domain_model_lock_ident,
-            &get_cqrs_fns_sig_idents(&cqrs_q),
+            (
+                &domain_model_struct_ident,
+                &domain_model_lock_ident,
+                false,
+                false,
+            ),
+            (
+                &get_cqrs_fns_sig_idents(&cqrs_q),
+                &get_cqrs_fns_attributes(&cqrs_q),
+            ),
             (&effect_ident, &effect_variants),
             &processing_error,
         );
         let cqrs_commands = generate_cqrs_functions(
-            &lifecycle_impl_ident,
+            (&lifecycle_impl_ident, &MacroArgs::default()),
             "Command",
-            &domain_model_struct_ident,
-            &domain_model_lock_ident,
-            &get_cqrs_fns_sig_idents(&cqrs_c),
+            (
+                &domain_model_struct_ident,
+                &domain_model_lock_ident,
+                false,
+                false,
+            ),
+            (
+                &get_cqrs_fns_sig_idents(&cqrs_c),
+                &get_cqrs_fns_attributes(&cqrs_c),
+            ),
             (&effect_ident, &effect_variants),
             &processing_error,
         );
@@ -1123,8 +1666,15 @@ mod tests {
 
         let expected = quote! {
             impl Cqrs for MyGoodDomainModelQuery {
-                fn process (self) -> Result < Vec < Effect > , ProcessingError > {
-                    let lifecycle = LifecycleImpl::get_singleton();
+                type Effect = Effect;
+                type Error = ProcessingError;
+                fn process(self) -> Result<Vec<Effect>, ProcessingError> {
+                    self.process_with(LifecycleImpl::get_singleton())
+                }
+            }
+            impl MyGoodDomainModelQuery {
+                /// processes on the given lifecycle instead of the global singleton, e.g. to run isolated app states in tests
+                pub fn process_with(self, lifecycle: &LifecycleImpl) -> Result<Vec<Effect>, ProcessingError> {
                     let app_state = &lifecycle.app_state;
                     let my_good_domain_model_lock = &app_state.my_good_domain_model_lock;
                     let result = match self {
@@ -1142,11 +1692,18 @@ mod tests {
                                 Effect::MyGoodDomainModelRenderMyGoodDomainModel(model_lock)
                         , })
                         .collect())
-                    }
                 }
+            }
             impl Cqrs for MyGoodDomainModelCommand {
+                type Effect = Effect;
+                type Error = ProcessingError;
                 fn process(self) -> Result<Vec<Effect>, ProcessingError> {
-                    let lifecycle = LifecycleImpl::get_singleton();
+                    self.process_with(LifecycleImpl::get_singleton())
+                }
+            }
+            impl MyGoodDomainModelCommand {
+                /// processes on the given lifecycle instead of the global singleton, e.g. to run isolated app states in tests
+                pub fn process_with(self, lifecycle: &LifecycleImpl) -> Result<Vec<Effect>, ProcessingError> {
                     let app_state = &lifecycle.app_state;
                     let my_good_domain_model_lock = &app_state.my_good_domain_model_lock;
                     let (state_changed, result) = match self {
@@ -1169,8 +1726,8 @@ mod tests {
                     }
                     .map_err(ProcessingError::MyGoodProcessingError)?;
                     if state_changed {
-                        app_state.mark_dirty();
-                        LifecycleImpl::persist()?;
+                        app_state.mark_model_dirty(generate_cqrs_api::ModelId("my_good_domain_model_lock"));
+                        lifecycle.persist_instance()?;
                     }
                     Ok(result
                     .into_iter()
@@ -1188,6 +1745,124 @@ mod tests {
         assert_eq!(expected.to_string(), result.to_string());
     }
 
+    #[test]
+    fn generate_cqrs_fns_test_custom_names() {
+        let ast = syn::parse_file(CODE_SECOND_MODEL).expect("test oracle should be parsable");
+        let domain_model_lock_ident = format_ident!("MySecondDomainModelLock");
+        let processing_error = format_ident!("MySecondProcessingError");
+        let effect_code = parse_str::<syn::ItemEnum>(
+            r#"pub enum MySecondDomainModelEffect {
+                RenderItems(model_lock)
+            }
+            "#,
+        )
+        .expect("Couldn't parse test oracle!");
+        let effect_variants: Vec<syn::Variant> = effect_code
+            .variants
+            .into_pairs()
+            .map(|pair| pair.value().clone())
+            .collect();
+        let (_, cqrs_c) = get_cqrs_functions(
+            &domain_model_lock_ident,
+            &effect_code.ident,
+            &processing_error,
+            &ast,
+        );
+        let macro_args = MacroArgs {
+            // independent of the feature the tests run with
+            tracing: false,
+            ..syn::parse2::<MacroArgs>(quote! {
+                "src/domain/second.rs",
+                app_state_field = "state",
+                preview,
+                field(MySecondDomainModelLock = "seconds")
+            })
+            .expect("test oracle args should be parsable")
+        };
+        let result = generate_cqrs_functions(
+            (&format_ident!("MyAppLifecycle"), &macro_args),
+            "Command",
+            (
+                &format_ident!("MySecondDomainModel"),
+                &domain_model_lock_ident,
+                false,
+                false,
+            ),
+            (
+                &get_cqrs_fns_sig_idents(&cqrs_c),
+                &get_cqrs_fns_attributes(&cqrs_c),
+            ),
+            (&effect_code.ident, &effect_variants),
+            &processing_error,
+        );
+
+        let expected = quote! {
+            impl Cqrs for MySecondDomainModelCommand {
+                type Effect = Effect;
+                type Error = ProcessingError;
+                fn process(self) -> Result<Vec<Effect>, ProcessingError> {
+                    self.process_with(MyAppLifecycle::get_singleton())
+                }
+            }
+            impl generate_cqrs_api::PreviewableCqrs for MySecondDomainModelCommand {
+                fn preview(self) -> Result<(bool, Vec<Effect>), ProcessingError> {
+                    self.preview_with(MyAppLifecycle::get_singleton())
+                }
+            }
+            impl MySecondDomainModelCommand {
+                /// processes on the given lifecycle instead of the global singleton, e.g. to run isolated app states in tests
+                pub fn process_with(self, lifecycle: &MyAppLifecycle) -> Result<Vec<Effect>, ProcessingError> {
+                    let app_state = &lifecycle.state;
+                    let my_second_domain_model_lock = &app_state.seconds;
+                    let (state_changed, result) = match self {
+                        MySecondDomainModelCommand::AddObject(item, priority) => my_second_domain_model_lock.add_object(item, priority),
+                        MySecondDomainModelCommand::CleanAllObjects => my_second_domain_model_lock.clean_all_objects(),
+                        MySecondDomainModelCommand::CopyItem(item_pos) => my_second_domain_model_lock.copy_item(item_pos),
+                    }
+                    .map_err(ProcessingError::MySecondProcessingError)?;
+                    if state_changed {
+                        app_state.mark_model_dirty(generate_cqrs_api::ModelId("seconds"));
+                        lifecycle.persist_instance()?;
+                    }
+                    Ok(result
+                        .into_iter()
+                        .map(|effect| match effect {
+                            MySecondDomainModelEffect::RenderItems(model_lock) => Effect::MySecondDomainModelRenderItems(model_lock),
+                        })
+                        .collect())
+                }
+                /// processes on a copy of the model, leaving the app state untouched and unpersisted.
+                /// The effects refer to the copy.
+                pub fn preview_with(self, lifecycle: &MyAppLifecycle) -> Result<(bool, Vec<Effect>), ProcessingError> {
+                    let app_state = &lifecycle.state;
+                    let my_second_domain_model_lock = &app_state.seconds;
+                    let my_second_domain_model_lock = &<MySecondDomainModelLock as CqrsModelLock<MySecondDomainModel>>::for_model(
+                        <MySecondDomainModelLock as generate_cqrs_api::SwappableModelLock<MySecondDomainModel>>::read_model(
+                            my_second_domain_model_lock,
+                            MySecondDomainModel::clone,
+                        ),
+                    );
+                    let (state_changed, result) = match self {
+                        MySecondDomainModelCommand::AddObject(item, priority) => my_second_domain_model_lock.add_object(item, priority),
+                        MySecondDomainModelCommand::CleanAllObjects => my_second_domain_model_lock.clean_all_objects(),
+                        MySecondDomainModelCommand::CopyItem(item_pos) => my_second_domain_model_lock.copy_item(item_pos),
+                    }
+                    .map_err(ProcessingError::MySecondProcessingError)?;
+                    Ok((
+                        state_changed,
+                        result
+                            .into_iter()
+                            .map(|effect| match effect {
+                                MySecondDomainModelEffect::RenderItems(model_lock) => Effect::MySecondDomainModelRenderItems(model_lock),
+                            })
+                            .collect(),
+                    ))
+                }
+            }
+        };
+        assert_eq!(expected.to_string(), result.to_string());
+    }
+
     #[test]
     fn generate_cqrs_impl_test_two_models() {
         let ast = syn::parse_file(CODE).expect("test oracle for model one should be parsable");
@@ -1244,7 +1919,8 @@ mod tests {
             },
         ];
         let lifecycle_impl_ident: Ident = format_ident!("LifecycleImpl");
-        let generated_cqrs = generate_cqrs_impl(&lifecycle_impl_ident, &models);
+        let generated_cqrs =
+            generate_cqrs_impl(&lifecycle_impl_ident, &models, &MacroArgs::default());
         let result = quote! {
             #(#generated_cqrs)*
         };
@@ -1263,8 +1939,15 @@ mod tests {
                 RemoveItem(usize)
             }
             impl Cqrs for MyGoodDomainModelQuery {
-                fn process (self) -> Result < Vec < Effect > , ProcessingError > {
-                    let lifecycle = LifecycleImpl::get_singleton();
+                type Effect = Effect;
+                type Error = ProcessingError;
+                fn process(self) -> Result<Vec<Effect>, ProcessingError> {
+                    self.process_with(LifecycleImpl::get_singleton())
+                }
+            }
+            impl MyGoodDomainModelQuery {
+                /// processes on the given lifecycle instead of the global singleton, e.g. to run isolated app states in tests
+                pub fn process_with(self, lifecycle: &LifecycleImpl) -> Result<Vec<Effect>, ProcessingError> {
                     let app_state = &lifecycle.app_state;
                     let my_good_domain_model_lock = &app_state.my_good_domain_model_lock;
                     let result = match self {
@@ -1282,11 +1965,18 @@ mod tests {
                                 Effect::MyGoodDomainModelRenderMyGoodDomainModel(model_lock)
                         , })
                         .collect())
-                    }
                 }
+            }
             impl Cqrs for MyGoodDomainModelCommand {
+                type Effect = Effect;
+                type Error = ProcessingError;
                 fn process(self) -> Result<Vec<Effect>, ProcessingError> {
-                    let lifecycle = LifecycleImpl::get_singleton();
+                    self.process_with(LifecycleImpl::get_singleton())
+                }
+            }
+            impl MyGoodDomainModelCommand {
+                /// processes on the given lifecycle instead of the global singleton, e.g. to run isolated app states in tests
+                pub fn process_with(self, lifecycle: &LifecycleImpl) -> Result<Vec<Effect>, ProcessingError> {
                     let app_state = &lifecycle.app_state;
                     let my_good_domain_model_lock = &app_state.my_good_domain_model_lock;
                     let (state_changed, result) = match self {
@@ -1309,8 +1999,8 @@ mod tests {
                     }
                     .map_err(ProcessingError::MyGoodProcessingError)?;
                     if state_changed {
-                        app_state.mark_dirty();
-                        LifecycleImpl::persist()?;
+                        app_state.mark_model_dirty(generate_cqrs_api::ModelId("my_good_domain_model_lock"));
+                        lifecycle.persist_instance()?;
                     }
                     Ok(result
                     .into_iter()
@@ -1335,8 +2025,15 @@ mod tests {
                 CopyItem(usize)
             }
             impl Cqrs for MySecondDomainModelQuery {
+                type Effect = Effect;
+                type Error = ProcessingError;
                 fn process(self) -> Result<Vec<Effect>, ProcessingError> {
-                    let lifecycle = LifecycleImpl::get_singleton();
+                    self.process_with(LifecycleImpl::get_singleton())
+                }
+            }
+            impl MySecondDomainModelQuery {
+                /// processes on the given lifecycle instead of the global singleton, e.g. to run isolated app states in tests
+                pub fn process_with(self, lifecycle: &LifecycleImpl) -> Result<Vec<Effect>, ProcessingError> {
                     let app_state  = &lifecycle.app_state;
                     let my_second_domain_model_lock = &app_state.my_second_domain_model_lock;
                     let result = match self {
@@ -1355,8 +2052,15 @@ mod tests {
                 }
             }
             impl Cqrs for MySecondDomainModelCommand {
+                type Effect = Effect;
+                type Error = ProcessingError;
                 fn process(self) -> Result<Vec<Effect>, ProcessingError> {
-                    let lifecycle = LifecycleImpl::get_singleton();
+                    self.process_with(LifecycleImpl::get_singleton())
+                }
+            }
+            impl MySecondDomainModelCommand {
+                /// processes on the given lifecycle instead of the global singleton, e.g. to run isolated app states in tests
+                pub fn process_with(self, lifecycle: &LifecycleImpl) -> Result<Vec<Effect>, ProcessingError> {
                     let app_state = &lifecycle.app_state;
                     let my_second_domain_model_lock = &app_state.my_second_domain_model_lock;
                     let (state_changed, result) = match self {
@@ -1366,8 +2070,8 @@ mod tests {
                     }
                     .map_err(ProcessingError::MySecondProcessingError)?;
                     if state_changed {
-                        app_state.mark_dirty();
-                        LifecycleImpl::persist()?;
+                        app_state.mark_model_dirty(generate_cqrs_api::ModelId("my_second_domain_model_lock"));
+                        lifecycle.persist_instance()?;
                     }
                     Ok(result
                         .into_iter()
@@ -1383,4 +2087,138 @@ mod tests {
 
         assert_eq!(expected.to_string(), result.to_string());
     }
+
+    #[test]
+    fn lock_the_model_instance_checking_invariants_or_undoing() {
+        let (model, lock) = (
+            format_ident!("MyGoodDomainModel"),
+            format_ident!("MyGoodDomainModelLock"),
+        );
+        let lock_var = format_ident!("my_good_domain_model_lock");
+        let expected_guard = quote! {
+            let commands_guard = <MyGoodDomainModelLock as generate_cqrs_api::SwappableModelLock<MyGoodDomainModel>>::lock_commands(my_good_domain_model_lock);
+        };
+        for (checks_invariants, locked) in [(true, false), (false, true)] {
+            let (guard, release) = generate_commands_guard_statements(
+                (&model, &lock, checks_invariants, true),
+                &lock_var,
+                locked,
+            );
+            assert_eq!(expected_guard.to_string(), guard.to_string());
+            assert_eq!("drop (commands_guard) ;", release.to_string());
+        }
+        let (guard, release) =
+            generate_commands_guard_statements((&model, &lock, false, true), &lock_var, false);
+        let (snapshot, check) =
+            generate_invariants_statements((&model, &lock, false, true), &lock_var);
+        assert!(guard.is_empty() && release.is_empty() && snapshot.is_empty() && check.is_empty());
+    }
+
+    #[test]
+    fn generate_serializable_cqrs_enum() {
+        let ast = syn::parse_file(CODE).expect("test oracle should be parsable");
+        let (_, cqrs_c) = get_cqrs_functions(
+            &format_ident!("MyGoodDomainModelLock"),
+            &format_ident!("MyGoodDomainModelEffect"),
+            &format_ident!("MyGoodProcessingError"),
+            &ast,
+        );
+        let result = generate_cqrs_command_enum(
+            &get_cqrs_fns_sig_tipes(&cqrs_c),
+            &format_ident!("MyGoodDomainModel"),
+            true,
+        );
+        assert!(result.to_string().starts_with(
+            "# [derive (Debug , Serialize , Deserialize)] pub enum MyGoodDomainModelCommand"
+        ));
+    }
+
+    #[test]
+    fn generate_any_cqrs_enums_test() {
+        let models = ["MyGoodDomainModel", "MySecondDomainModel"]
+            .into_iter()
+            .map(|domain_model| ModelNEffectsNErrors {
+                base_path: BasePath("domain::model".to_string()),
+                ast: syn::parse_file("").unwrap(),
+                domain_model_ident: format_ident!("{domain_model}"),
+                domain_model_lock_ident: format_ident!("{domain_model}Lock"),
+                effect_ident: format_ident!("{domain_model}Effect"),
+                effect_variants: vec![],
+                error_ident: format_ident!("{domain_model}ProcessingError"),
+            })
+            .collect::<Vec<ModelNEffectsNErrors>>();
+        let lifecycle_impl_ident: Ident = format_ident!("LifecycleImpl");
+        assert!(
+            generate_any_cqrs_enums(&lifecycle_impl_ident, &models, &MacroArgs::default())
+                .is_empty()
+        );
+
+        let macro_args = MacroArgs {
+            serializable_cqrs: true,
+            ..Default::default()
+        };
+        let result = generate_any_cqrs_enums(&lifecycle_impl_ident, &models, &macro_args);
+        let expected_any_command = quote! {
+            #[doc = " every model's commands, e.g. to log, queue or replay them"]
+            #[derive(Debug, Serialize, Deserialize)]
+            pub enum AnyCommand {
+                MyGoodDomainModel(MyGoodDomainModelCommand),
+                MySecondDomainModel(MySecondDomainModelCommand)
+            }
+            impl Cqrs for AnyCommand {
+                type Effect = Effect;
+                type Error = ProcessingError;
+                fn process(self) -> Result<Vec<Effect>, ProcessingError> {
+                    self.process_with(LifecycleImpl::get_singleton())
+                }
+            }
+            impl AnyCommand {
+                pub fn process_with(self, lifecycle: &LifecycleImpl) -> Result<Vec<Effect>, ProcessingError> {
+                    match self {
+                        AnyCommand::MyGoodDomainModel(cqrs) => cqrs.process_with(lifecycle),
+                        AnyCommand::MySecondDomainModel(cqrs) => cqrs.process_with(lifecycle)
+                    }
+                }
+            }
+            impl From<MyGoodDomainModelCommand> for AnyCommand {
+                fn from(cqrs: MyGoodDomainModelCommand) -> Self {
+                    AnyCommand::MyGoodDomainModel(cqrs)
+                }
+            }
+            impl From<MySecondDomainModelCommand> for AnyCommand {
+                fn from(cqrs: MySecondDomainModelCommand) -> Self {
+                    AnyCommand::MySecondDomainModel(cqrs)
+                }
+            }
+        };
+        assert!(result
+            .to_string()
+            .starts_with(&expected_any_command.to_string()));
+        assert!(result.to_string().contains("pub enum AnyQuery"));
+
+        let macro_args = MacroArgs {
+            serializable_cqrs: true,
+            persistence: PersistenceStrategy::Journal {
+                snapshot_every: 100,
+            },
+            ..Default::default()
+        };
+        let result = generate_any_cqrs_enums(&lifecycle_impl_ident, &models, &macro_args);
+        let expected_replay_with = quote! {
+            /// replays the command from the journal (only for persistence = "journal")
+            fn replay_with(self, lifecycle: &LifecycleImpl) -> Result<Vec<Effect>, ProcessingError> {
+                match self {
+                    AnyCommand::MyGoodDomainModel(cqrs) => cqrs.replay_with(lifecycle),
+                    AnyCommand::MySecondDomainModel(cqrs) => cqrs.replay_with(lifecycle)
+                }
+            }
+        };
+        assert_eq!(
+            1,
+            result
+                .to_string()
+                .matches(&expected_replay_with.to_string())
+                .count()
+        );
+    }
 }
@@ -0,0 +1,623 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{parse_quote, ImplItem, ItemImpl};
+
+use crate::parsing::{
+    extract_type::get_type_as_capital_ident,
+    macro_args::{MacroArgs, PersistenceStrategy},
+};
+
+/// adds the default implementation of every `Lifecycle` function the user omitted (only for `default_lifecycle`).
+/// `type Error` has to be declared by the user, converting from the persister's error.
+/// The lifecycle is stored in a `static OnceLock`, its app state is loaded by the persister
+/// or created with `AppState::new()`, if the persister didn't find any.
+/// Additionally `new_instance()` creates lifecycles besides the singleton, e.g. for isolated tests.
+/// For persistence = "journal" the lifecycle has a `journal` field, whose commands are replayed on initialisation.
+/// For `undo` the lifecycle has an `undo_history` field, for `interceptors` an `interceptors` field,
+/// for `metrics` a `metrics` field, in which persisting is recorded, and for `authorization` an `authorization` field.
+/// For persistence = "debounced" the lifecycle has a `deferred_persistence` field, holding the lifecycle weakly
+/// to persist it in the background. Thus `new_instance()` returns it as `Arc`.
+/// @returns (the completed `impl Lifecycle`, the singleton's static and `new_instance()`)
+pub(crate) fn generate_default_lifecycle(
+    lifecycle_impl: TokenStream,
+    macro_args: &MacroArgs,
+) -> syn::Result<(TokenStream, TokenStream)> {
+    let Some(default_lifecycle) = &macro_args.default_lifecycle else {
+        return Ok((lifecycle_impl, quote! {}));
+    };
+    let app_config = &default_lifecycle.app_config;
+    let app_state = &default_lifecycle.app_state;
+    let persister = &default_lifecycle.persister;
+    let app_state_field = macro_args.app_state_field();
+
+    let journal_snapshot_every = match macro_args.persistence {
+        PersistenceStrategy::Journal { snapshot_every } => Some(snapshot_every),
+        _ => None,
+    };
+    // the journal's commands up to the persisted position are contained in the snapshot.
+    // No command is processed meanwhile, so that the position matches the persisted models.
+    let (read_journal_position, compact_journal) = match journal_snapshot_every {
+        Some(_) => (
+            quote! {
+                let journal_guard = self.journal.lock_commands();
+                let journal_position = self.#app_state_field.journal_position();
+            },
+            quote! {
+                drop(journal_guard);
+                self.journal.compact(journal_position).map_err(|error| {
+                    AppStatePersistError::<ProcessingError>::to_processing_error(&error)
+                })?;
+            },
+        ),
+        None => (quote! {}, quote! {}),
+    };
+    let undo_history = match macro_args.undo_limit {
+        Some(undo_limit) => quote! {
+            undo_history: generate_cqrs_api::UndoHistory::new(#undo_limit),
+        },
+        None => quote! {},
+    };
+    let interceptors = if macro_args.interceptors {
+        quote! {
+            interceptors: generate_cqrs_api::Interceptors::default(),
+        }
+    } else {
+        quote! {}
+    };
+    let metrics = if macro_args.metrics {
+        quote! {
+            metrics: generate_cqrs_api::MetricsRegistry::default(),
+        }
+    } else {
+        quote! {}
+    };
+    let authorization = if macro_args.authorization {
+        quote! {
+            authorization: generate_cqrs_api::Authorization::default(),
+        }
+    } else {
+        quote! {}
+    };
+    let persist_models = quote! {
+        self.persister
+            .persist_models(&self.#app_state_field, &dirty_models)
+            .map_err(|error| {
+                AppStatePersistError::<ProcessingError>::to_processing_error(&error)
+            })
+            .inspect_err(|_| self.#app_state_field.mark_models_dirty(&dirty_models))
+    };
+    let persist_models_statement = if macro_args.metrics {
+        quote! {
+            let persisting_started = std::time::Instant::now();
+            let persisted = #persist_models;
+            self.metrics.record_persistence(persisting_started.elapsed(), persisted.is_err());
+            persisted?;
+        }
+    } else {
+        quote! { #persist_models?; }
+    };
+    let mut lifecycle_impl = syn::parse2::<ItemImpl>(lifecycle_impl)?;
+    let lifecycle_impl_ident = get_type_as_capital_ident(&lifecycle_impl.self_ty)?;
+    let is_implemented = |name: &str| {
+        lifecycle_impl.items.iter().any(|item| match item {
+            ImplItem::Fn(function) => function.sig.ident == name,
+            _ => false,
+        })
+    };
+
+    let default_items: Vec<(&str, ImplItem)> = vec![
+        (
+            "initialise_with_app_config",
+            parse_quote! {
+                fn initialise_with_app_config<AC: AppConfig + std::fmt::Debug>(
+                    app_config: AC,
+                ) -> Result<&'static Self, Self::Error> {
+                    if let Some(lifecycle) = LIFECYCLE_SINGLETON.get() {
+                        return Ok(lifecycle);
+                    }
+                    let lifecycle = Self::new_instance(&app_config)?;
+                    Ok(LIFECYCLE_SINGLETON.get_or_init(|| lifecycle))
+                }
+            },
+        ),
+        (
+            "initialise",
+            parse_quote! {
+                fn initialise(app_state_url: Option<String>) -> Result<(), Self::Error> {
+                    Self::initialise_with_app_config(<#app_config as AppConfig>::new(app_state_url))?;
+                    Ok(())
+                }
+            },
+        ),
+        (
+            "get_singleton",
+            parse_quote! {
+                fn get_singleton() -> &'static Self {
+                    LIFECYCLE_SINGLETON
+                        .get()
+                        .expect("The Lifecycle is not initialised! Call Lifecycle::initialise() first.")
+                }
+            },
+        ),
+        (
+            "persist",
+            parse_quote! {
+                fn persist() -> Result<(), ProcessingError> {
+                    Self::get_singleton().persist_instance()
+                }
+            },
+        ),
+        (
+            "persist_instance",
+            parse_quote! {
+                fn persist_instance(&self) -> Result<(), ProcessingError> {
+                    #read_journal_position
+                    // held until the taken models are persisted, so that no older state overwrites them
+                    let _persisting_guard = self.#app_state_field.lock_persisting();
+                    // taken before persisting, so that models changed meanwhile stay dirty
+                    let dirty_models = self.#app_state_field.take_dirty_models();
+                    #persist_models_statement
+                    #compact_journal
+                    Ok(())
+                }
+            },
+        ),
+        (
+            "shutdown",
+            parse_quote! {
+                fn shutdown() -> Result<(), ProcessingError> {
+                    if Self::get_singleton().#app_state_field.dirty_flag_value() {
+                        Self::persist()?;
+                    }
+                    Ok(())
+                }
+            },
+        ),
+        (
+            "initialisation_result",
+            parse_quote! {
+                fn initialisation_result() -> generate_cqrs_api::InitialisationResult {
+                    Self::get_singleton().persister.initialisation_result()
+                }
+            },
+        ),
+    ];
+    // the background worker of persistence = "debounced" shares the lifecycle
+    let (instance_type, singleton_type) = match macro_args.persistence {
+        PersistenceStrategy::Debounced { .. } => (
+            quote! { std::sync::Arc<Self> },
+            quote! { std::sync::Arc<#lifecycle_impl_ident> },
+        ),
+        _ => (quote! { Self }, quote! { #lifecycle_impl_ident }),
+    };
+    // the singleton is only needed if its access is generated
+    let generated_singleton =
+        if is_implemented("initialise_with_app_config") && is_implemented("get_singleton") {
+            quote! {}
+        } else {
+            quote! {
+                static LIFECYCLE_SINGLETON: std::sync::OnceLock<#singleton_type> =
+                    std::sync::OnceLock::new();
+            }
+        };
+    let missing_items = default_items
+        .into_iter()
+        .filter(|(name, _)| !is_implemented(name))
+        .map(|(_, item)| item)
+        .collect::<Vec<ImplItem>>();
+    lifecycle_impl.items.extend(missing_items);
+
+    let replay_journal = quote! {
+        lifecycle.journal.replay(lifecycle.#app_state_field.journal_position(), |entry| {
+            generate_cqrs_api::serde_json::from_str::<AnyCommand>(entry)
+                .map_err(|error| error.to_string())?
+                .replay_with(&lifecycle)
+                .map_err(|error| error.to_string())?;
+            Ok(())
+        })
+    };
+    let (open_journal, create_lifecycle) = match journal_snapshot_every {
+        Some(snapshot_every) => (
+            quote! {
+                let journal = generate_cqrs_api::CommandJournal::open(app_config, #snapshot_every)?;
+            },
+            quote! {
+                let lifecycle = Self {
+                    #app_state_field: loaded_app_state,
+                    persister,
+                    journal,
+                    #interceptors
+                    #metrics
+                    #authorization
+                };
+                #replay_journal?;
+                Ok(lifecycle)
+            },
+        ),
+        None => (
+            quote! {},
+            match macro_args.persistence {
+                PersistenceStrategy::Debounced { .. } => quote! {
+                    Ok(std::sync::Arc::new_cyclic(|lifecycle: &std::sync::Weak<Self>| {
+                        let lifecycle = lifecycle.clone();
+                        Self {
+                            #app_state_field: loaded_app_state,
+                            persister,
+                            #undo_history
+                            #interceptors
+                            #metrics
+                            #authorization
+                            deferred_persistence: Self::new_deferred_persistence(move || {
+                                match lifecycle.upgrade() {
+                                    Some(lifecycle) => lifecycle.persist_instance(),
+                                    // a dropped lifecycle has nothing to persist
+                                    None => Ok(()),
+                                }
+                            }),
+                        }
+                    }))
+                },
+                _ => quote! {
+                    Ok(Self {
+                        #app_state_field: loaded_app_state,
+                        persister,
+                        #undo_history
+                        #interceptors
+                        #metrics
+                        #authorization
+                    })
+                },
+            },
+        ),
+    };
+    let generated_code = quote! {
+        #generated_singleton
+
+        impl #lifecycle_impl_ident {
+            /// creates a lifecycle, which is not the singleton. Process CQRS calls on it with `process_with()`.
+            pub fn new_instance<AC: AppConfig>(
+                app_config: &AC,
+            ) -> Result<#instance_type, <Self as Lifecycle<ProcessingError>>::Error> {
+                let persister = <#persister as AppStatePersister>::new(app_config)?;
+                #open_journal
+                let loaded_app_state = match persister.load_app_state::<AC, #app_state>() {
+                    Ok(app_state) => app_state,
+                    Err(error)
+                        if AppStatePersistError::<ProcessingError>::is_not_found(&error) => {
+                        <#app_state as AppState>::new(app_config)
+                    }
+                    Err(error) => return Err(error.into()),
+                };
+                #create_lifecycle
+            }
+        }
+    };
+    Ok((quote! { #lifecycle_impl }, generated_code))
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::quote;
+    use syn::{parse_quote, ItemImpl};
+
+    use crate::{
+        generating::generate_default_lifecycle::generate_default_lifecycle,
+        parsing::macro_args::MacroArgs,
+    };
+
+    #[test]
+    fn no_default_lifecycle() {
+        let lifecycle_impl = quote! {
+            impl Lifecycle for MyLifecycle {}
+        };
+        let (result, generated_code) =
+            generate_default_lifecycle(lifecycle_impl.clone(), &MacroArgs::default()).unwrap();
+        assert_eq!(lifecycle_impl.to_string(), result.to_string());
+        assert!(generated_code.is_empty());
+    }
+    #[test]
+    fn generate_all_lifecycle_items() {
+        let macro_args = syn::parse2::<MacroArgs>(quote! {
+            "tests/good_source_file/mod.rs",
+            default_lifecycle(app_config = AppConfigImpl, app_state = AppStateImpl, persister = FilePersister)
+        })
+        .unwrap();
+        let (result, generated_code) = generate_default_lifecycle(
+            quote! {
+                impl Lifecycle for MyLifecycle {
+                    type Error = FilePersisterError;
+                }
+            },
+            &macro_args,
+        )
+        .unwrap();
+        let expected_code = quote! {
+            static LIFECYCLE_SINGLETON: std::sync::OnceLock<MyLifecycle> =
+                std::sync::OnceLock::new();
+
+            impl MyLifecycle {
+                /// creates a lifecycle, which is not the singleton. Process CQRS calls on it with `process_with()`.
+                pub fn new_instance<AC: AppConfig>(
+                    app_config: &AC,
+                ) -> Result<Self, <Self as Lifecycle<ProcessingError>>::Error> {
+                    let persister = <FilePersister as AppStatePersister>::new(app_config)?;
+                    let loaded_app_state = match persister.load_app_state::<AC, AppStateImpl>() {
+                        Ok(app_state) => app_state,
+                        Err(error)
+                        if AppStatePersistError::<ProcessingError>::is_not_found(&error) => {
+                            <AppStateImpl as AppState>::new(app_config)
+                        }
+                        Err(error) => return Err(error.into()),
+                    };
+                    Ok(Self {
+                        app_state: loaded_app_state,
+                        persister,
+                    })
+                }
+            }
+        };
+        assert_eq!(expected_code.to_string(), generated_code.to_string());
+        // parsed, as syn prints the closure's `||` differently than quote!
+        let expected: ItemImpl = parse_quote! {
+            impl Lifecycle for MyLifecycle {
+                type Error = FilePersisterError;
+                fn initialise_with_app_config<AC: AppConfig + std::fmt::Debug>(
+                    app_config: AC,
+                ) -> Result<&'static Self, Self::Error> {
+                    if let Some(lifecycle) = LIFECYCLE_SINGLETON.get() {
+                        return Ok(lifecycle);
+                    }
+                    let lifecycle = Self::new_instance(&app_config)?;
+                    Ok(LIFECYCLE_SINGLETON.get_or_init(|| lifecycle))
+                }
+                fn initialise(app_state_url: Option<String>) -> Result<(), Self::Error> {
+                    Self::initialise_with_app_config(<AppConfigImpl as AppConfig>::new(app_state_url))?;
+                    Ok(())
+                }
+                fn get_singleton() -> &'static Self {
+                    LIFECYCLE_SINGLETON
+                        .get()
+                        .expect("The Lifecycle is not initialised! Call Lifecycle::initialise() first.")
+                }
+                fn persist() -> Result<(), ProcessingError> {
+                    Self::get_singleton().persist_instance()
+                }
+                fn persist_instance(&self) -> Result<(), ProcessingError> {
+                    let _persisting_guard = self.app_state.lock_persisting();
+                    let dirty_models = self.app_state.take_dirty_models();
+                    self.persister
+                        .persist_models(&self.app_state, &dirty_models)
+                        .map_err(|error| {
+                            AppStatePersistError::<ProcessingError>::to_processing_error(&error)
+                        })
+                        .inspect_err(|_| self.app_state.mark_models_dirty(&dirty_models))?;
+                    Ok(())
+                }
+                fn shutdown() -> Result<(), ProcessingError> {
+                    if Self::get_singleton().app_state.dirty_flag_value() {
+                        Self::persist()?;
+                    }
+                    Ok(())
+                }
+                fn initialisation_result() -> generate_cqrs_api::InitialisationResult {
+                    Self::get_singleton().persister.initialisation_result()
+                }
+            }
+        };
+        assert_eq!(quote! {#expected}.to_string(), result.to_string());
+    }
+    #[test]
+    fn keep_user_provided_lifecycle_items() {
+        let macro_args = syn::parse2::<MacroArgs>(quote! {
+            "tests/good_source_file/mod.rs",
+            app_state_field = "state",
+            default_lifecycle(app_config = AppConfigImpl, app_state = AppStateImpl, persister = FilePersister)
+        })
+        .unwrap();
+        let (result, generated_code) = generate_default_lifecycle(
+            quote! {
+                impl Lifecycle for MyLifecycle {
+                    type Error = MyError;
+                    fn initialise_with_app_config<AC: AppConfig + std::fmt::Debug>(
+                        app_config: AC,
+                    ) -> Result<&'static Self, Self::Error> {
+                        todo!()
+                    }
+                    fn get_singleton() -> &'static Self {
+                        todo!()
+                    }
+                    fn shutdown() -> Result<(), ProcessingError> {
+                        Ok(())
+                    }
+                }
+            },
+            &macro_args,
+        )
+        .unwrap();
+        assert!(!generated_code
+            .to_string()
+            .contains("static LIFECYCLE_SINGLETON"));
+        let expected = quote! {
+            impl Lifecycle for MyLifecycle {
+                type Error = MyError;
+                fn initialise_with_app_config<AC: AppConfig + std::fmt::Debug>(
+                    app_config: AC,
+                ) -> Result<&'static Self, Self::Error> {
+                    todo!()
+                }
+                fn get_singleton() -> &'static Self {
+                    todo!()
+                }
+                fn shutdown() -> Result<(), ProcessingError> {
+                    Ok(())
+                }
+                fn initialise(app_state_url: Option<String>) -> Result<(), Self::Error> {
+                    Self::initialise_with_app_config(<AppConfigImpl as AppConfig>::new(app_state_url))?;
+                    Ok(())
+                }
+                fn persist() -> Result<(), ProcessingError> {
+                    Self::get_singleton().persist_instance()
+                }
+                fn persist_instance(&self) -> Result<(), ProcessingError> {
+                    let _persisting_guard = self.state.lock_persisting();
+                    let dirty_models = self.state.take_dirty_models();
+                    self.persister
+                        .persist_models(&self.state, &dirty_models)
+                        .map_err(|error| {
+                            AppStatePersistError::<ProcessingError>::to_processing_error(&error)
+                        })
+                        .inspect_err(|_| self.state.mark_models_dirty(&dirty_models))?;
+                    Ok(())
+                }
+                fn initialisation_result() -> generate_cqrs_api::InitialisationResult {
+                    Self::get_singleton().persister.initialisation_result()
+                }
+            }
+        };
+        assert_eq!(expected.to_string(), result.to_string());
+    }
+    #[test]
+    fn replay_journal_on_new_instance() {
+        let macro_args = syn::parse2::<MacroArgs>(quote! {
+            "tests/good_source_file/mod.rs",
+            generate_app_state,
+            serializable_cqrs,
+            persistence = "journal",
+            snapshot_every = 20,
+            default_lifecycle(app_config = AppConfigImpl, persister = FilePersister)
+        })
+        .unwrap();
+        let (result, generated_code) = generate_default_lifecycle(
+            quote! {
+                impl Lifecycle for MyLifecycle {
+                    type Error = MyError;
+                }
+            },
+            &macro_args,
+        )
+        .unwrap();
+        let expected_new_instance = quote! {
+            let persister = <FilePersister as AppStatePersister>::new(app_config)?;
+            let journal = generate_cqrs_api::CommandJournal::open(app_config, 20u64)?;
+        };
+        assert!(generated_code
+            .to_string()
+            .contains(&expected_new_instance.to_string()));
+        let expected_replay = quote! {
+            let lifecycle = Self {
+                app_state: loaded_app_state,
+                persister,
+                journal,
+            };
+            lifecycle.journal.replay(lifecycle.app_state.journal_position(), |entry| {
+                generate_cqrs_api::serde_json::from_str::<AnyCommand>(entry)
+                    .map_err(|error| error.to_string())?
+                    .replay_with(&lifecycle)
+                    .map_err(|error| error.to_string())?;
+                Ok(())
+            })?;
+            Ok(lifecycle)
+        };
+        assert!(generated_code
+            .to_string()
+            .contains(&expected_replay.to_string()));
+        let expected_persist_instance: ItemImpl = parse_quote! {
+            impl Lifecycle for MyLifecycle {
+                fn persist_instance(&self) -> Result<(), ProcessingError> {
+                    let journal_guard = self.journal.lock_commands();
+                    let journal_position = self.app_state.journal_position();
+                    let _persisting_guard = self.app_state.lock_persisting();
+                    let dirty_models = self.app_state.take_dirty_models();
+                    self.persister
+                        .persist_models(&self.app_state, &dirty_models)
+                        .map_err(|error| {
+                            AppStatePersistError::<ProcessingError>::to_processing_error(&error)
+                        })
+                        .inspect_err(|_| self.app_state.mark_models_dirty(&dirty_models))?;
+                    drop(journal_guard);
+                    self.journal.compact(journal_position).map_err(|error| {
+                        AppStatePersistError::<ProcessingError>::to_processing_error(&error)
+                    })?;
+                    Ok(())
+                }
+            }
+        };
+        let expected_persist_instance = match &expected_persist_instance.items[0] {
+            syn::ImplItem::Fn(function) => quote! {#function}.to_string(),
+            _ => unreachable!(),
+        };
+        assert!(result.to_string().contains(&expected_persist_instance));
+    }
+    #[test]
+    fn replay_the_journal_trusted() {
+        let macro_args = syn::parse2::<MacroArgs>(quote! {
+            "tests/good_source_file/mod.rs",
+            generate_app_state,
+            serializable_cqrs,
+            persistence = "journal",
+            authorization,
+            default_lifecycle(app_config = AppConfigImpl, persister = FilePersister)
+        })
+        .unwrap();
+        let (_, generated_code) = generate_default_lifecycle(
+            quote! {
+                impl Lifecycle for MyLifecycle {
+                    type Error = MyError;
+                }
+            },
+            &macro_args,
+        )
+        .unwrap();
+        let expected_replay = quote! {
+            let lifecycle = Self {
+                app_state: loaded_app_state,
+                persister,
+                journal,
+                authorization: generate_cqrs_api::Authorization::default(),
+            };
+        };
+        let generated_code = generated_code.to_string();
+        assert!(generated_code.contains(&expected_replay.to_string()));
+        let expected_replay = quote! {
+            .replay_with(&lifecycle)
+        };
+        assert!(generated_code.contains(&expected_replay.to_string()));
+    }
+    #[test]
+    fn share_the_instance_with_the_deferred_persistence() {
+        let macro_args = syn::parse2::<MacroArgs>(quote! {
+            "tests/good_source_file/mod.rs",
+            persistence = "debounced",
+            default_lifecycle(app_config = AppConfigImpl, app_state = AppStateImpl, persister = FilePersister)
+        })
+        .unwrap();
+        let (_, generated_code) = generate_default_lifecycle(
+            quote! {
+                impl Lifecycle for MyLifecycle {
+                    type Error = MyError;
+                }
+            },
+            &macro_args,
+        )
+        .unwrap();
+        let generated_code = generated_code.to_string();
+        let expected_singleton = quote! {
+            static LIFECYCLE_SINGLETON: std::sync::OnceLock<std::sync::Arc<MyLifecycle> > =
+                std::sync::OnceLock::new();
+        };
+        assert!(generated_code.contains(&expected_singleton.to_string()));
+        let expected_signature = quote! {
+            -> Result<std::sync::Arc<Self>, <Self as Lifecycle<ProcessingError>>::Error>
+        };
+        assert!(generated_code.contains(&expected_signature.to_string()));
+        let expected_instance = quote! {
+            deferred_persistence: Self::new_deferred_persistence(move || {
+                match lifecycle.upgrade() {
+                    Some(lifecycle) => lifecycle.persist_instance(),
+                    None => Ok(()),
+                }
+            }),
+        };
+        assert!(generated_code.contains(&expected_instance.to_string()));
+    }
+}
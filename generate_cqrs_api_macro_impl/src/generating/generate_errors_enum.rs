@@ -6,11 +6,15 @@ use syn::Ident;
 use crate::generate_api_macro_impl::ModelNEffects;
 use crate::generate_api_macro_impl::ModelNEffectsNErrors;
 use crate::parsing::get_enum::get_enum_type_by_ident_keyword;
+use crate::parsing::get_struct_by_trait::checks_invariants;
+use crate::parsing::macro_args::MacroArgs;
 
 use super::generate_use_statement::generate_use_statement;
+use super::generate_validation::validates_arguments;
 
 pub(crate) fn generate_errors_enum(
     models_n_effects: Vec<ModelNEffects>,
+    macro_args: &MacroArgs,
 ) -> (Vec<ModelNEffectsNErrors>, TokenStream) {
     // }
     // fn generate_error_enum(ast: &File) -> (Ident, TokenStream) {
@@ -41,6 +45,33 @@ pub(crate) fn generate_errors_enum(
         .iter()
         .map(|model| model.error_ident.clone())
         .collect::<Vec<Ident>>();
+    // only returned by the models checking invariants
+    let invariant_violated = models_n_effects_n_errors
+        .iter()
+        .any(|model| checks_invariants(&model.ast, &model.domain_model_ident))
+        .then(|| {
+            quote! {
+                #[error("The command violated the invariant '{rule}' of {model} and was rolled back")]
+                InvariantViolated { model: String, rule: String },
+            }
+        });
+    // only returned for the option `authorization`
+    let unauthorized = macro_args.authorization.then(|| {
+        quote! {
+            #[error("The call {command} is not authorized")]
+            Unauthorized { command: String },
+        }
+    });
+    // only returned by the models validating arguments
+    let invalid_argument = models_n_effects_n_errors
+        .iter()
+        .any(|model| validates_arguments(&model.ast))
+        .then(|| {
+            quote! {
+                #[error("The argument '{argument}' of {command} is invalid: {reason}")]
+                InvalidArgument { command: String, argument: String, reason: String },
+            }
+        });
     (
         models_n_effects_n_errors,
         quote! {
@@ -51,6 +82,15 @@ pub(crate) fn generate_errors_enum(
                     #processing_errors ( #processing_errors ),)*
                 #[error("Processing was fine, but state could not be persisted in url '{url}': {error}")]
                 NotPersisted { error: String, url: String },
+                #invariant_violated
+                #unauthorized
+                #invalid_argument
+            }
+
+            impl generate_cqrs_api::NotPersistedError for ProcessingError {
+                fn not_persisted(error: String, url: String) -> Self {
+                    ProcessingError::NotPersisted { error, url }
+                }
             }
         },
     )
@@ -63,6 +103,7 @@ mod tests {
     use crate::{
         generate_api_macro_impl::{BasePath, ModelNEffects},
         generating::generate_errors_enum::generate_errors_enum,
+        parsing::macro_args::MacroArgs,
     };
 
     #[test]
@@ -78,14 +119,17 @@ mod tests {
         )
         .expect("test oracle should be parsable");
 
-        let result = generate_errors_enum(vec![ModelNEffects {
-            base_path: BasePath("domain::model".to_string()),
-            ast,
-            domain_model_ident: format_ident!("MyGoodDomain"),
-            domain_model_lock_ident: format_ident!("MyGoodDomainLock"),
-            effect_ident: format_ident!("MyGoodDomainEffect"),
-            effect_variants: vec![],
-        }]);
+        let result = generate_errors_enum(
+            vec![ModelNEffects {
+                base_path: BasePath("domain::model".to_string()),
+                ast,
+                domain_model_ident: format_ident!("MyGoodDomain"),
+                domain_model_lock_ident: format_ident!("MyGoodDomainLock"),
+                effect_ident: format_ident!("MyGoodDomainEffect"),
+                effect_variants: vec![],
+            }],
+            &MacroArgs::default(),
+        );
         let expected_code = quote! {
             use domain::model::MyGoodProcessingError;
             #[derive(thiserror::Error, Debug)]
@@ -95,6 +139,12 @@ mod tests {
                 #[error("Processing was fine, but state could not be persisted in url '{url}': {error}")]
                 NotPersisted { error: String, url: String },
             }
+
+            impl generate_cqrs_api::NotPersistedError for ProcessingError {
+                fn not_persisted(error: String, url: String) -> Self {
+                    ProcessingError::NotPersisted { error, url }
+                }
+            }
         };
         assert_eq!(expected_code.to_string(), result.1.to_string());
     }
@@ -122,24 +172,27 @@ mod tests {
         )
         .expect("test oracle ast_two should be parsable");
 
-        let result = generate_errors_enum(vec![
-            ModelNEffects {
-                ast: ast_one,
-                domain_model_ident: format_ident!("MyGoodDomain"),
-                domain_model_lock_ident: format_ident!("MyGoodDomainLock"),
-                effect_ident: format_ident!("MyGoodDomainEffect"),
-                base_path: BasePath("domain::model".to_string()),
-                effect_variants: vec![],
-            },
-            ModelNEffects {
-                ast: ast_two,
-                domain_model_ident: format_ident!("MySecondDomain"),
-                domain_model_lock_ident: format_ident!("MySecondDomainLock"),
-                effect_ident: format_ident!("MySecondDomainEffect"),
-                base_path: BasePath("domain::second".to_string()),
-                effect_variants: vec![],
-            },
-        ]);
+        let result = generate_errors_enum(
+            vec![
+                ModelNEffects {
+                    ast: ast_one,
+                    domain_model_ident: format_ident!("MyGoodDomain"),
+                    domain_model_lock_ident: format_ident!("MyGoodDomainLock"),
+                    effect_ident: format_ident!("MyGoodDomainEffect"),
+                    base_path: BasePath("domain::model".to_string()),
+                    effect_variants: vec![],
+                },
+                ModelNEffects {
+                    ast: ast_two,
+                    domain_model_ident: format_ident!("MySecondDomain"),
+                    domain_model_lock_ident: format_ident!("MySecondDomainLock"),
+                    effect_ident: format_ident!("MySecondDomainEffect"),
+                    base_path: BasePath("domain::second".to_string()),
+                    effect_variants: vec![],
+                },
+            ],
+            &MacroArgs::default(),
+        );
         let expected_code = quote! {
             use domain::model::MyGoodProcessingError ;
             use domain::second::MySecondProcessingError ;
@@ -152,10 +205,127 @@ mod tests {
                 #[error("Processing was fine, but state could not be persisted in url '{url}': {error}")]
                 NotPersisted { error: String, url: String },
             }
+
+            impl generate_cqrs_api::NotPersistedError for ProcessingError {
+                fn not_persisted(error: String, url: String) -> Self {
+                    ProcessingError::NotPersisted { error, url }
+                }
+            }
         };
         assert_eq!(expected_code.to_string(), result.1.to_string());
     }
 
+    #[test]
+    fn generate_invariant_violated_for_checked_models() {
+        let ast = syn::parse_file(
+            r#"
+        #[derive(thiserror::Error, Debug)]
+         pub enum MyGoodProcessingError {
+             #[error("Error during processing: {0}")]
+             Error(String)
+         }
+         #[cqrs_model]
+         impl CqrsModel for MyGoodDomain {
+             fn check_invariants(&self) -> Result<(), String> {
+                 Ok(())
+             }
+         }
+         "#,
+        )
+        .expect("test oracle should be parsable");
+
+        let result = generate_errors_enum(
+            vec![ModelNEffects {
+                base_path: BasePath("domain::model".to_string()),
+                ast,
+                domain_model_ident: format_ident!("MyGoodDomain"),
+                domain_model_lock_ident: format_ident!("MyGoodDomainLock"),
+                effect_ident: format_ident!("MyGoodDomainEffect"),
+                effect_variants: vec![],
+            }],
+            &MacroArgs::default(),
+        );
+        let expected_variant = quote! {
+            #[error("The command violated the invariant '{rule}' of {model} and was rolled back")]
+            InvariantViolated { model: String, rule: String },
+        };
+        assert!(result.1.to_string().contains(&expected_variant.to_string()));
+    }
+
+    #[test]
+    fn generate_unauthorized_for_authorization() {
+        let ast = syn::parse_file(
+            r#"
+        #[derive(thiserror::Error, Debug)]
+         pub enum MyGoodProcessingError {
+             #[error("Error during processing: {0}")]
+             Error(String)
+         }
+         "#,
+        )
+        .expect("test oracle should be parsable");
+
+        let result = generate_errors_enum(
+            vec![ModelNEffects {
+                base_path: BasePath("domain::model".to_string()),
+                ast,
+                domain_model_ident: format_ident!("MyGoodDomain"),
+                domain_model_lock_ident: format_ident!("MyGoodDomainLock"),
+                effect_ident: format_ident!("MyGoodDomainEffect"),
+                effect_variants: vec![],
+            }],
+            &MacroArgs {
+                authorization: true,
+                ..Default::default()
+            },
+        );
+        let expected_variant = quote! {
+            #[error("The call {command} is not authorized")]
+            Unauthorized { command: String },
+        };
+        assert!(result.1.to_string().contains(&expected_variant.to_string()));
+    }
+
+    #[test]
+    fn generate_invalid_argument_for_validated_arguments() {
+        let ast = syn::parse_file(
+            r#"
+        #[derive(thiserror::Error, Debug)]
+         pub enum MyGoodProcessingError {
+             #[error("Error during processing: {0}")]
+             Error(String)
+         }
+         impl MyGoodDomainLock {
+             #[cqrs]
+             pub(crate) fn add_item(
+                 &self,
+                 #[validate(length(min = 1))] item: String,
+             ) -> Result<(bool, Vec<MyGoodDomainEffect>), MyGoodProcessingError> {
+                 Ok((true, vec![]))
+             }
+         }
+         "#,
+        )
+        .expect("test oracle should be parsable");
+
+        let result = generate_errors_enum(
+            vec![ModelNEffects {
+                base_path: BasePath("domain::model".to_string()),
+                ast,
+                domain_model_ident: format_ident!("MyGoodDomain"),
+                domain_model_lock_ident: format_ident!("MyGoodDomainLock"),
+                effect_ident: format_ident!("MyGoodDomainEffect"),
+                effect_variants: vec![],
+            }],
+            &MacroArgs::default(),
+        );
+        let expected_variant = quote! {
+            #[error("The argument '{argument}' of {command} is invalid: {reason}")]
+            InvalidArgument { command: String, argument: String, reason: String },
+        };
+        assert!(result.1.to_string().contains(&expected_variant.to_string()));
+    }
+
     #[test]
     #[should_panic(
         expected = r#"More than one Error enum found! Please combine all Error cases in one Enum. Found: [
@@ -184,14 +354,17 @@ mod tests {
         )
         .expect("test oracle should be parsable");
 
-        let result = generate_errors_enum(vec![ModelNEffects {
-            base_path: BasePath("".to_string()),
-            ast,
-            domain_model_ident: format_ident!("MyGoodDomain"),
-            domain_model_lock_ident: format_ident!("MyGoodDomainLock"),
-            effect_ident: format_ident!("MyGoodDomainEffect"),
-            effect_variants: vec![],
-        }]);
+        let result = generate_errors_enum(
+            vec![ModelNEffects {
+                base_path: BasePath("".to_string()),
+                ast,
+                domain_model_ident: format_ident!("MyGoodDomain"),
+                domain_model_lock_ident: format_ident!("MyGoodDomainLock"),
+                effect_ident: format_ident!("MyGoodDomainEffect"),
+                effect_variants: vec![],
+            }],
+            &MacroArgs::default(),
+        );
 
         let expected_code = quote! {
             #[derive(thiserror::Error, Debug)]
@@ -201,6 +374,12 @@ mod tests {
                 #[error("Processing was fine, but state could not be persisted in url '{url}': {error}")]
                 NotPersisted { error: String, url: String },
             }
+
+            impl generate_cqrs_api::NotPersistedError for ProcessingError {
+                fn not_persisted(error: String, url: String) -> Self {
+                    ProcessingError::NotPersisted { error, url }
+                }
+            }
         };
         assert_eq!(expected_code.to_string(), result.1.to_string());
     }
@@ -218,14 +397,17 @@ mod tests {
         )
         .expect("test oracle should be parsable");
 
-        let result = generate_errors_enum(vec![ModelNEffects {
-            base_path: BasePath("".to_string()),
-            ast,
-            domain_model_ident: format_ident!("MyGoodDomain"),
-            domain_model_lock_ident: format_ident!("MyGoodDomainLock"),
-            effect_ident: format_ident!("MyGoodDomainEffect"),
-            effect_variants: vec![],
-        }]);
+        let result = generate_errors_enum(
+            vec![ModelNEffects {
+                base_path: BasePath("".to_string()),
+                ast,
+                domain_model_ident: format_ident!("MyGoodDomain"),
+                domain_model_lock_ident: format_ident!("MyGoodDomainLock"),
+                effect_ident: format_ident!("MyGoodDomainEffect"),
+                effect_variants: vec![],
+            }],
+            &MacroArgs::default(),
+        );
 
         let expected_code = quote! {
             #[derive(thiserror::Error, Debug)]
@@ -235,6 +417,12 @@ mod tests {
                 #[error("Processing was fine, but state could not be persisted in url '{url}': {error}")]
                 NotPersisted { error: String, url: String },
             }
+
+            impl generate_cqrs_api::NotPersistedError for ProcessingError {
+                fn not_persisted(error: String, url: String) -> Self {
+                    ProcessingError::NotPersisted { error, url }
+                }
+            }
         };
         assert_eq!(expected_code.to_string(), result.1.to_string());
     }
@@ -0,0 +1,58 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::Ident;
+
+use crate::parsing::macro_args::MacroArgs;
+
+/// generates the statements running the lifecycle's interceptors around `processing_fn` (only for `interceptors`),
+/// which can reject the call
+pub(crate) fn generate_intercepting_layer(
+    domain_model_name: &str,
+    processing_fn: &Ident,
+) -> TokenStream {
+    quote! {
+        lifecycle.interceptors.intercept(#domain_model_name, self.variant_name(), self, |cqrs| {
+            cqrs.#processing_fn(lifecycle)
+        })
+    }
+}
+
+/// generates the lifecycle's `register_interceptor()` (only for `interceptors`).
+/// The lifecycle needs the field `interceptors: generate_cqrs_api::Interceptors<Effect, ProcessingError>`.
+pub(crate) fn generate_register_interceptor(
+    lifecycle_impl_ident: &Ident,
+    macro_args: &MacroArgs,
+) -> TokenStream {
+    if !macro_args.interceptors {
+        return quote! {};
+    }
+    quote! {
+        impl #lifecycle_impl_ident {
+            /// registers an interceptor running around every CQRS call processed by the singleton.
+            /// Register at other lifecycles with `lifecycle.interceptors.register()`.
+            pub fn register_interceptor(
+                interceptor: impl generate_cqrs_api::Interceptor<Effect, ProcessingError> + 'static,
+            ) {
+                Self::get_singleton().interceptors.register(interceptor);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::{format_ident, quote};
+
+    use crate::generating::generate_interceptors::generate_intercepting_layer;
+
+    #[test]
+    fn generate_intercepting_statements() {
+        let result = generate_intercepting_layer("MyModel", &format_ident!("process_inner"));
+        let expected = quote! {
+            lifecycle.interceptors.intercept("MyModel", self.variant_name(), self, |cqrs| {
+                cqrs.process_inner(lifecycle)
+            })
+        };
+        assert_eq!(expected.to_string(), result.to_string());
+    }
+}
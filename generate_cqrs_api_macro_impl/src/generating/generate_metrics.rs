@@ -0,0 +1,76 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::Ident;
+
+use crate::parsing::macro_args::MacroArgs;
+
+/// generates the statements recording the count, errors and latency of `processing_fn` (only for `metrics`)
+pub(crate) fn generate_metrics_layer(
+    domain_model_name: &str,
+    processing_fn: &Ident,
+) -> TokenStream {
+    quote! {
+        let variant = self.variant_name();
+        let started = std::time::Instant::now();
+        let result = self.#processing_fn(lifecycle);
+        lifecycle.metrics.record_call(#domain_model_name, variant, started.elapsed(), result.is_err());
+        result
+    }
+}
+
+/// generates the lifecycle's `metrics_snapshot()` (only for `metrics`).
+/// The lifecycle needs the field `metrics: generate_cqrs_api::MetricsRegistry`.
+pub(crate) fn generate_metrics_snapshot(
+    lifecycle_impl_ident: &Ident,
+    macro_args: &MacroArgs,
+) -> TokenStream {
+    if !macro_args.metrics {
+        return quote! {};
+    }
+    quote! {
+        impl #lifecycle_impl_ident {
+            /// the metrics of the singleton's CQRS calls and persistence, e.g. for a diagnostics page.
+            /// Use `lifecycle.metrics.snapshot()` for other lifecycles.
+            pub fn metrics_snapshot() -> generate_cqrs_api::MetricsSnapshot {
+                Self::get_singleton().metrics.snapshot()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::{format_ident, quote};
+    use syn::parse2;
+
+    use crate::{
+        generating::generate_metrics::{generate_metrics_layer, generate_metrics_snapshot},
+        parsing::macro_args::MacroArgs,
+    };
+
+    #[test]
+    fn generate_measuring_statements() {
+        let result = generate_metrics_layer("MyModel", &format_ident!("process_inner"));
+        let expected = quote! {
+            let variant = self.variant_name();
+            let started = std::time::Instant::now();
+            let result = self.process_inner(lifecycle);
+            lifecycle.metrics.record_call("MyModel", variant, started.elapsed(), result.is_err());
+            result
+        };
+        assert_eq!(expected.to_string(), result.to_string());
+    }
+    #[test]
+    fn generate_snapshot_only_for_metrics() {
+        let lifecycle_impl_ident = format_ident!("LifecycleImpl");
+        let macro_args = parse2::<MacroArgs>(quote! {"tests/good_source_file/mod.rs"}).unwrap();
+        assert!(generate_metrics_snapshot(&lifecycle_impl_ident, &macro_args).is_empty());
+        let macro_args =
+            parse2::<MacroArgs>(quote! {"tests/good_source_file/mod.rs", metrics}).unwrap();
+        assert!(
+            generate_metrics_snapshot(&lifecycle_impl_ident, &macro_args)
+                .to_string()
+                .contains("pub fn metrics_snapshot")
+        );
+    }
+}
@@ -0,0 +1,319 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{parse_quote, Ident, ImplItem, ItemImpl, Stmt};
+
+use crate::parsing::macro_args::{MacroArgs, PersistenceStrategy};
+
+/// generates the statement, which is executed after a CQRS command changed the state
+/// @param domain_model_lock_field: the AppState's field of the changed model
+pub(crate) fn generate_update_state_statement(
+    persistence: &PersistenceStrategy,
+    domain_model_lock_field: &Ident,
+) -> TokenStream {
+    let model_id = domain_model_lock_field.to_string();
+    let persist_statement = generate_persist_statement(persistence);
+    // the command is journaled before the app state is marked dirty, so that its position is persisted with it
+    // the snapshot locks the journal's commands itself
+    let (journal_statement, release_journal_statement) = match persistence {
+        PersistenceStrategy::Journal { .. } => (
+            quote! {
+                let journal_position = lifecycle
+                    .journal
+                    .append(&journal_entry)
+                    .map_err(|error| AppStatePersistError::<ProcessingError>::to_processing_error(&error))?;
+                app_state.set_journal_position(journal_position);
+            },
+            quote! {
+                drop(journal_guard);
+            },
+        ),
+        _ => (quote! {}, quote! {}),
+    };
+    quote! {
+        if state_changed {
+            #journal_statement
+            app_state.mark_model_dirty(generate_cqrs_api::ModelId(#model_id));
+            #release_journal_statement
+            #persist_statement
+        }
+    }
+}
+
+/// generates the statement persisting the app state according to the strategy, after it was marked dirty
+pub(crate) fn generate_persist_statement(persistence: &PersistenceStrategy) -> TokenStream {
+    match persistence {
+        PersistenceStrategy::Immediate => quote! {
+            lifecycle.persist_instance()?;
+        },
+        PersistenceStrategy::Debounced { .. } => quote! {
+            lifecycle.deferred_persistence.schedule();
+        },
+        PersistenceStrategy::Manual => quote! {},
+        PersistenceStrategy::Journal { .. } => quote! {
+            if lifecycle.journal.needs_snapshot() {
+                lifecycle.persist_instance()?;
+            }
+        },
+    }
+}
+
+/// generates the statements serializing the command for the journal and locking the journal's commands,
+/// before it is processed (only for persistence = "journal")
+pub(crate) fn generate_journal_entry_statement(
+    persistence: &PersistenceStrategy,
+    domain_model_ident: &Ident,
+) -> TokenStream {
+    let PersistenceStrategy::Journal { .. } = persistence else {
+        return quote! {};
+    };
+    let model_name = domain_model_ident.to_string();
+    quote! {
+        let journal_entry = lifecycle
+            .journal
+            .serialize_entry(#model_name, &self)
+            .map_err(|error| AppStatePersistError::<ProcessingError>::to_processing_error(&error))?;
+        let journal_guard = lifecycle.journal.lock_commands();
+    }
+}
+
+/// generates the constructor of the lifecycle's `deferred_persistence: generate_cqrs_api::DeferredPersistence<ProcessingError>`,
+/// which persists the lifecycle in the background (only for persistence = "debounced")
+pub(crate) fn generate_deferred_persistence(
+    lifecycle_impl_ident: &Ident,
+    persistence: &PersistenceStrategy,
+) -> TokenStream {
+    let PersistenceStrategy::Debounced { debounce_ms } = persistence else {
+        return quote! {};
+    };
+    quote! {
+        impl #lifecycle_impl_ident {
+            /// creates the lifecycle's `deferred_persistence`, calling `persist` after the debounce window.
+            /// Hold the lifecycle weakly in `persist`, as it runs on a background worker.
+            pub fn new_deferred_persistence(
+                persist: impl Fn() -> Result<(), ProcessingError> + Send + Sync + 'static,
+            ) -> generate_cqrs_api::DeferredPersistence<ProcessingError> {
+                generate_cqrs_api::DeferredPersistence::new(
+                    std::time::Duration::from_millis(#debounce_ms),
+                    persist,
+                )
+            }
+            /// errors of persisting the singleton in the background can't be returned by `Cqrs::process()`.
+            /// They are passed to this handler - or, if none is registered, returned by `Lifecycle::shutdown()`.
+            /// Set it at other lifecycles with `lifecycle.deferred_persistence.set_error_handler()`.
+            pub fn on_deferred_persist_error(
+                handler: impl Fn(ProcessingError) + Send + Sync + 'static,
+            ) {
+                Self::get_singleton()
+                    .deferred_persistence
+                    .set_error_handler(handler);
+            }
+        }
+    }
+}
+
+/// adds flushing pending writes to the start of `Lifecycle::shutdown()`
+pub(crate) fn inject_shutdown_flush(
+    lifecycle_impl: TokenStream,
+    macro_args: &MacroArgs,
+) -> syn::Result<TokenStream> {
+    let app_state_field = macro_args.app_state_field();
+    let flush_statement: Stmt = match macro_args.persistence {
+        // the journal is written durably with each command
+        PersistenceStrategy::Immediate | PersistenceStrategy::Journal { .. } => {
+            return Ok(lifecycle_impl)
+        }
+        PersistenceStrategy::Debounced { .. } => parse_quote! {
+            Self::get_singleton().deferred_persistence.flush()?;
+        },
+        PersistenceStrategy::Manual => parse_quote! {
+            if Self::get_singleton().#app_state_field.dirty_flag_value() {
+                Self::persist()?;
+            }
+        },
+    };
+    let mut lifecycle_impl = syn::parse2::<ItemImpl>(lifecycle_impl)?;
+    for item in lifecycle_impl.items.iter_mut() {
+        if let ImplItem::Fn(function) = item {
+            if function.sig.ident == "shutdown" {
+                function.block.stmts.insert(0, flush_statement.clone());
+            }
+        }
+    }
+    Ok(quote! { #lifecycle_impl })
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::{format_ident, quote};
+
+    use crate::{
+        generating::generate_persistence::{
+            generate_deferred_persistence, generate_journal_entry_statement,
+            generate_update_state_statement, inject_shutdown_flush,
+        },
+        parsing::macro_args::{MacroArgs, PersistenceStrategy},
+    };
+
+    #[test]
+    fn generate_update_state_statement_immediate() {
+        let result = generate_update_state_statement(
+            &PersistenceStrategy::Immediate,
+            &format_ident!("my_model_lock"),
+        );
+        let expected = quote! {
+            if state_changed {
+                app_state.mark_model_dirty(generate_cqrs_api::ModelId("my_model_lock"));
+                lifecycle.persist_instance()?;
+            }
+        };
+        assert_eq!(expected.to_string(), result.to_string());
+    }
+    #[test]
+    fn generate_update_state_statement_journal() {
+        let result = generate_update_state_statement(
+            &PersistenceStrategy::Journal {
+                snapshot_every: 100,
+            },
+            &format_ident!("my_model_lock"),
+        );
+        let expected = quote! {
+            if state_changed {
+                let journal_position = lifecycle
+                    .journal
+                    .append(&journal_entry)
+                    .map_err(|error| AppStatePersistError::<ProcessingError>::to_processing_error(&error))?;
+                app_state.set_journal_position(journal_position);
+                app_state.mark_model_dirty(generate_cqrs_api::ModelId("my_model_lock"));
+                drop(journal_guard);
+                if lifecycle.journal.needs_snapshot() {
+                    lifecycle.persist_instance()?;
+                }
+            }
+        };
+        assert_eq!(expected.to_string(), result.to_string());
+
+        let result = generate_journal_entry_statement(
+            &PersistenceStrategy::Journal {
+                snapshot_every: 100,
+            },
+            &format_ident!("MyModel"),
+        );
+        let expected = quote! {
+            let journal_entry = lifecycle
+                .journal
+                .serialize_entry("MyModel", &self)
+                .map_err(|error| AppStatePersistError::<ProcessingError>::to_processing_error(&error))?;
+            let journal_guard = lifecycle.journal.lock_commands();
+        };
+        assert_eq!(expected.to_string(), result.to_string());
+        assert!(generate_journal_entry_statement(
+            &PersistenceStrategy::Immediate,
+            &format_ident!("MyModel")
+        )
+        .is_empty());
+    }
+    #[test]
+    fn generate_update_state_statement_debounced() {
+        let result = generate_update_state_statement(
+            &PersistenceStrategy::Debounced { debounce_ms: 100 },
+            &format_ident!("my_model_lock"),
+        );
+        let expected = quote! {
+            if state_changed {
+                app_state.mark_model_dirty(generate_cqrs_api::ModelId("my_model_lock"));
+                lifecycle.deferred_persistence.schedule();
+            }
+        };
+        assert_eq!(expected.to_string(), result.to_string());
+    }
+    #[test]
+    fn generate_update_state_statement_manual() {
+        let result = generate_update_state_statement(
+            &PersistenceStrategy::Manual,
+            &format_ident!("my_model_lock"),
+        );
+        let expected = quote! {
+            if state_changed {
+                app_state.mark_model_dirty(generate_cqrs_api::ModelId("my_model_lock"));
+            }
+        };
+        assert_eq!(expected.to_string(), result.to_string());
+    }
+    #[test]
+    fn generate_deferred_persistence_only_when_debounced() {
+        let lifecycle_impl_ident = format_ident!("MyLifecycle");
+        assert!(generate_deferred_persistence(
+            &lifecycle_impl_ident,
+            &PersistenceStrategy::Immediate
+        )
+        .is_empty());
+        assert!(
+            generate_deferred_persistence(&lifecycle_impl_ident, &PersistenceStrategy::Manual)
+                .is_empty()
+        );
+        let debounced = generate_deferred_persistence(
+            &lifecycle_impl_ident,
+            &PersistenceStrategy::Debounced { debounce_ms: 100 },
+        )
+        .to_string();
+        assert!(debounced.contains("from_millis (100u64)"));
+        assert!(debounced.contains("Self :: get_singleton () . deferred_persistence"));
+    }
+    #[test]
+    fn inject_shutdown_flush_debounced() {
+        let lifecycle_impl = quote! {
+            impl Lifecycle for MyLifecycle {
+                fn get_singleton() -> &'static Self {
+                    unimplemented!()
+                }
+                fn shutdown() -> Result<(), ProcessingError> {
+                    Self::persist()
+                }
+            }
+        };
+        let macro_args = MacroArgs {
+            persistence: PersistenceStrategy::Debounced { debounce_ms: 100 },
+            ..Default::default()
+        };
+        let result = inject_shutdown_flush(lifecycle_impl, &macro_args).unwrap();
+        let expected = quote! {
+            impl Lifecycle for MyLifecycle {
+                fn get_singleton() -> &'static Self {
+                    unimplemented!()
+                }
+                fn shutdown() -> Result<(), ProcessingError> {
+                    Self::get_singleton().deferred_persistence.flush()?;
+                    Self::persist()
+                }
+            }
+        };
+        assert_eq!(expected.to_string(), result.to_string());
+    }
+    #[test]
+    fn inject_shutdown_flush_manual() {
+        let lifecycle_impl = quote! {
+            impl Lifecycle for MyLifecycle {
+                fn shutdown() -> Result<(), ProcessingError> {
+                    Ok(())
+                }
+            }
+        };
+        let macro_args = MacroArgs {
+            persistence: PersistenceStrategy::Manual,
+            app_state_field: Some(format_ident!("state")),
+            ..Default::default()
+        };
+        let result = inject_shutdown_flush(lifecycle_impl, &macro_args).unwrap();
+        let expected = quote! {
+            impl Lifecycle for MyLifecycle {
+                fn shutdown() -> Result<(), ProcessingError> {
+                    if Self::get_singleton().state.dirty_flag_value() {
+                        Self::persist()?;
+                    }
+                    Ok(())
+                }
+            }
+        };
+        assert_eq!(expected.to_string(), result.to_string());
+    }
+}
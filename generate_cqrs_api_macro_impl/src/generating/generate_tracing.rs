@@ -0,0 +1,194 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::Ident;
+
+use crate::parsing::macro_args::MacroArgs;
+
+/// generates the statements opening a `tracing` span around `processing_fn` and emitting an event on errors (only for the feature `tracing`).
+/// The span's empty fields are recorded by the statements of `generate_tracing_statements()`.
+pub(crate) fn generate_tracing_layer(
+    domain_model_name: &str,
+    cqrs_kind: &str,
+    processing_fn: &Ident,
+) -> TokenStream {
+    quote! {
+        let span = generate_cqrs_api::tracing::info_span!(
+            "process",
+            model = #domain_model_name,
+            kind = #cqrs_kind,
+            variant = self.variant_name(),
+            arguments = ?self,
+            lock_wait_us = generate_cqrs_api::tracing::field::Empty,
+            execution_us = generate_cqrs_api::tracing::field::Empty,
+            state_changed = generate_cqrs_api::tracing::field::Empty,
+            persistence_us = generate_cqrs_api::tracing::field::Empty,
+        );
+        let _entered = span.enter();
+        let result = self.#processing_fn(lifecycle);
+        if let Err(error) = &result {
+            generate_cqrs_api::tracing::error!(%error, "processing failed");
+        }
+        result
+    }
+}
+
+/// generates the statements recording the timings into the current span (only for the feature `tracing`).
+/// The wait for the model's lock is timed around `SwappableModelLock::lock_commands()`, which keeps the model's other commands out.
+/// Without it, the lock's function is timed including waiting for the model's lock.
+/// @param locks_commands: if the call locks the model's commands
+/// @returns (the statements before the model's commands are locked, after they are locked, after the lock's function returned, after the state is persisted)
+pub(crate) fn generate_tracing_statements(
+    macro_args: &MacroArgs,
+    cqrs_kind: &str,
+    locks_commands: bool,
+) -> (TokenStream, TokenStream, TokenStream, TokenStream) {
+    if !macro_args.tracing {
+        return (quote! {}, quote! {}, quote! {}, quote! {});
+    }
+    let (before_locking, after_locking) = if locks_commands {
+        (
+            quote! {
+                let tracing_span = generate_cqrs_api::tracing::Span::current();
+                let tracing_timer = std::time::Instant::now();
+            },
+            quote! {
+                tracing_span.record("lock_wait_us", tracing_timer.elapsed().as_micros() as u64);
+                let tracing_timer = std::time::Instant::now();
+            },
+        )
+    } else {
+        (
+            quote! {
+                let tracing_span = generate_cqrs_api::tracing::Span::current();
+            },
+            quote! {
+                let tracing_timer = std::time::Instant::now();
+            },
+        )
+    };
+    if cqrs_kind != "Command" {
+        return (
+            before_locking,
+            after_locking,
+            quote! {
+                tracing_span.record("execution_us", tracing_timer.elapsed().as_micros() as u64);
+                tracing_span.record("state_changed", false);
+            },
+            quote! {},
+        );
+    }
+    (
+        before_locking,
+        after_locking,
+        quote! {
+            tracing_span.record("execution_us", tracing_timer.elapsed().as_micros() as u64);
+            tracing_span.record("state_changed", state_changed);
+            let tracing_timer = std::time::Instant::now();
+        },
+        quote! {
+            tracing_span.record("persistence_us", tracing_timer.elapsed().as_micros() as u64);
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::{format_ident, quote};
+    use syn::parse2;
+
+    use crate::{
+        generating::generate_tracing::{generate_tracing_layer, generate_tracing_statements},
+        parsing::macro_args::MacroArgs,
+    };
+
+    #[test]
+    fn generate_tracing_span() {
+        let result = generate_tracing_layer("MyModel", "Command", &format_ident!("process_inner"));
+        let expected = quote! {
+            let span = generate_cqrs_api::tracing::info_span!(
+                "process",
+                model = "MyModel",
+                kind = "Command",
+                variant = self.variant_name(),
+                arguments = ?self,
+                lock_wait_us = generate_cqrs_api::tracing::field::Empty,
+                execution_us = generate_cqrs_api::tracing::field::Empty,
+                state_changed = generate_cqrs_api::tracing::field::Empty,
+                persistence_us = generate_cqrs_api::tracing::field::Empty,
+            );
+            let _entered = span.enter();
+            let result = self.process_inner(lifecycle);
+            if let Err(error) = &result {
+                generate_cqrs_api::tracing::error!(%error, "processing failed");
+            }
+            result
+        };
+        assert_eq!(expected.to_string(), result.to_string());
+    }
+    #[test]
+    fn generate_query_tracing_statements() {
+        let macro_args = MacroArgs {
+            tracing: true,
+            ..parse2::<MacroArgs>(quote! {"tests/good_source_file/mod.rs"}).unwrap()
+        };
+        let (before_locking, after_locking, after_processing, after_persistence) =
+            generate_tracing_statements(&macro_args, "Query", false);
+        let expected = quote! {
+            let tracing_span = generate_cqrs_api::tracing::Span::current();
+        };
+        assert_eq!(expected.to_string(), before_locking.to_string());
+        let expected = quote! {
+            let tracing_timer = std::time::Instant::now();
+        };
+        assert_eq!(expected.to_string(), after_locking.to_string());
+        let expected = quote! {
+            tracing_span.record("execution_us", tracing_timer.elapsed().as_micros() as u64);
+            tracing_span.record("state_changed", false);
+        };
+        assert_eq!(expected.to_string(), after_processing.to_string());
+        assert!(after_persistence.is_empty());
+    }
+    #[test]
+    fn generate_command_tracing_statements_timing_the_lock() {
+        let macro_args = MacroArgs {
+            tracing: true,
+            ..parse2::<MacroArgs>(quote! {"tests/good_source_file/mod.rs"}).unwrap()
+        };
+        let (before_locking, after_locking, after_processing, after_persistence) =
+            generate_tracing_statements(&macro_args, "Command", true);
+        let expected = quote! {
+            let tracing_span = generate_cqrs_api::tracing::Span::current();
+            let tracing_timer = std::time::Instant::now();
+        };
+        assert_eq!(expected.to_string(), before_locking.to_string());
+        let expected = quote! {
+            tracing_span.record("lock_wait_us", tracing_timer.elapsed().as_micros() as u64);
+            let tracing_timer = std::time::Instant::now();
+        };
+        assert_eq!(expected.to_string(), after_locking.to_string());
+        let expected = quote! {
+            tracing_span.record("execution_us", tracing_timer.elapsed().as_micros() as u64);
+            tracing_span.record("state_changed", state_changed);
+            let tracing_timer = std::time::Instant::now();
+        };
+        assert_eq!(expected.to_string(), after_processing.to_string());
+        let expected = quote! {
+            tracing_span.record("persistence_us", tracing_timer.elapsed().as_micros() as u64);
+        };
+        assert_eq!(expected.to_string(), after_persistence.to_string());
+    }
+    #[test]
+    fn generate_nothing_without_tracing() {
+        // independent of the feature the tests run with
+        let macro_args = MacroArgs {
+            tracing: false,
+            ..parse2::<MacroArgs>(quote! {"tests/good_source_file/mod.rs"}).unwrap()
+        };
+        let (before_locking, after_locking, after_processing, after_persistence) =
+            generate_tracing_statements(&macro_args, "Command", true);
+        assert!(before_locking.is_empty());
+        assert!(after_locking.is_empty());
+        assert!(after_processing.is_empty());
+        assert!(after_persistence.is_empty());
+    }
+}
@@ -0,0 +1,217 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use stringcase::snake_case_with_sep;
+use syn::Ident;
+
+use crate::generate_api_macro_impl::ModelNEffectsNErrors;
+use crate::generating::generate_cqrs_impl::generate_effects_match_statements;
+use crate::generating::generate_persistence::generate_persist_statement;
+use crate::parsing::macro_args::MacroArgs;
+
+/// generates the statement capturing the model's state before a command is processed (only for `undo`).
+/// It runs under the model's `SwappableModelLock::lock_commands()` along with the command, so no other command slips in between.
+/// @param domain_model_lock_var: the variable holding the model's lock
+pub(crate) fn generate_undo_snapshot_statement(
+    macro_args: &MacroArgs,
+    domain_model_ident: &Ident,
+    domain_model_lock_ident: &Ident,
+    domain_model_lock_var: &Ident,
+) -> TokenStream {
+    if macro_args.undo_limit.is_none() {
+        return quote! {};
+    }
+    quote! {
+        let undo_snapshot = ModelSnapshot::#domain_model_ident(
+            <#domain_model_lock_ident as generate_cqrs_api::SwappableModelLock<#domain_model_ident>>::read_model(
+                #domain_model_lock_var,
+                #domain_model_ident::clone,
+            ),
+        );
+    }
+}
+
+/// generates the statement recording the captured state, if the command changed the model (only for `undo`)
+pub(crate) fn generate_record_undo_statement(macro_args: &MacroArgs) -> TokenStream {
+    if macro_args.undo_limit.is_none() {
+        return quote! {};
+    }
+    quote! {
+        if state_changed {
+            lifecycle.undo_history.record(undo_snapshot);
+        }
+    }
+}
+
+/// generates the `ModelSnapshot` enum, holding a model's recorded state, and the lifecycle's `undo()` and `redo()` (only for `undo`).
+/// The lifecycle needs the field `undo_history: generate_cqrs_api::UndoHistory<ModelSnapshot>`.
+pub(crate) fn generate_undo(
+    lifecycle_impl_ident: &Ident,
+    models: &[ModelNEffectsNErrors],
+    macro_args: &MacroArgs,
+) -> TokenStream {
+    if macro_args.undo_limit.is_none() {
+        return quote! {};
+    }
+    let app_state_field = macro_args.app_state_field();
+    let domain_model_idents = models
+        .iter()
+        .map(|model| &model.domain_model_ident)
+        .collect::<Vec<&Ident>>();
+    let restore_arms = models.iter().map(|model| {
+        let domain_model_ident = &model.domain_model_ident;
+        let domain_model_lock_ident = &model.domain_model_lock_ident;
+        let domain_model_lock_field = macro_args.model_field(domain_model_lock_ident);
+        let model_id = domain_model_lock_field.to_string();
+        let domain_model_lock_var = format_ident!(
+            "{}",
+            snake_case_with_sep(&domain_model_lock_ident.to_string(), "_")
+        );
+        let effects_match_statements = generate_effects_match_statements(
+            domain_model_ident,
+            (&model.effect_ident, &model.effect_variants),
+        );
+        quote! {
+            ModelSnapshot::#domain_model_ident(model) => {
+                let #domain_model_lock_var = &app_state.#domain_model_lock_field;
+                let replaced = <#domain_model_lock_ident as generate_cqrs_api::SwappableModelLock<#domain_model_ident>>::replace_model(
+                    #domain_model_lock_var,
+                    model,
+                );
+                app_state.mark_model_dirty(generate_cqrs_api::ModelId(#model_id));
+                let effects = <#domain_model_lock_ident as generate_cqrs_api::UndoableModelLock<#domain_model_ident>>::undo_effects(
+                    #domain_model_lock_var,
+                )
+                .into_iter()
+                .map(|effect| match effect {
+                    #(#effects_match_statements)*
+                })
+                .collect();
+                (ModelSnapshot::#domain_model_ident(replaced), effects)
+            }
+        }
+    });
+    let lock_all_commands = models.iter().map(|model| {
+        let domain_model_ident = &model.domain_model_ident;
+        let domain_model_lock_ident = &model.domain_model_lock_ident;
+        let domain_model_lock_field = macro_args.model_field(domain_model_lock_ident);
+        quote! {
+            <#domain_model_lock_ident as generate_cqrs_api::SwappableModelLock<#domain_model_ident>>::lock_commands(
+                &lifecycle.#app_state_field.#domain_model_lock_field,
+            ),
+        }
+    })
+    .collect::<Vec<TokenStream>>();
+    let persist_statement = generate_persist_statement(&macro_args.persistence);
+    let generate_restore_fn = |history_fn: Ident| {
+        let restore_fn = format_ident!("{history_fn}_with");
+        let doc = format!(" {history_fn}s on the given lifecycle instead of the global singleton, like `process_with()`");
+        quote! {
+            #[doc = #doc]
+            pub fn #restore_fn(&self) -> Result<Vec<Effect>, ProcessingError> {
+                let lifecycle = self;
+                // the commands lock their model before recording in the undo history, so lock all models first
+                let commands_guards = (#(#lock_all_commands)*);
+                let Some(effects) = lifecycle
+                    .undo_history
+                    .#history_fn(|snapshot| lifecycle.restore_undo_snapshot(snapshot))
+                else {
+                    return Ok(Vec::new());
+                };
+                drop(commands_guards);
+                #persist_statement
+                Ok(effects)
+            }
+        }
+    };
+    let undo_with = generate_restore_fn(format_ident!("undo"));
+    let redo_with = generate_restore_fn(format_ident!("redo"));
+    quote! {
+        /// a model's state before a command changed it, recorded for undo and redo
+        #[derive(Debug)]
+        pub enum ModelSnapshot {
+            #(#domain_model_idents(#domain_model_idents)),*
+        }
+
+        impl #lifecycle_impl_ident {
+            /// reverts the last state changing command.
+            /// @returns the effects re-rendering the restored model - none, if there is nothing to undo
+            pub fn undo() -> Result<Vec<Effect>, ProcessingError> {
+                Self::get_singleton().undo_with()
+            }
+            /// repeats the last undone command, see `undo()`
+            pub fn redo() -> Result<Vec<Effect>, ProcessingError> {
+                Self::get_singleton().redo_with()
+            }
+            pub fn can_undo() -> bool {
+                Self::get_singleton().undo_history.can_undo()
+            }
+            pub fn can_redo() -> bool {
+                Self::get_singleton().undo_history.can_redo()
+            }
+            #undo_with
+            #redo_with
+            /// @returns (the replaced state, the effects re-rendering the model)
+            fn restore_undo_snapshot(&self, snapshot: ModelSnapshot) -> (ModelSnapshot, Vec<Effect>) {
+                let app_state = &self.#app_state_field;
+                match snapshot {
+                    #(#restore_arms)*
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::{format_ident, quote};
+    use syn::parse2;
+
+    use crate::{
+        generating::generate_undo::{
+            generate_record_undo_statement, generate_undo_snapshot_statement,
+        },
+        parsing::macro_args::MacroArgs,
+    };
+
+    #[test]
+    fn generate_undo_statements() {
+        let macro_args =
+            parse2::<MacroArgs>(quote! {"tests/good_source_file/mod.rs", undo}).unwrap();
+        let result = generate_undo_snapshot_statement(
+            &macro_args,
+            &format_ident!("MyModel"),
+            &format_ident!("MyModelLock"),
+            &format_ident!("my_model_lock"),
+        );
+        let expected = quote! {
+            let undo_snapshot = ModelSnapshot::MyModel(
+                <MyModelLock as generate_cqrs_api::SwappableModelLock<MyModel>>::read_model(
+                    my_model_lock,
+                    MyModel::clone,
+                ),
+            );
+        };
+        assert_eq!(expected.to_string(), result.to_string());
+        let expected = quote! {
+            if state_changed {
+                lifecycle.undo_history.record(undo_snapshot);
+            }
+        };
+        assert_eq!(
+            expected.to_string(),
+            generate_record_undo_statement(&macro_args).to_string()
+        );
+    }
+    #[test]
+    fn generate_nothing_without_undo() {
+        let macro_args = parse2::<MacroArgs>(quote! {"tests/good_source_file/mod.rs"}).unwrap();
+        assert!(generate_undo_snapshot_statement(
+            &macro_args,
+            &format_ident!("MyModel"),
+            &format_ident!("MyModelLock"),
+            &format_ident!("my_model_lock"),
+        )
+        .is_empty());
+        assert!(generate_record_undo_statement(&macro_args).is_empty());
+    }
+}
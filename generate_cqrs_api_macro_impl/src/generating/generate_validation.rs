@@ -0,0 +1,190 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{File, Ident, ImplItem, Item};
+
+use crate::cqrs_attribute_macro_impl::{get_cqrs_fn_attributes, CqrsFnAttributes, ValidationRule};
+use crate::generating::generate_cqrs_impl::get_cqrs_enum_variant_ident;
+
+/// generates `validate()` of the CQRS enum, checking the arguments' `#[validate(...)]` rules before the model's lock is taken
+/// (only if an argument has rules)
+/// @returns (the statement calling `validate()`, the fn)
+pub(crate) fn generate_validation(
+    enum_ident: &Ident,
+    cqrs_sig_idents: &[(Ident, Vec<Ident>)],
+    cqrs_fns_attributes: &[CqrsFnAttributes],
+) -> (TokenStream, TokenStream) {
+    let validated_arms = cqrs_sig_idents
+        .iter()
+        .zip(cqrs_fns_attributes)
+        .filter(|(_, attributes)| !attributes.validations.is_empty())
+        .map(|((ident, args), attributes)| {
+            let variant = get_cqrs_enum_variant_ident(ident);
+            let command = format!("{enum_ident}::{variant}");
+            // only the validated arguments are bound
+            let patterns = args.iter().map(|arg| {
+                if attributes
+                    .validations
+                    .iter()
+                    .any(|validation| validation.argument == *arg)
+                {
+                    quote! {#arg}
+                } else {
+                    quote! {_}
+                }
+            });
+            let checks = attributes.validations.iter().flat_map(|validation| {
+                let argument = &validation.argument;
+                let argument_name = argument.to_string();
+                let command = &command;
+                validation.rules.iter().map(move |rule| {
+                    let check = generate_check(rule, argument);
+                    quote! {
+                        #check.map_err(|reason| ProcessingError::InvalidArgument {
+                            command: #command.to_string(),
+                            argument: #argument_name.to_string(),
+                            reason,
+                        })?;
+                    }
+                })
+            });
+            quote! {
+                #enum_ident::#variant(#(#patterns),*) => {
+                    #(#checks)*
+                }
+            }
+        })
+        .collect::<Vec<TokenStream>>();
+    if validated_arms.is_empty() {
+        return (quote! {}, quote! {});
+    }
+    let unvalidated_arm = if validated_arms.len() < cqrs_sig_idents.len() {
+        quote! { _ => {} }
+    } else {
+        quote! {}
+    };
+    (
+        quote! {
+            self.validate()?;
+        },
+        quote! {
+            /// checks the arguments' `#[validate(...)]` rules
+            fn validate(&self) -> Result<(), ProcessingError> {
+                match self {
+                    #(#validated_arms)*
+                    #unvalidated_arm
+                }
+                Ok(())
+            }
+        },
+    )
+}
+
+/// if a function of the model's file has arguments annotated with `#[validate(...)]`
+pub(crate) fn validates_arguments(ast: &File) -> bool {
+    ast.items
+        .iter()
+        .filter_map(|item| match item {
+            Item::Impl(item_impl) => Some(&item_impl.items),
+            _ => None,
+        })
+        .flatten()
+        .any(|impl_item| {
+            matches!(impl_item, ImplItem::Fn(function)
+                if !get_cqrs_fn_attributes(function).validations.is_empty())
+        })
+}
+
+/// @returns the expression checking the rule, resulting in `Err(reason)` for an invalid argument
+fn generate_check(rule: &ValidationRule, argument: &Ident) -> TokenStream {
+    let bound = |bound: &Option<syn::Expr>| match bound {
+        Some(bound) => quote! { Some(#bound) },
+        None => quote! { None },
+    };
+    match rule {
+        ValidationRule::Range { min, max } => {
+            let (min, max) = (bound(min), bound(max));
+            quote! { generate_cqrs_api::check_range(#argument, #min, #max) }
+        }
+        ValidationRule::Length { min, max } => {
+            let (min, max) = (bound(min), bound(max));
+            quote! { generate_cqrs_api::check_length(#argument, #min, #max) }
+        }
+        ValidationRule::With(validating_fn) => quote! { #validating_fn(#argument) },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::{format_ident, quote};
+    use syn::parse_quote;
+
+    use crate::{
+        cqrs_attribute_macro_impl::{get_cqrs_fn_attributes, CqrsFnAttributes},
+        generating::generate_validation::generate_validation,
+    };
+
+    #[test]
+    fn generate_validate_fn() {
+        let cqrs_sig_idents = vec![
+            (
+                format_ident!("add_item"),
+                vec![format_ident!("item"), format_ident!("position")],
+            ),
+            (format_ident!("clean_list"), vec![]),
+        ];
+        let cqrs_fns_attributes = vec![
+            get_cqrs_fn_attributes(&parse_quote! {
+                #[cqrs]
+                pub(crate) fn add_item(
+                    &self,
+                    #[validate(length(min = 1, max = 200), with = no_tabs)] item: String,
+                    position: usize,
+                ) -> Result<(bool, Vec<MyModelEffect>), MyModelError> {
+                    Ok((true, vec![]))
+                }
+            }),
+            CqrsFnAttributes::default(),
+        ];
+        let (validate_statement, validate_fn) = generate_validation(
+            &format_ident!("MyModelCommand"),
+            &cqrs_sig_idents,
+            &cqrs_fns_attributes,
+        );
+        assert_eq!(
+            quote! { self.validate()?; }.to_string(),
+            validate_statement.to_string()
+        );
+        let expected = quote! {
+            /// checks the arguments' `#[validate(...)]` rules
+            fn validate(&self) -> Result<(), ProcessingError> {
+                match self {
+                    MyModelCommand::AddItem(item, _) => {
+                        generate_cqrs_api::check_length(item, Some(1), Some(200)).map_err(|reason| ProcessingError::InvalidArgument {
+                            command: "MyModelCommand::AddItem".to_string(),
+                            argument: "item".to_string(),
+                            reason,
+                        })?;
+                        no_tabs(item).map_err(|reason| ProcessingError::InvalidArgument {
+                            command: "MyModelCommand::AddItem".to_string(),
+                            argument: "item".to_string(),
+                            reason,
+                        })?;
+                    }
+                    _ => {}
+                }
+                Ok(())
+            }
+        };
+        assert_eq!(expected.to_string(), validate_fn.to_string());
+    }
+    #[test]
+    fn generate_nothing_without_rules() {
+        let (validate_statement, validate_fn) = generate_validation(
+            &format_ident!("MyModelCommand"),
+            &[(format_ident!("clean_list"), vec![])],
+            &[CqrsFnAttributes::default()],
+        );
+        assert!(validate_statement.is_empty());
+        assert!(validate_fn.is_empty());
+    }
+}
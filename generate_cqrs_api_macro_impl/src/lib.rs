@@ -1,3 +1,6 @@
+pub mod cqrs_attribute_macro_impl;
+pub mod cqrs_lock_macro_impl;
+pub mod cqrs_model_macro_impl;
 pub mod generate_api_macro_impl;
 mod generating;
 mod parsing;
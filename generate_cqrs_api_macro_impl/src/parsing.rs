@@ -1,6 +1,8 @@
 pub(crate) mod file_location_2_base_path;
+pub(crate) mod get_app_state_fields;
 pub(crate) mod get_enum;
 pub(crate) mod get_struct_by_trait;
+pub(crate) mod macro_args;
 // not used, but kept if neede later.
 // pub(crate) mod get_use_statements;
 pub(crate) mod extract_type;
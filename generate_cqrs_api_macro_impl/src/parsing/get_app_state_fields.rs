@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+
+use log::debug;
+use proc_macro2::Span;
+use syn::{File, Ident, Result};
+
+use super::extract_type::get_type_as_capital_ident;
+
+/// finds the fields of the struct implementing `AppState`, which hold the models' locks.
+/// Fields configured with `field(MyModelLock = "my_field")` are checked to exist,
+/// all others are looked up by their type.
+/// @returns a map "lock name: field"
+pub(crate) fn get_app_state_fields(
+    ast: &File,
+    domain_model_lock_idents: &[Ident],
+    configured_fields: &HashMap<String, Ident>,
+) -> Result<HashMap<String, Ident>> {
+    let app_state_ident = ast
+        .items
+        .iter()
+        .find_map(|item| match item {
+            syn::Item::Impl(item_impl) => match &item_impl.trait_ {
+                Some((_, trait_path, _)) if trait_path.is_ident("AppState") => {
+                    get_type_as_capital_ident(&item_impl.self_ty).ok()
+                }
+                _ => None,
+            },
+            _ => None,
+        })
+        .ok_or_else(|| {
+            syn::Error::new(
+                Span::call_site(),
+                "No 'impl AppState for' found in the given app_state_file!",
+            )
+        })?;
+    debug!("app state struct: {:#?}", app_state_ident);
+
+    let app_state_fields = ast
+        .items
+        .iter()
+        .find_map(|item| match item {
+            syn::Item::Struct(item_struct) if item_struct.ident == app_state_ident => {
+                Some(&item_struct.fields)
+            }
+            _ => None,
+        })
+        .ok_or_else(|| {
+            syn::Error::new(
+                Span::call_site(),
+                format!("The AppState struct '{app_state_ident}' is not defined in the given app_state_file!"),
+            )
+        })?
+        .iter()
+        .filter_map(|field| {
+            Some((
+                field.ident.to_owned()?,
+                get_type_as_capital_ident(&field.ty).ok()?,
+            ))
+        })
+        .collect::<Vec<(Ident, Ident)>>();
+
+    domain_model_lock_idents
+        .iter()
+        .map(|lock_ident| {
+            let lock_name = lock_ident.to_string();
+            if let Some(configured_field) = configured_fields.get(&lock_name) {
+                return if app_state_fields
+                    .iter()
+                    .any(|(field, _)| field == configured_field)
+                {
+                    Ok((lock_name, configured_field.to_owned()))
+                } else {
+                    Err(syn::Error::new(
+                        Span::call_site(),
+                        format!("field({lock_name} = \"{configured_field}\"), but the AppState struct '{app_state_ident}' has no field '{configured_field}'!"),
+                    ))
+                };
+            }
+            let matching_fields = app_state_fields
+                .iter()
+                .filter(|(_, tipe)| tipe == lock_ident)
+                .map(|(field, _)| field.to_owned())
+                .collect::<Vec<Ident>>();
+            match matching_fields.as_slice() {
+                [field] => Ok((lock_name, field.to_owned())),
+                [] => Err(syn::Error::new(
+                    Span::call_site(),
+                    format!("The AppState struct '{app_state_ident}' has no field of type '{lock_name}'! Add one, like '{}: {lock_name}'.", stringcase::snake_case(&lock_name)),
+                )),
+                _ => Err(syn::Error::new(
+                    Span::call_site(),
+                    format!("The AppState struct '{app_state_ident}' has more than one field of type '{lock_name}': {:?}. Choose one with field({lock_name} = \"...\").", matching_fields.iter().map(|field| field.to_string()).collect::<Vec<String>>()),
+                )),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use quote::format_ident;
+
+    use super::get_app_state_fields;
+
+    const CODE: &str = r#"
+        #[derive(Debug, serde::Serialize, serde::Deserialize)]
+        pub(crate) struct MyAppState {
+            pub todos: MyGoodDomainModelLock,
+            pub(crate) seconds: MySecondDomainModelLock,
+            pub backup: MySecondDomainModelLock,
+            dirty: AtomicBool,
+        }
+        impl AppState for MyAppState {
+            fn new<A: AppConfig>(app_config: &A) -> Self {
+                todo!()
+            }
+        }
+    "#;
+
+    #[test]
+    fn get_fields_by_type() {
+        let ast = syn::parse_file(CODE).expect("test oracle should be parsable");
+        let result = get_app_state_fields(
+            &ast,
+            &[format_ident!("MyGoodDomainModelLock")],
+            &HashMap::new(),
+        )
+        .unwrap();
+        assert_eq!("todos", result["MyGoodDomainModelLock"].to_string());
+    }
+    #[test]
+    fn get_configured_fields() {
+        let ast = syn::parse_file(CODE).expect("test oracle should be parsable");
+        let result = get_app_state_fields(
+            &ast,
+            &[
+                format_ident!("MyGoodDomainModelLock"),
+                format_ident!("MySecondDomainModelLock"),
+            ],
+            &HashMap::from([(
+                "MySecondDomainModelLock".to_string(),
+                format_ident!("seconds"),
+            )]),
+        )
+        .unwrap();
+        assert_eq!("todos", result["MyGoodDomainModelLock"].to_string());
+        assert_eq!("seconds", result["MySecondDomainModelLock"].to_string());
+    }
+    #[test]
+    fn fail_missing_lock_field() {
+        let ast = syn::parse_file(CODE).expect("test oracle should be parsable");
+        let error = get_app_state_fields(
+            &ast,
+            &[format_ident!("MyThirdDomainModelLock")],
+            &HashMap::new(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            "The AppState struct 'MyAppState' has no field of type 'MyThirdDomainModelLock'! Add one, like 'my_third_domain_model_lock: MyThirdDomainModelLock'.",
+            error.to_string()
+        );
+    }
+    #[test]
+    fn fail_ambiguous_lock_field() {
+        let ast = syn::parse_file(CODE).expect("test oracle should be parsable");
+        let error = get_app_state_fields(
+            &ast,
+            &[format_ident!("MySecondDomainModelLock")],
+            &HashMap::new(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            "The AppState struct 'MyAppState' has more than one field of type 'MySecondDomainModelLock': [\"seconds\", \"backup\"]. Choose one with field(MySecondDomainModelLock = \"...\").",
+            error.to_string()
+        );
+    }
+    #[test]
+    fn fail_configured_field_missing() {
+        let ast = syn::parse_file(CODE).expect("test oracle should be parsable");
+        let error = get_app_state_fields(
+            &ast,
+            &[format_ident!("MyGoodDomainModelLock")],
+            &HashMap::from([(
+                "MyGoodDomainModelLock".to_string(),
+                format_ident!("my_todos"),
+            )]),
+        )
+        .unwrap_err();
+        assert_eq!(
+            "field(MyGoodDomainModelLock = \"my_todos\"), but the AppState struct 'MyAppState' has no field 'my_todos'!",
+            error.to_string()
+        );
+    }
+    #[test]
+    fn fail_no_app_state_impl() {
+        let ast =
+            syn::parse_file("pub struct MyAppState {}").expect("test oracle should be parsable");
+        let error = get_app_state_fields(&ast, &[], &HashMap::new()).unwrap_err();
+        assert_eq!(
+            "No 'impl AppState for' found in the given app_state_file!",
+            error.to_string()
+        );
+    }
+}
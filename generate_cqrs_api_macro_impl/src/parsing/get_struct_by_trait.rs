@@ -52,9 +52,63 @@ pub(crate) fn get_structs_by_traits(ast: &File, trait_idents: &[&str]) -> HashMa
     }
 }
 
+/// finds the name of the struct annotated with the given attribute, like `#[cqrs_lock(...)]`
+pub(crate) fn get_struct_by_attribute(ast: &syn::File, attribute: &str) -> Option<Ident> {
+    ast.items.iter().find_map(|item| match item {
+        syn::Item::Struct(item_struct)
+            if item_struct.attrs.iter().any(|attr| {
+                attr.path()
+                    .segments
+                    .last()
+                    .is_some_and(|segment| segment.ident == attribute)
+            }) =>
+        {
+            Some(item_struct.ident.to_owned())
+        }
+        _ => None,
+    })
+}
+
+/// if the model's `impl CqrsModel` in the model's file implements `check_invariants()` - with or without `#[cqrs_model]`
+pub(crate) fn checks_invariants(ast: &File, domain_model_ident: &Ident) -> bool {
+    ast.items.iter().any(|item| match item {
+        syn::Item::Impl(item_impl) => {
+            item_impl.trait_.as_ref().is_some_and(|(_, trait_path, _)| {
+                trait_path
+                    .segments
+                    .last()
+                    .is_some_and(|segment| segment.ident == "CqrsModel")
+            }) && get_type_as_capital_ident(&item_impl.self_ty)
+                .is_ok_and(|ident| ident == *domain_model_ident)
+                && item_impl.items.iter().any(|impl_item| {
+                    matches!(impl_item, syn::ImplItem::Fn(function) if function.sig.ident == "check_invariants")
+                })
+        }
+        _ => false,
+    })
+}
+
+/// if the model's lock in the model's file is a `#[cqrs_lock]` or implements `SwappableModelLock`
+pub(crate) fn is_swappable_lock(ast: &File, domain_model_lock_ident: &Ident) -> bool {
+    get_struct_by_attribute(ast, "cqrs_lock").is_some_and(|ident| ident == *domain_model_lock_ident)
+        || ast.items.iter().any(|item| match item {
+            syn::Item::Impl(item_impl) => {
+                item_impl.trait_.as_ref().is_some_and(|(_, trait_path, _)| {
+                    trait_path
+                        .segments
+                        .last()
+                        .is_some_and(|segment| segment.ident == "SwappableModelLock")
+                }) && get_type_as_capital_ident(&item_impl.self_ty)
+                    .is_ok_and(|ident| ident == *domain_model_lock_ident)
+            }
+            _ => false,
+        })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use quote::format_ident;
     use syn::parse_file;
 
     const AST_STR: &str = r#"
@@ -107,4 +161,69 @@ trait ModelTrait {
 
         get_structs_by_traits(&ast, &["ModelTrait", "DifferentTrait", "NotImpl"]);
     }
+    #[test]
+    fn find_struct_by_attribute() {
+        let ast = syn::parse_file(
+            r#"
+            #[derive(Debug)]
+            pub struct MyModel {}
+            #[generate_cqrs_api_macro::cqrs_lock(model = MyModel)]
+            #[derive(Debug, Clone)]
+            pub struct MyModelLock;
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            "MyModelLock",
+            get_struct_by_attribute(&ast, "cqrs_lock")
+                .unwrap()
+                .to_string()
+        );
+        assert_eq!(None, get_struct_by_attribute(&ast, "cqrs_model"));
+    }
+    #[test]
+    fn find_invariant_checks() {
+        let ast = syn::parse_file(
+            r#"
+            #[cqrs_model]
+            impl CqrsModel for MyCheckedModel {
+                fn check_invariants(&self) -> Result<(), String> {
+                    Ok(())
+                }
+            }
+            #[cqrs_model(version = 2, migrations(migrate_v1_to_v2))]
+            impl CqrsModel for MyVersionedModel {}
+            impl CqrsModel for MyManualModel {
+                fn check_invariants(&self) -> Result<(), String> {
+                    Ok(())
+                }
+            }
+            "#,
+        )
+        .unwrap();
+        assert!(checks_invariants(&ast, &format_ident!("MyCheckedModel")));
+        assert!(!checks_invariants(&ast, &format_ident!("MyVersionedModel")));
+        assert!(checks_invariants(&ast, &format_ident!("MyManualModel")));
+    }
+    #[test]
+    fn find_swappable_locks() {
+        let ast = syn::parse_file(
+            r#"
+            #[cqrs_lock(model = MyModel)]
+            pub struct MyModelLock;
+            pub struct MyManualLock {
+                lock: RustAutoOpaque<MyModel>,
+            }
+            impl generate_cqrs_api::SwappableModelLock<MyModel> for MyManualLock {}
+            pub struct MyPlainLock {
+                lock: RustAutoOpaque<MyModel>,
+            }
+            impl CqrsModelLock<MyModel> for MyPlainLock {}
+            "#,
+        )
+        .unwrap();
+        assert!(is_swappable_lock(&ast, &format_ident!("MyModelLock")));
+        assert!(is_swappable_lock(&ast, &format_ident!("MyManualLock")));
+        assert!(!is_swappable_lock(&ast, &format_ident!("MyPlainLock")));
+    }
 }
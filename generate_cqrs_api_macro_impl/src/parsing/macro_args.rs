@@ -0,0 +1,759 @@
+use std::collections::HashMap;
+
+use log::info;
+use quote::{format_ident, ToTokens};
+use stringcase::snake_case_with_sep;
+use syn::{
+    parse::{Parse, ParseStream},
+    punctuated::Punctuated,
+    Expr, Ident, Lit, LitStr, Meta, Path, Result, Token,
+};
+
+/// default time window in which changes are collected before they are persisted
+const DEFAULT_DEBOUNCE_MS: u64 = 500;
+/// default number of journaled commands after which a snapshot of the app state is persisted
+const DEFAULT_SNAPSHOT_EVERY: u64 = 100;
+/// default number of commands, which can be undone
+const DEFAULT_UNDO_LIMIT: usize = 100;
+
+const SUPPORTED_OPTIONS: &str = r#"Unknown option! Supported are:
+    persistence = "immediate" | "debounced" | "manual" | "journal",
+    debounce_ms = <milliseconds>,
+    snapshot_every = <number of journaled commands>,
+    app_state_field = "<the Lifecycle's field holding the AppState>",
+    app_state_file = "<path to the file defining the AppState struct>",
+    generate_app_state [= "<name of the generated AppState struct>"],
+    serializable_cqrs,
+    undo,
+    undo_limit = <number of undoable commands>,
+    preview,
+    interceptors,
+    metrics,
+    authorization,
+    default_lifecycle(app_config = <AppConfig>, app_state = <AppState>, persister = <AppStatePersister>)
+    field(<ModelLock> = "<the AppState's field holding the ModelLock>", ...)"#;
+
+/// defines when the app state is persisted after a CQRS command changed it
+#[derive(Debug, PartialEq, Clone, Default)]
+pub(crate) enum PersistenceStrategy {
+    /// persists within the command's `process()` call (the default)
+    #[default]
+    Immediate,
+    /// persists at most every `debounce_ms` milliseconds on a background thread
+    Debounced { debounce_ms: u64 },
+    /// only marks the state as dirty, the shell app calls `Lifecycle::persist()`
+    Manual,
+    /// appends each command to the `CommandJournal`, persisting a snapshot every `snapshot_every` commands
+    Journal { snapshot_every: u64 },
+}
+
+/// the types used by the generated default `impl Lifecycle` methods,
+/// set by `default_lifecycle(app_config = AppConfigImpl, app_state = AppStateImpl, persister = FilePersister)`
+#[derive(Clone)]
+pub(crate) struct DefaultLifecycle {
+    pub(crate) app_config: Path,
+    pub(crate) app_state: Path,
+    pub(crate) persister: Path,
+}
+
+// syn implements Debug only with the "extra-traits" feature
+impl std::fmt::Debug for DefaultLifecycle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DefaultLifecycle")
+            .field("app_config", &self.app_config.to_token_stream().to_string())
+            .field("app_state", &self.app_state.to_token_stream().to_string())
+            .field("persister", &self.persister.to_token_stream().to_string())
+            .finish()
+    }
+}
+
+/// the arguments passed to the macro, like
+/// #[generate_api("domain/model.rs", persistence = "debounced", debounce_ms = 200)]
+#[derive(Debug, Default, Clone)]
+pub(crate) struct MacroArgs {
+    pub(crate) file_paths: Vec<String>,
+    pub(crate) persistence: PersistenceStrategy,
+    /// set by `app_state_field = "state"`
+    pub(crate) app_state_field: Option<Ident>,
+    /// set by `field(MyModelLock = "my_model")`, keyed by the lock's name
+    pub(crate) model_fields: HashMap<String, Ident>,
+    /// set by `app_state_file = "src/app_state.rs"`, to look up the model fields by their type
+    pub(crate) app_state_file: Option<String>,
+    /// set by `generate_app_state` (named `AppStateImpl`) or `generate_app_state = "MyAppState"`
+    pub(crate) generate_app_state: Option<Ident>,
+    pub(crate) default_lifecycle: Option<DefaultLifecycle>,
+    /// set by `serializable_cqrs`, deriving serde for the `*Command` and `*Query` enums and generating `AnyCommand` and `AnyQuery`
+    pub(crate) serializable_cqrs: bool,
+    /// set by `undo`, the number of commands which can be undone (`undo_limit = 20`)
+    pub(crate) undo_limit: Option<usize>,
+    /// set by `preview`, generating `preview()` of the commands, running them on a copy of the model
+    pub(crate) preview: bool,
+    /// set by `interceptors`, running the lifecycle's `Interceptors` around every CQRS call
+    pub(crate) interceptors: bool,
+    /// set by `metrics`, recording the CQRS calls and persisting in the lifecycle's `MetricsRegistry`
+    pub(crate) metrics: bool,
+    /// set by `authorization`, checking `#[cqrs(requires = "role")]` with the lifecycle's `Authorization`
+    pub(crate) authorization: bool,
+    /// set by the cargo feature `tracing`, tracing every CQRS call in a span
+    pub(crate) tracing: bool,
+}
+
+impl MacroArgs {
+    /// the Lifecycle's field holding the AppState. Defaults to `app_state`.
+    pub(crate) fn app_state_field(&self) -> Ident {
+        self.app_state_field
+            .clone()
+            .unwrap_or_else(|| format_ident!("app_state"))
+    }
+
+    /// the AppState's field holding the model's lock. Defaults to the lock's name in snake_case.
+    pub(crate) fn model_field(&self, domain_model_lock_ident: &Ident) -> Ident {
+        self.model_fields
+            .get(&domain_model_lock_ident.to_string())
+            .cloned()
+            .unwrap_or_else(|| {
+                format_ident!(
+                    "{}",
+                    snake_case_with_sep(&domain_model_lock_ident.to_string(), "_")
+                )
+            })
+    }
+}
+
+/// a single argument: either a file path or an option
+enum MacroArg {
+    FilePath(LitStr),
+    Option(Box<Meta>),
+}
+
+impl Parse for MacroArg {
+    fn parse(input: ParseStream) -> Result<Self> {
+        if input.peek(LitStr) {
+            Ok(MacroArg::FilePath(input.parse()?))
+        } else {
+            Ok(MacroArg::Option(Box::new(input.parse()?)))
+        }
+    }
+}
+
+impl Parse for MacroArgs {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let args = Punctuated::<MacroArg, Token![,]>::parse_terminated(input)?;
+        let mut file_paths = vec![];
+        let mut persistence = None;
+        let mut debounce_ms = None;
+        let mut snapshot_every = None;
+        let mut app_state_field = None;
+        let mut model_fields = HashMap::new();
+        let mut app_state_file = None;
+        let mut generate_app_state = None;
+        let mut default_lifecycle = None;
+        let mut serializable_cqrs = false;
+        let mut undo = false;
+        let mut undo_limit = None;
+        let mut preview = false;
+        let mut interceptors = false;
+        let mut metrics = false;
+        let mut authorization = false;
+        for arg in args {
+            match arg {
+                MacroArg::FilePath(lit_str) => file_paths.push(lit_str.value()),
+                MacroArg::Option(meta) => {
+                    let option = meta
+                        .path()
+                        .get_ident()
+                        .map(|ident| ident.to_string())
+                        .unwrap_or_default();
+                    match option.as_str() {
+                        "persistence" => persistence = Some(get_lit_str(&meta)?),
+                        "debounce_ms" => debounce_ms = Some(get_lit_u64(&meta)?),
+                        "snapshot_every" => snapshot_every = Some(get_lit_u64(&meta)?),
+                        "app_state_field" => {
+                            app_state_field = Some(get_lit_str(&meta)?.parse::<Ident>()?)
+                        }
+                        "field" => model_fields.extend(get_model_fields(&meta)?),
+                        "app_state_file" => app_state_file = Some(get_lit_str(&meta)?.value()),
+                        "default_lifecycle" => default_lifecycle = Some(meta),
+                        "generate_app_state" => {
+                            generate_app_state = Some(match *meta {
+                                Meta::Path(_) => format_ident!("AppStateImpl"),
+                                _ => get_lit_str(&meta)?.parse::<Ident>()?,
+                            })
+                        }
+                        "serializable_cqrs" if matches!(*meta, Meta::Path(_)) => {
+                            serializable_cqrs = true
+                        }
+                        "undo" if matches!(*meta, Meta::Path(_)) => undo = true,
+                        "undo_limit" => undo_limit = Some(get_lit_u64(&meta)? as usize),
+                        "preview" if matches!(*meta, Meta::Path(_)) => preview = true,
+                        "interceptors" if matches!(*meta, Meta::Path(_)) => interceptors = true,
+                        "metrics" if matches!(*meta, Meta::Path(_)) => metrics = true,
+                        "authorization" if matches!(*meta, Meta::Path(_)) => authorization = true,
+                        _ => return Err(syn::Error::new_spanned(meta, SUPPORTED_OPTIONS)),
+                    }
+                }
+            }
+        }
+        if app_state_file.is_some() && generate_app_state.is_some() {
+            return Err(syn::Error::new(
+                proc_macro2::Span::call_site(),
+                "'app_state_file' can't be used with 'generate_app_state', the generated AppState's fields are known",
+            ));
+        }
+        let default_lifecycle = default_lifecycle
+            .map(|meta| get_default_lifecycle(&meta, generate_app_state.as_ref()))
+            .transpose()?;
+        let persistence = get_persistence_strategy(persistence, debounce_ms, snapshot_every)?;
+        if matches!(persistence, PersistenceStrategy::Journal { .. })
+            && !(serializable_cqrs && generate_app_state.is_some() && default_lifecycle.is_some())
+        {
+            return Err(syn::Error::new(
+                proc_macro2::Span::call_site(),
+                "persistence = \"journal\" needs 'serializable_cqrs', 'generate_app_state' and 'default_lifecycle(...)', to journal the commands and replay them on initialisation",
+            ));
+        }
+        let undo_limit = get_undo_limit(undo, undo_limit, &persistence)?;
+        if interceptors && !serializable_cqrs {
+            return Err(syn::Error::new(
+                proc_macro2::Span::call_site(),
+                "'interceptors' needs 'serializable_cqrs', to pass the serialized arguments to the interceptors",
+            ));
+        }
+        info!("Parsing content of: {:#?}", file_paths);
+        Ok(MacroArgs {
+            file_paths,
+            persistence,
+            app_state_field,
+            model_fields,
+            app_state_file,
+            generate_app_state,
+            default_lifecycle,
+            serializable_cqrs,
+            undo_limit,
+            preview,
+            interceptors,
+            metrics,
+            authorization,
+            tracing: cfg!(feature = "tracing"),
+        })
+    }
+}
+
+/// parses `field(MyModelLock = "my_model", MyOtherModelLock = "other")`
+fn get_model_fields(meta: &Meta) -> Result<Vec<(String, Ident)>> {
+    let Meta::List(list) = meta else {
+        return Err(syn::Error::new_spanned(
+            meta,
+            "Expected 'field(MyModelLock = \"my_app_state_field\")'",
+        ));
+    };
+    list.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)?
+        .iter()
+        .map(|entry| {
+            let lock_ident = entry.path().require_ident()?;
+            let field_ident = get_lit_str(entry)?.parse::<Ident>()?;
+            Ok((lock_ident.to_string(), field_ident))
+        })
+        .collect()
+}
+
+/// parses `default_lifecycle(app_config = AppConfigImpl, app_state = AppStateImpl, persister = FilePersister)`.
+/// `app_state` can be omitted when the AppState is generated.
+fn get_default_lifecycle(
+    meta: &Meta,
+    generated_app_state: Option<&Ident>,
+) -> Result<DefaultLifecycle> {
+    const USAGE: &str = "Expected 'default_lifecycle(app_config = MyAppConfig, app_state = MyAppState, persister = MyAppStatePersister)'";
+    let Meta::List(list) = meta else {
+        return Err(syn::Error::new_spanned(meta, USAGE));
+    };
+    let (mut app_config, mut app_state, mut persister) = (None, None, None);
+    for entry in list.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)? {
+        let value = Some(get_path(&entry)?);
+        match entry
+            .path()
+            .get_ident()
+            .map(|ident| ident.to_string())
+            .as_deref()
+        {
+            Some("app_config") => app_config = value,
+            Some("app_state") => app_state = value,
+            Some("persister") => persister = value,
+            _ => return Err(syn::Error::new_spanned(entry, USAGE)),
+        }
+    }
+    let app_state =
+        app_state.or_else(|| generated_app_state.map(|ident| Path::from(ident.to_owned())));
+    match (app_config, app_state, persister) {
+        (Some(app_config), Some(app_state), Some(persister)) => Ok(DefaultLifecycle {
+            app_config,
+            app_state,
+            persister,
+        }),
+        _ => Err(syn::Error::new_spanned(meta, USAGE)),
+    }
+}
+
+fn get_persistence_strategy(
+    persistence: Option<LitStr>,
+    debounce_ms: Option<u64>,
+    snapshot_every: Option<u64>,
+) -> Result<PersistenceStrategy> {
+    let strategy = match persistence.as_ref().map(LitStr::value).as_deref() {
+        None | Some("immediate") => PersistenceStrategy::Immediate,
+        Some("debounced") => PersistenceStrategy::Debounced {
+            debounce_ms: debounce_ms.unwrap_or(DEFAULT_DEBOUNCE_MS),
+        },
+        Some("manual") => PersistenceStrategy::Manual,
+        Some("journal") => PersistenceStrategy::Journal {
+            snapshot_every: snapshot_every.unwrap_or(DEFAULT_SNAPSHOT_EVERY),
+        },
+        Some(other) => {
+            return Err(syn::Error::new(
+                persistence.expect("checked above").span(),
+                format!("Unknown persistence strategy '{other}'! Use \"immediate\", \"debounced\", \"manual\" or \"journal\"."),
+            ))
+        }
+    };
+    if debounce_ms.is_some() && !matches!(strategy, PersistenceStrategy::Debounced { .. }) {
+        return Err(syn::Error::new(
+            proc_macro2::Span::call_site(),
+            "'debounce_ms' can only be used with persistence = \"debounced\"",
+        ));
+    }
+    if snapshot_every.is_some() && !matches!(strategy, PersistenceStrategy::Journal { .. }) {
+        return Err(syn::Error::new(
+            proc_macro2::Span::call_site(),
+            "'snapshot_every' can only be used with persistence = \"journal\"",
+        ));
+    }
+    Ok(strategy)
+}
+
+fn get_undo_limit(
+    undo: bool,
+    undo_limit: Option<usize>,
+    persistence: &PersistenceStrategy,
+) -> Result<Option<usize>> {
+    if !undo {
+        return match undo_limit {
+            Some(_) => Err(syn::Error::new(
+                proc_macro2::Span::call_site(),
+                "'undo_limit' can only be used with 'undo'",
+            )),
+            None => Ok(None),
+        };
+    }
+    // replaying the journal would redo undone commands
+    if matches!(persistence, PersistenceStrategy::Journal { .. }) {
+        return Err(syn::Error::new(
+            proc_macro2::Span::call_site(),
+            "'undo' can't be used with persistence = \"journal\"",
+        ));
+    }
+    Ok(Some(undo_limit.unwrap_or(DEFAULT_UNDO_LIMIT)))
+}
+
+/// gets the value of `option = "value"`
+fn get_lit_str(meta: &Meta) -> Result<LitStr> {
+    match meta {
+        Meta::NameValue(name_value) => match &name_value.value {
+            Expr::Lit(expr_lit) => match &expr_lit.lit {
+                Lit::Str(lit_str) => Ok(lit_str.to_owned()),
+                _ => Err(syn::Error::new_spanned(
+                    &name_value.value,
+                    "Expected a string literal",
+                )),
+            },
+            _ => Err(syn::Error::new_spanned(
+                &name_value.value,
+                "Expected a string literal",
+            )),
+        },
+        _ => Err(syn::Error::new_spanned(
+            meta,
+            "Expected 'option = \"value\"'",
+        )),
+    }
+}
+
+/// gets the value of `option = MyType`
+fn get_path(meta: &Meta) -> Result<Path> {
+    match meta {
+        Meta::NameValue(name_value) => match &name_value.value {
+            Expr::Path(expr_path) => Ok(expr_path.path.to_owned()),
+            _ => Err(syn::Error::new_spanned(
+                &name_value.value,
+                "Expected a type",
+            )),
+        },
+        _ => Err(syn::Error::new_spanned(meta, "Expected 'option = MyType'")),
+    }
+}
+
+/// gets the value of `option = 42`
+fn get_lit_u64(meta: &Meta) -> Result<u64> {
+    match meta {
+        Meta::NameValue(name_value) => match &name_value.value {
+            Expr::Lit(expr_lit) => match &expr_lit.lit {
+                Lit::Int(lit_int) => lit_int.base10_parse::<u64>(),
+                _ => Err(syn::Error::new_spanned(
+                    &name_value.value,
+                    "Expected a number",
+                )),
+            },
+            _ => Err(syn::Error::new_spanned(
+                &name_value.value,
+                "Expected a number",
+            )),
+        },
+        _ => Err(syn::Error::new_spanned(meta, "Expected 'option = number'")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::{format_ident, quote};
+    use syn::parse2;
+
+    use super::{MacroArgs, PersistenceStrategy};
+
+    #[test]
+    fn parse_one_filepath() {
+        let input = quote! {"tests/good_source_file/mod.rs"};
+        let result = parse2::<MacroArgs>(input).unwrap();
+        assert_eq!(vec!["tests/good_source_file/mod.rs"], result.file_paths);
+        assert_eq!(PersistenceStrategy::Immediate, result.persistence);
+    }
+    #[test]
+    fn parse_two_filepaths() {
+        let input = quote! {"tests/good_source_file/mod.rs", "tests/second_model_file/mod.rs"};
+        assert_eq!(
+            vec![
+                "tests/good_source_file/mod.rs",
+                "tests/second_model_file/mod.rs"
+            ],
+            parse2::<MacroArgs>(input).unwrap().file_paths
+        );
+    }
+    #[test]
+    fn parse_three_filepaths() {
+        let input = quote! {"tests/good_source_file/mod.rs", "tests/second_model_file/mod.rs", "tests/third_model_file/mod.rs"};
+        assert_eq!(
+            vec![
+                "tests/good_source_file/mod.rs",
+                "tests/second_model_file/mod.rs",
+                "tests/third_model_file/mod.rs"
+            ],
+            parse2::<MacroArgs>(input).unwrap().file_paths
+        );
+    }
+    #[test]
+    fn parse_persistence_strategies() {
+        let manual = quote! {"tests/good_source_file/mod.rs", persistence = "manual"};
+        assert_eq!(
+            PersistenceStrategy::Manual,
+            parse2::<MacroArgs>(manual).unwrap().persistence
+        );
+        let debounced = quote! {"tests/good_source_file/mod.rs", persistence = "debounced"};
+        assert_eq!(
+            PersistenceStrategy::Debounced { debounce_ms: 500 },
+            parse2::<MacroArgs>(debounced).unwrap().persistence
+        );
+        let debounced_ms =
+            quote! {"tests/good_source_file/mod.rs", persistence = "debounced", debounce_ms = 200};
+        let result = parse2::<MacroArgs>(debounced_ms).unwrap();
+        assert_eq!(
+            PersistenceStrategy::Debounced { debounce_ms: 200 },
+            result.persistence
+        );
+        assert_eq!(vec!["tests/good_source_file/mod.rs"], result.file_paths);
+    }
+    #[test]
+    fn fail_unknown_persistence_strategy() {
+        let input = quote! {"tests/good_source_file/mod.rs", persistence = "sometimes"};
+        let error = parse2::<MacroArgs>(input).unwrap_err();
+        assert_eq!(
+            "Unknown persistence strategy 'sometimes'! Use \"immediate\", \"debounced\", \"manual\" or \"journal\".",
+            error.to_string()
+        );
+    }
+    #[test]
+    fn fail_debounce_ms_without_debounced() {
+        let input = quote! {"tests/good_source_file/mod.rs", debounce_ms = 200};
+        let error = parse2::<MacroArgs>(input).unwrap_err();
+        assert_eq!(
+            "'debounce_ms' can only be used with persistence = \"debounced\"",
+            error.to_string()
+        );
+    }
+    #[test]
+    fn parse_field_names() {
+        let input = quote! {
+            "tests/good_source_file/mod.rs",
+            app_state_field = "state",
+            field(MyGoodDomainModelLock = "todos", MySecondDomainModelLock = "seconds")
+        };
+        let result = parse2::<MacroArgs>(input).unwrap();
+        assert_eq!("state", result.app_state_field().to_string());
+        assert_eq!(
+            "todos",
+            result
+                .model_field(&format_ident!("MyGoodDomainModelLock"))
+                .to_string()
+        );
+        assert_eq!(
+            "seconds",
+            result
+                .model_field(&format_ident!("MySecondDomainModelLock"))
+                .to_string()
+        );
+    }
+    #[test]
+    fn parse_app_state_file() {
+        let input = quote! {"tests/good_source_file/mod.rs", app_state_file = "tests/mocks/app_state_mock.rs"};
+        assert_eq!(
+            Some("tests/mocks/app_state_mock.rs".to_string()),
+            parse2::<MacroArgs>(input).unwrap().app_state_file
+        );
+    }
+    #[test]
+    fn parse_generate_app_state() {
+        let default_name = quote! {"tests/good_source_file/mod.rs", generate_app_state};
+        assert_eq!(
+            Some(format_ident!("AppStateImpl")),
+            parse2::<MacroArgs>(default_name)
+                .unwrap()
+                .generate_app_state
+        );
+        let custom_name =
+            quote! {"tests/good_source_file/mod.rs", generate_app_state = "MyAppState"};
+        assert_eq!(
+            Some(format_ident!("MyAppState")),
+            parse2::<MacroArgs>(custom_name).unwrap().generate_app_state
+        );
+        assert_eq!(
+            None,
+            parse2::<MacroArgs>(quote! {"tests/good_source_file/mod.rs"})
+                .unwrap()
+                .generate_app_state
+        );
+    }
+    #[test]
+    fn parse_journal_persistence() {
+        let input = quote! {
+            "tests/good_source_file/mod.rs",
+            persistence = "journal",
+            snapshot_every = 20,
+            serializable_cqrs,
+            generate_app_state,
+            default_lifecycle(app_config = AppConfigImpl, persister = FilePersister)
+        };
+        assert_eq!(
+            PersistenceStrategy::Journal { snapshot_every: 20 },
+            parse2::<MacroArgs>(input).unwrap().persistence
+        );
+    }
+    #[test]
+    fn fail_journal_without_serializable_cqrs() {
+        let input = quote! {
+            "tests/good_source_file/mod.rs",
+            persistence = "journal",
+            generate_app_state,
+            default_lifecycle(app_config = AppConfigImpl, persister = FilePersister)
+        };
+        assert!(parse2::<MacroArgs>(input)
+            .unwrap_err()
+            .to_string()
+            .starts_with("persistence = \"journal\" needs 'serializable_cqrs'"));
+    }
+    #[test]
+    fn fail_snapshot_every_without_journal() {
+        let input = quote! {"tests/good_source_file/mod.rs", snapshot_every = 20};
+        assert_eq!(
+            "'snapshot_every' can only be used with persistence = \"journal\"",
+            parse2::<MacroArgs>(input).unwrap_err().to_string()
+        );
+    }
+    #[test]
+    fn parse_serializable_cqrs() {
+        assert!(
+            parse2::<MacroArgs>(quote! {"tests/good_source_file/mod.rs", serializable_cqrs})
+                .unwrap()
+                .serializable_cqrs
+        );
+        assert!(
+            !parse2::<MacroArgs>(quote! {"tests/good_source_file/mod.rs"})
+                .unwrap()
+                .serializable_cqrs
+        );
+    }
+    #[test]
+    fn parse_metrics() {
+        assert!(
+            parse2::<MacroArgs>(quote! {"tests/good_source_file/mod.rs", metrics})
+                .unwrap()
+                .metrics
+        );
+    }
+    #[test]
+    fn parse_preview() {
+        assert!(
+            parse2::<MacroArgs>(quote! {"tests/good_source_file/mod.rs", preview})
+                .unwrap()
+                .preview
+        );
+        assert!(
+            !parse2::<MacroArgs>(quote! {"tests/good_source_file/mod.rs"})
+                .unwrap()
+                .preview
+        );
+    }
+    #[test]
+    fn parse_authorization() {
+        assert!(
+            parse2::<MacroArgs>(quote! {"tests/good_source_file/mod.rs", authorization})
+                .unwrap()
+                .authorization
+        );
+        assert!(
+            !parse2::<MacroArgs>(quote! {"tests/good_source_file/mod.rs"})
+                .unwrap()
+                .authorization
+        );
+    }
+    #[test]
+    fn parse_interceptors() {
+        assert!(
+            parse2::<MacroArgs>(
+                quote! {"tests/good_source_file/mod.rs", serializable_cqrs, interceptors}
+            )
+            .unwrap()
+            .interceptors
+        );
+        assert_eq!(
+            "'interceptors' needs 'serializable_cqrs', to pass the serialized arguments to the interceptors",
+            parse2::<MacroArgs>(quote! {"tests/good_source_file/mod.rs", interceptors})
+                .unwrap_err()
+                .to_string()
+        );
+    }
+    #[test]
+    fn parse_undo() {
+        let parse_undo_limit =
+            |input| parse2::<MacroArgs>(input).map(|macro_args| macro_args.undo_limit);
+        assert_eq!(
+            Some(100),
+            parse_undo_limit(quote! {"tests/good_source_file/mod.rs", undo}).unwrap()
+        );
+        assert_eq!(
+            Some(20),
+            parse_undo_limit(quote! {"tests/good_source_file/mod.rs", undo, undo_limit = 20})
+                .unwrap()
+        );
+        assert_eq!(
+            None,
+            parse_undo_limit(quote! {"tests/good_source_file/mod.rs"}).unwrap()
+        );
+        assert_eq!(
+            "'undo_limit' can only be used with 'undo'",
+            parse_undo_limit(quote! {"tests/good_source_file/mod.rs", undo_limit = 20})
+                .unwrap_err()
+                .to_string()
+        );
+    }
+    #[test]
+    fn fail_undo_with_journal() {
+        let input = quote! {
+            "tests/good_source_file/mod.rs",
+            generate_app_state,
+            serializable_cqrs,
+            persistence = "journal",
+            undo,
+            default_lifecycle(app_config = AppConfigImpl, persister = FilePersister)
+        };
+        assert_eq!(
+            "'undo' can't be used with persistence = \"journal\"",
+            parse2::<MacroArgs>(input).unwrap_err().to_string()
+        );
+    }
+    #[test]
+    fn fail_generate_app_state_with_app_state_file() {
+        let input = quote! {
+            "tests/good_source_file/mod.rs",
+            generate_app_state,
+            app_state_file = "tests/mocks/app_state_mock.rs"
+        };
+        assert_eq!(
+            "'app_state_file' can't be used with 'generate_app_state', the generated AppState's fields are known",
+            parse2::<MacroArgs>(input).unwrap_err().to_string()
+        );
+    }
+    #[test]
+    fn parse_default_lifecycle() {
+        let input = quote! {
+            "tests/good_source_file/mod.rs",
+            default_lifecycle(app_config = AppConfigImpl, app_state = AppStateImpl, persister = persisters::FilePersister)
+        };
+        let default_lifecycle = parse2::<MacroArgs>(input)
+            .unwrap()
+            .default_lifecycle
+            .unwrap();
+        let app_config = &default_lifecycle.app_config;
+        let app_state = &default_lifecycle.app_state;
+        let persister = &default_lifecycle.persister;
+        assert_eq!(
+            "AppConfigImpl AppStateImpl persisters :: FilePersister",
+            quote! {#app_config #app_state #persister}.to_string()
+        );
+    }
+    #[test]
+    fn parse_default_lifecycle_with_generated_app_state() {
+        let input = quote! {
+            "tests/good_source_file/mod.rs",
+            default_lifecycle(app_config = AppConfigImpl, persister = FilePersister),
+            generate_app_state = "MyAppState"
+        };
+        let default_lifecycle = parse2::<MacroArgs>(input)
+            .unwrap()
+            .default_lifecycle
+            .unwrap();
+        assert!(default_lifecycle.app_state.is_ident("MyAppState"));
+    }
+    #[test]
+    fn fail_default_lifecycle_without_app_state() {
+        let input = quote! {
+            "tests/good_source_file/mod.rs",
+            default_lifecycle(app_config = AppConfigImpl, persister = FilePersister)
+        };
+        assert!(parse2::<MacroArgs>(input)
+            .unwrap_err()
+            .to_string()
+            .starts_with("Expected 'default_lifecycle("));
+    }
+    #[test]
+    fn default_field_names() {
+        let result = parse2::<MacroArgs>(quote! {"tests/good_source_file/mod.rs"}).unwrap();
+        assert_eq!("app_state", result.app_state_field().to_string());
+        assert_eq!(
+            "my_good_domain_model_lock",
+            result
+                .model_field(&format_ident!("MyGoodDomainModelLock"))
+                .to_string()
+        );
+    }
+    #[test]
+    fn fail_field_name_not_an_ident() {
+        let input =
+            quote! {"tests/good_source_file/mod.rs", field(MyGoodDomainModelLock = "my todos")};
+        assert!(parse2::<MacroArgs>(input).is_err());
+    }
+    #[test]
+    fn fail_unknown_option() {
+        let input = quote! {"tests/good_source_file/mod.rs", persist = "always"};
+        assert!(parse2::<MacroArgs>(input)
+            .unwrap_err()
+            .to_string()
+            .starts_with("Unknown option!"));
+    }
+}
@@ -1,5 +1,5 @@
-use log::{debug, info, trace};
-use proc_macro2::{Span, TokenStream, TokenTree};
+use log::{debug, trace};
+use proc_macro2::Span;
 use syn::Result;
 
 use crate::{
@@ -7,28 +7,8 @@ use crate::{
     parsing::file_location_2_base_path::file_location_2_base_path,
 };
 
-/// extracts file locations from a TokenStream
-pub(crate) fn tokens_2_file_locations(file_paths: TokenStream) -> Result<Vec<String>> {
-    let file_paths = file_paths
-        .into_iter()
-        .filter_map(|token| match token {
-            TokenTree::Literal(literal) => Some(literal),
-            _ => None,
-        })
-        .map(|literal| {
-            // if to_string() breaks, parse it with https://github.com/LukasKalbertodt/litrs/
-            let cleaned = literal.to_string();
-            cleaned[1..cleaned.len() - 1].to_string()
-        })
-        .collect::<Vec<String>>();
-    info!("Parsing content of: {:#?}", file_paths);
-    Ok(file_paths)
-}
-
 /// reads multiple rust files, generates use statements for them and returns their content in one concatenated String
-pub(crate) fn read_rust_file_content(
-    file_paths: Vec<String>,
-) -> Result<Vec<ParsedFiles>> {
+pub(crate) fn read_rust_file_content(file_paths: Vec<String>) -> Result<Vec<ParsedFiles>> {
     file_paths.iter().map(|file_path| { 
         // Attempt to read each file's content as a string.
         std::fs::read_to_string(file_path)           
@@ -55,43 +35,4 @@ pub(crate) fn read_rust_file_content(
                 ParsedFiles{ base_path, source_code: SourceCodeString(source)}
             })
         }).collect()
-
-}
-
-#[cfg(test)]
-mod tests {
-    use crate::parsing::read_rust_files::tokens_2_file_locations;
-    use quote::quote;
-
-    #[test]
-    fn parse_one_filepath() {
-        let input = quote! {"tests/good_source_file/mod.rs"};
-        assert_eq!(
-            vec!["tests/good_source_file/mod.rs"],
-            tokens_2_file_locations(input).unwrap()
-        );
-    }
-    #[test]
-    fn parse_two_filepaths() {
-        let input = quote! {"tests/good_source_file/mod.rs", "tests/second_model_file/mod.rs"};
-        assert_eq!(
-            vec![
-                "tests/good_source_file/mod.rs",
-                "tests/second_model_file/mod.rs"
-            ],
-            tokens_2_file_locations(input).unwrap()
-        );
-    }
-    #[test]
-    fn parse_three_filepaths() {
-        let input = quote! {"tests/good_source_file/mod.rs", "tests/second_model_file/mod.rs", "tests/third_model_file/mod.rs"};
-        assert_eq!(
-            vec![
-                "tests/good_source_file/mod.rs",
-                "tests/second_model_file/mod.rs",
-                "tests/third_model_file/mod.rs"
-            ],
-            tokens_2_file_locations(input).unwrap()
-        );
-    }
 }
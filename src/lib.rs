@@ -1,14 +1,49 @@
 extern crate proc_macro;
 
-use generate_cqrs_api_macro_impl::generate_api_macro_impl;
+use generate_cqrs_api_macro_impl::{
+    cqrs_attribute_macro_impl, cqrs_lock_macro_impl, cqrs_model_macro_impl, generate_api_macro_impl,
+};
 use proc_macro::TokenStream;
 
 #[proc_macro_attribute]
-pub fn generate_api(file_paths: TokenStream, item: TokenStream) -> proc_macro::TokenStream {
+pub fn generate_api(macro_args: TokenStream, item: TokenStream) -> proc_macro::TokenStream {
     TokenStream::from(
         generate_api_macro_impl::generate_api_impl(
             proc_macro2::TokenStream::from(item),
-            proc_macro2::TokenStream::from(file_paths),
+            proc_macro2::TokenStream::from(macro_args),
+        )
+        .unwrap_or_else(|e| e.to_compile_error()),
+    )
+}
+
+#[proc_macro_attribute]
+pub fn cqrs_lock(macro_args: TokenStream, item: TokenStream) -> proc_macro::TokenStream {
+    TokenStream::from(
+        cqrs_lock_macro_impl::generate_cqrs_lock_impl(
+            proc_macro2::TokenStream::from(item),
+            proc_macro2::TokenStream::from(macro_args),
+        )
+        .unwrap_or_else(|e| e.to_compile_error()),
+    )
+}
+
+#[proc_macro_attribute]
+pub fn cqrs_model(macro_args: TokenStream, item: TokenStream) -> proc_macro::TokenStream {
+    TokenStream::from(
+        cqrs_model_macro_impl::generate_cqrs_model_impl(
+            proc_macro2::TokenStream::from(item),
+            proc_macro2::TokenStream::from(macro_args),
+        )
+        .unwrap_or_else(|e| e.to_compile_error()),
+    )
+}
+
+#[proc_macro_attribute]
+pub fn cqrs(macro_args: TokenStream, item: TokenStream) -> proc_macro::TokenStream {
+    TokenStream::from(
+        cqrs_attribute_macro_impl::generate_cqrs_attribute_impl(
+            proc_macro2::TokenStream::from(item),
+            proc_macro2::TokenStream::from(macro_args),
         )
         .unwrap_or_else(|e| e.to_compile_error()),
     )
@@ -0,0 +1,56 @@
+use crate::*;
+use generate_cqrs_api_macro::{cqrs, cqrs_lock, cqrs_model};
+
+#[derive(Clone, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct MyGuardedModel {
+    items: Vec<String>,
+}
+
+#[cqrs_lock(model = MyGuardedModel)]
+#[derive(Debug, Clone, Default)]
+pub struct MyGuardedModelLock;
+
+#[allow(dead_code)]
+pub enum MyGuardedModelEffect {
+    RenderItems(MyGuardedModelLock),
+}
+
+#[allow(dead_code)]
+impl MyGuardedModel {
+    pub fn get_items(&self) -> Vec<String> {
+        self.items.clone()
+    }
+}
+
+#[allow(dead_code)]
+impl MyGuardedModelLock {
+    pub(crate) fn add_item(
+        &self,
+        item: String,
+    ) -> Result<(bool, Vec<MyGuardedModelEffect>), MyGuardedProcessingError> {
+        self.lock.blocking_write().items.push(item);
+        Ok((true, vec![MyGuardedModelEffect::RenderItems(self.clone())]))
+    }
+    #[cqrs(requires = "admin")]
+    pub(crate) fn clean_list(
+        &self,
+    ) -> Result<(bool, Vec<MyGuardedModelEffect>), MyGuardedProcessingError> {
+        self.lock.blocking_write().items.clear();
+        Ok((true, vec![MyGuardedModelEffect::RenderItems(self.clone())]))
+    }
+    #[cqrs(requires = "auditor")]
+    pub(crate) fn get_all_items(
+        &self,
+    ) -> Result<Vec<MyGuardedModelEffect>, MyGuardedProcessingError> {
+        Ok(vec![MyGuardedModelEffect::RenderItems(self.clone())])
+    }
+}
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum MyGuardedProcessingError {
+    #[error("The item does not exist!")]
+    ItemDoesNotExist,
+}
+
+#[cqrs_model]
+impl CqrsModel for MyGuardedModel {}
@@ -0,0 +1,91 @@
+mod authorization_model_file;
+
+use std::sync::{Arc, RwLock};
+
+use generate_cqrs_api::{
+    Authorization, Authorizer, FileAppStatePersister, FileAppStatePersisterError,
+};
+use generate_cqrs_api_macro::generate_api;
+
+include!("./mocks/app_config_mock.rs");
+include!("./mocks/rust_auto_opaque_mock.rs");
+include!("./mocks/app_state_path_mock.rs");
+
+pub struct LifecycleImpl {
+    app_state: AppStateImpl,
+    persister: FileAppStatePersister,
+    authorization: Authorization,
+}
+
+#[generate_api(
+    "tests/authorization_model_file/mod.rs",
+    generate_app_state,
+    preview,
+    authorization,
+    default_lifecycle(app_config = AppConfigImpl, persister = FileAppStatePersister)
+)]
+impl Lifecycle for LifecycleImpl {
+    type Error = FileAppStatePersisterError;
+}
+
+/// grants the roles of the currently logged in principal
+struct SessionAuthorizer(Arc<RwLock<Vec<&'static str>>>);
+
+impl Authorizer for SessionAuthorizer {
+    fn is_authorized(&self, role: &str, _command: &str) -> bool {
+        self.0.read().unwrap().contains(&role)
+    }
+}
+
+fn get_items(lifecycle: &LifecycleImpl) -> Vec<String> {
+    lifecycle
+        .app_state
+        .my_guarded_model_lock
+        .lock
+        .blocking_read()
+        .get_items()
+}
+
+#[test]
+fn reject_calls_without_the_required_role() {
+    let app_state_path = fresh_app_state_path("authorization_tests");
+    let lifecycle = LifecycleImpl::new_instance(&AppConfigImpl::new(Some(
+        app_state_path.to_string_lossy().to_string(),
+    )))
+    .unwrap();
+
+    // calls without a required role don't need an authorizer
+    MyGuardedModelCommand::AddItem("item".to_string())
+        .process_with(&lifecycle)
+        .unwrap();
+    let Err(ProcessingError::Unauthorized { command }) =
+        MyGuardedModelCommand::CleanList.process_with(&lifecycle)
+    else {
+        panic!("cleaning the list without an authorizer has to be rejected");
+    };
+    assert_eq!("MyGuardedModelCommand::CleanList", command);
+
+    let session_roles = Arc::new(RwLock::new(vec!["auditor"]));
+    lifecycle
+        .authorization
+        .set_authorizer(SessionAuthorizer(session_roles.clone()));
+    assert!(MyGuardedModelQuery::GetAllItems
+        .process_with(&lifecycle)
+        .is_ok());
+    assert!(matches!(
+        MyGuardedModelCommand::CleanList.process_with(&lifecycle),
+        Err(ProcessingError::Unauthorized { .. })
+    ));
+    assert!(matches!(
+        MyGuardedModelCommand::CleanList.preview_with(&lifecycle),
+        Err(ProcessingError::Unauthorized { .. })
+    ));
+    assert_eq!(vec!["item"], get_items(&lifecycle));
+
+    // the principal logs in as admin
+    session_roles.write().unwrap().push("admin");
+    MyGuardedModelCommand::CleanList
+        .process_with(&lifecycle)
+        .unwrap();
+    assert!(get_items(&lifecycle).is_empty());
+}
@@ -0,0 +1,106 @@
+mod authorization_model_file;
+
+use std::sync::{Arc, RwLock};
+
+use generate_cqrs_api::{
+    Authorization, Authorizer, CommandJournal, CommandJournalError, FileAppStatePersister,
+    FileAppStatePersisterError,
+};
+use generate_cqrs_api_macro::generate_api;
+
+include!("./mocks/app_config_mock.rs");
+include!("./mocks/rust_auto_opaque_mock.rs");
+include!("./mocks/app_state_path_mock.rs");
+
+pub struct LifecycleImpl {
+    app_state: AppStateImpl,
+    persister: FileAppStatePersister,
+    journal: CommandJournal,
+    authorization: Authorization,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum LifecycleError {
+    #[error(transparent)]
+    Persister(#[from] FileAppStatePersisterError),
+    #[error(transparent)]
+    Journal(#[from] CommandJournalError),
+}
+
+impl AppStatePersistError<ProcessingError> for LifecycleError {
+    fn to_processing_error(&self) -> ProcessingError {
+        match self {
+            LifecycleError::Persister(error) => {
+                AppStatePersistError::<ProcessingError>::to_processing_error(error)
+            }
+            LifecycleError::Journal(error) => {
+                AppStatePersistError::<ProcessingError>::to_processing_error(error)
+            }
+        }
+    }
+    fn is_not_found(&self) -> bool {
+        matches!(self, LifecycleError::Persister(error)
+            if AppStatePersistError::<ProcessingError>::is_not_found(error))
+    }
+}
+
+#[generate_api(
+    "tests/authorization_model_file/mod.rs",
+    generate_app_state,
+    serializable_cqrs,
+    persistence = "journal",
+    authorization,
+    default_lifecycle(app_config = AppConfigImpl, persister = FileAppStatePersister)
+)]
+impl Lifecycle for LifecycleImpl {
+    type Error = LifecycleError;
+}
+
+/// grants the roles of the currently logged in principal
+struct SessionAuthorizer(Arc<RwLock<Vec<&'static str>>>);
+
+impl Authorizer for SessionAuthorizer {
+    fn is_authorized(&self, role: &str, _command: &str) -> bool {
+        self.0.read().unwrap().contains(&role)
+    }
+}
+
+fn get_items(lifecycle: &LifecycleImpl) -> Vec<String> {
+    lifecycle
+        .app_state
+        .my_guarded_model_lock
+        .lock
+        .blocking_read()
+        .get_items()
+}
+
+#[test]
+fn replay_authorized_commands_without_authorizer() {
+    let app_state_path = fresh_app_state_path("authorized_journal_tests");
+    let app_config = AppConfigImpl::new(Some(app_state_path.to_string_lossy().to_string()));
+
+    let lifecycle = LifecycleImpl::new_instance(&app_config).unwrap();
+    lifecycle
+        .authorization
+        .set_authorizer(SessionAuthorizer(Arc::new(RwLock::new(vec!["admin"]))));
+    MyGuardedModelCommand::AddItem("first item".to_string())
+        .process_with(&lifecycle)
+        .unwrap();
+    MyGuardedModelCommand::CleanList
+        .process_with(&lifecycle)
+        .unwrap();
+    MyGuardedModelCommand::AddItem("second item".to_string())
+        .process_with(&lifecycle)
+        .unwrap();
+    drop(lifecycle);
+
+    // the journaled commands were authorized when they were processed
+    let restarted = LifecycleImpl::new_instance(&app_config).unwrap();
+    assert_eq!(vec!["second item"], get_items(&restarted));
+    // calls after the replay are checked again
+    assert!(matches!(
+        MyGuardedModelCommand::CleanList.process_with(&restarted),
+        Err(ProcessingError::Unauthorized { .. })
+    ));
+    assert_eq!(vec!["second item"], get_items(&restarted));
+}
@@ -0,0 +1,54 @@
+mod cqrs_lock_model_file;
+
+use generate_cqrs_api::{BackupAppStatePersister, BackupAppStatePersisterError};
+use generate_cqrs_api_macro::generate_api;
+
+include!("./mocks/app_config_mock.rs");
+include!("./mocks/rust_auto_opaque_mock.rs");
+include!("./mocks/app_state_path_mock.rs");
+
+pub struct LifecycleImpl {
+    app_state: AppStateImpl,
+    persister: BackupAppStatePersister,
+}
+
+#[generate_api(
+    "tests/cqrs_lock_model_file/mod.rs",
+    generate_app_state,
+    default_lifecycle(app_config = AppConfigImpl, persister = BackupAppStatePersister)
+)]
+impl Lifecycle for LifecycleImpl {
+    type Error = BackupAppStatePersisterError;
+}
+
+#[test]
+fn restore_corrupt_app_state_from_backup() {
+    let app_state_path = fresh_app_state_path("backup_persister_tests");
+    let app_config = AppConfigImpl::new(Some(app_state_path.to_string_lossy().to_string()));
+
+    let lifecycle = LifecycleImpl::new_instance(&app_config).unwrap();
+    for item in ["first item", "second item"] {
+        MyLockedDomainModelCommand::AddItem(item.to_string())
+            .process_with(&lifecycle)
+            .unwrap();
+    }
+    // truncate the newest snapshot, like a crash while writing it would
+    let content = std::fs::read(&app_state_path).unwrap();
+    std::fs::write(&app_state_path, &content[..content.len() / 2]).unwrap();
+
+    LifecycleImpl::initialise(Some(app_state_path.to_string_lossy().to_string())).unwrap();
+    assert_eq!(
+        vec!["first item".to_string()],
+        LifecycleImpl::get_singleton()
+            .app_state
+            .my_locked_domain_model_lock
+            .lock
+            .blocking_read()
+            .get_items()
+    );
+    assert!(matches!(
+        LifecycleImpl::initialisation_result(),
+        generate_cqrs_api::InitialisationResult::RestoredFromBackup { snapshot, .. }
+            if snapshot.ends_with("app_state.json.1")
+    ));
+}
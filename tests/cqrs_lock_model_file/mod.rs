@@ -0,0 +1,50 @@
+use crate::*;
+use generate_cqrs_api_macro::cqrs_lock;
+
+#[derive(Clone, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct MyLockedDomainModel {
+    items: Vec<String>,
+}
+
+#[cqrs_lock(model = MyLockedDomainModel)]
+#[derive(Debug, Clone, Default)]
+pub struct MyLockedDomainModelLock;
+
+#[allow(dead_code)]
+pub enum MyLockedDomainModelEffect {
+    RenderItems(MyLockedDomainModelLock),
+}
+
+#[allow(dead_code)]
+impl MyLockedDomainModel {
+    pub fn get_items(&self) -> Vec<String> {
+        self.items.clone()
+    }
+}
+
+#[allow(dead_code)]
+impl MyLockedDomainModelLock {
+    pub(crate) fn add_item(
+        &self,
+        item: String,
+    ) -> Result<(bool, Vec<MyLockedDomainModelEffect>), MyLockedDomainProcessingError> {
+        self.lock.blocking_write().items.push(item);
+        Ok((
+            true,
+            vec![MyLockedDomainModelEffect::RenderItems(self.clone())],
+        ))
+    }
+    pub(crate) fn get_all_items(
+        &self,
+    ) -> Result<Vec<MyLockedDomainModelEffect>, MyLockedDomainProcessingError> {
+        Ok(vec![MyLockedDomainModelEffect::RenderItems(self.clone())])
+    }
+}
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum MyLockedDomainProcessingError {
+    #[error("The item does not exist!")]
+    ItemDoesNotExist,
+}
+
+impl CqrsModel for MyLockedDomainModel {}
@@ -0,0 +1,57 @@
+mod cqrs_lock_model_file;
+
+use generate_cqrs_api_macro::generate_api;
+
+include!("./mocks/app_config_mock.rs");
+include!("./mocks/app_state_persister_mock.rs");
+include!("./mocks/rust_auto_opaque_mock.rs");
+
+pub struct LifecycleImpl {
+    app_state: AppStateImpl,
+    persister: AppStatePersisterMock,
+}
+
+// AppStateImpl and the MyLockedDomainModelLock boilerplate are generated
+#[generate_api("tests/cqrs_lock_model_file/mod.rs", generate_app_state)]
+impl Lifecycle for LifecycleImpl {
+    type Error = AppStatePersisterErrorMock;
+    fn initialise_with_app_config<AC: AppConfig + std::fmt::Debug>(
+        _app_config: AC,
+    ) -> Result<&'static Self, Self::Error> {
+        unimplemented!()
+    }
+    fn initialise(_app_state_url: Option<String>) -> Result<(), Self::Error> {
+        unimplemented!()
+    }
+
+    fn get_singleton() -> &'static Self {
+        unimplemented!()
+    }
+    /// persist the app state to the previously stored location
+    fn persist() -> Result<(), ProcessingError> {
+        let lifecycle = Self::get_singleton();
+        let app_state = &lifecycle.app_state;
+        lifecycle
+            .persister
+            .persist_app_state(&lifecycle.app_state)
+            .map_err(|err| err.to_processing_error())?;
+        app_state.mark_persisted();
+        Ok(())
+    }
+
+    fn shutdown() -> Result<(), ProcessingError> {
+        Self::persist()
+    }
+}
+
+#[test]
+fn cqrs_lock_roundtrip() {
+    let lock = MyLockedDomainModelLock::for_model(MyLockedDomainModel::default());
+    lock.add_item("first item".to_string()).unwrap();
+
+    let serialized = serde_json::to_string(&lock).unwrap();
+    assert_eq!(r#"{"items":["first item"]}"#, serialized);
+    let deserialized: MyLockedDomainModelLock = serde_json::from_str(&serialized).unwrap();
+    let model = MyLockedDomainModel::from(deserialized);
+    assert_eq!(vec!["first item".to_string()], model.get_items());
+}
@@ -0,0 +1,64 @@
+mod good_source_file;
+mod second_model_file;
+
+use generate_cqrs_api::{FileAppStatePersister, FileAppStatePersisterError};
+use generate_cqrs_api_macro::generate_api;
+
+include!("./mocks/app_config_mock.rs");
+include!("./mocks/rust_auto_opaque_mock.rs");
+include!("./mocks/custom_app_state_mock.rs");
+include!("./mocks/app_state_path_mock.rs");
+
+pub struct MyAppLifecycle {
+    state: MyAppState,
+    persister: FileAppStatePersister,
+}
+
+#[generate_api(
+    "tests/good_source_file/mod.rs",
+    "tests/second_model_file/mod.rs",
+    app_state_field = "state",
+    // MySecondDomainModelLock's field is found by its type
+    app_state_file = "tests/mocks/custom_app_state_mock.rs",
+    field(MyGoodDomainModelLock = "todos"),
+    default_lifecycle(
+        app_config = AppConfigImpl,
+        app_state = MyAppState,
+        persister = FileAppStatePersister
+    )
+)]
+impl Lifecycle for MyAppLifecycle {
+    type Error = FileAppStatePersisterError;
+}
+
+#[test]
+fn process_on_the_custom_named_fields() {
+    let app_state_path = fresh_app_state_path("custom_names_tests");
+    let app_config = AppConfigImpl::new(Some(app_state_path.to_string_lossy().to_string()));
+    let lifecycle = MyAppLifecycle::new_instance(&app_config).unwrap();
+
+    MyGoodDomainModelCommand::AddItem("todo".to_string())
+        .process_with(&lifecycle)
+        .unwrap();
+    MySecondDomainModelCommand::AddSecondItem("second".to_string())
+        .process_with(&lifecycle)
+        .unwrap();
+    assert_eq!(
+        vec!["todo".to_string()],
+        lifecycle
+            .state
+            .todos
+            .lock
+            .blocking_read()
+            .get_items_as_string()
+    );
+    assert_eq!(
+        vec!["second".to_string()],
+        lifecycle.state.seconds.lock.blocking_read().get_items()
+    );
+    assert_eq!(
+        "#cqrs:json\n{\"todos\":{\"items\":[{\"text\":\"todo\"}]},\"seconds\":{\"items\":[{\"text\":\"second\"}]}}",
+        std::fs::read_to_string(&app_state_path).unwrap()
+    );
+    assert!(!lifecycle.state.dirty_flag_value());
+}
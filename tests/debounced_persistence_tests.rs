@@ -0,0 +1,136 @@
+mod cqrs_lock_model_file;
+
+use generate_cqrs_api::{FileAppStatePersister, FileAppStatePersisterError};
+use generate_cqrs_api_macro::generate_api;
+
+include!("./mocks/app_config_mock.rs");
+include!("./mocks/rust_auto_opaque_mock.rs");
+include!("./mocks/app_state_path_mock.rs");
+
+pub struct LifecycleImpl {
+    app_state: AppStateImpl,
+    persister: FileAppStatePersister,
+    deferred_persistence: generate_cqrs_api::DeferredPersistence<ProcessingError>,
+}
+
+#[generate_api(
+    "tests/cqrs_lock_model_file/mod.rs",
+    generate_app_state,
+    persistence = "debounced",
+    debounce_ms = 2000,
+    default_lifecycle(app_config = AppConfigImpl, persister = FileAppStatePersister)
+)]
+impl Lifecycle for LifecycleImpl {
+    type Error = FileAppStatePersisterError;
+}
+
+/// polls the condition until it holds, failing after a deadline way beyond the debounce
+fn wait_until(condition: impl Fn() -> bool) {
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+    while !condition() {
+        assert!(
+            std::time::Instant::now() < deadline,
+            "timed out waiting for the debounced write"
+        );
+        std::thread::sleep(std::time::Duration::from_millis(5));
+    }
+}
+
+#[test]
+fn persist_the_processed_instance_after_the_debounce() {
+    let app_state_path = fresh_app_state_path("debounced_persistence_tests_instance");
+    let app_config = AppConfigImpl::new(Some(app_state_path.to_string_lossy().to_string()));
+    let lifecycle = LifecycleImpl::new_instance(&app_config).unwrap();
+
+    MyLockedDomainModelCommand::AddItem("debounced item".to_string())
+        .process_with(&lifecycle)
+        .unwrap();
+    assert!(!app_state_path.exists());
+    wait_until(|| app_state_path.exists());
+    assert_eq!(
+        "#cqrs:json\n{\"my_locked_domain_model_lock\":{\"items\":[\"debounced item\"]}}",
+        std::fs::read_to_string(&app_state_path).unwrap()
+    );
+    assert!(!lifecycle.app_state.dirty_flag_value());
+}
+
+#[test]
+fn coalesce_quick_commands_into_one_write() {
+    let app_state_path = fresh_app_state_path("debounced_persistence_tests_coalesce");
+    let app_config = AppConfigImpl::new(Some(app_state_path.to_string_lossy().to_string()));
+    let lifecycle = LifecycleImpl::new_instance(&app_config).unwrap();
+
+    for item in ["first", "second", "third"] {
+        MyLockedDomainModelCommand::AddItem(item.to_string())
+            .process_with(&lifecycle)
+            .unwrap();
+    }
+    // not written per command
+    assert!(!app_state_path.exists());
+    wait_until(|| app_state_path.exists());
+    assert_eq!(
+        "#cqrs:json\n{\"my_locked_domain_model_lock\":{\"items\":[\"first\",\"second\",\"third\"]}}",
+        std::fs::read_to_string(&app_state_path).unwrap()
+    );
+}
+
+#[test]
+fn return_background_errors_on_flush() {
+    let app_state_path = fresh_app_state_path("debounced_persistence_tests_flush_error");
+    let app_config = AppConfigImpl::new(Some(app_state_path.to_string_lossy().to_string()));
+    let lifecycle = LifecycleImpl::new_instance(&app_config).unwrap();
+    // the app state's directory can't be created anymore
+    let app_state_dir = app_state_path.parent().unwrap();
+    std::fs::remove_dir_all(app_state_dir).unwrap();
+    std::fs::write(app_state_dir, "blocking file").unwrap();
+
+    MyLockedDomainModelCommand::AddItem("lost item".to_string())
+        .process_with(&lifecycle)
+        .unwrap();
+    // the worker took the pending write, flush() joins it and returns its error
+    wait_until(|| format!("{:?}", lifecycle.deferred_persistence).contains("pending: false"));
+    assert!(matches!(
+        lifecycle.deferred_persistence.flush(),
+        Err(ProcessingError::NotPersisted { .. })
+    ));
+    std::fs::remove_file(app_state_dir).unwrap();
+}
+
+// the only test using the singleton, as it is shared by all tests
+#[test]
+fn flush_on_shutdown_and_pass_errors_to_the_handler() {
+    let app_state_path = fresh_app_state_path("debounced_persistence_tests_singleton");
+    LifecycleImpl::initialise(Some(app_state_path.to_string_lossy().to_string())).unwrap();
+
+    MyLockedDomainModelCommand::AddItem("flushed item".to_string())
+        .process()
+        .unwrap();
+    LifecycleImpl::shutdown().unwrap();
+    assert_eq!(
+        "#cqrs:json\n{\"my_locked_domain_model_lock\":{\"items\":[\"flushed item\"]}}",
+        std::fs::read_to_string(&app_state_path).unwrap()
+    );
+
+    let (error_sender, errors) = std::sync::mpsc::channel();
+    let error_sender = std::sync::Mutex::new(error_sender);
+    LifecycleImpl::on_deferred_persist_error(move |error| {
+        error_sender.lock().unwrap().send(error).unwrap();
+    });
+    let app_state_dir = app_state_path.parent().unwrap();
+    std::fs::remove_dir_all(app_state_dir).unwrap();
+    std::fs::write(app_state_dir, "blocking file").unwrap();
+    MyLockedDomainModelCommand::AddItem("retried item".to_string())
+        .process()
+        .unwrap();
+    assert!(matches!(
+        errors.recv_timeout(std::time::Duration::from_secs(10)),
+        Ok(ProcessingError::NotPersisted { .. })
+    ));
+    // the error was consumed by the handler, the still dirty state is persisted on shutdown
+    std::fs::remove_file(app_state_dir).unwrap();
+    LifecycleImpl::shutdown().unwrap();
+    assert_eq!(
+        "#cqrs:json\n{\"my_locked_domain_model_lock\":{\"items\":[\"flushed item\",\"retried item\"]}}",
+        std::fs::read_to_string(&app_state_path).unwrap()
+    );
+}
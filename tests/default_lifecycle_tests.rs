@@ -0,0 +1,156 @@
+mod cqrs_lock_model_file;
+
+use generate_cqrs_api_macro::generate_api;
+
+include!("./mocks/app_config_mock.rs");
+include!("./mocks/rust_auto_opaque_mock.rs");
+
+/// keeps the persisted app states in memory, by their url
+#[derive(Debug)]
+pub(crate) struct InMemoryPersister {
+    url: String,
+}
+
+static PERSISTED_APP_STATES: std::sync::Mutex<Vec<(String, String)>> =
+    std::sync::Mutex::new(Vec::new());
+
+/// the models passed to `persist_models()`, by url
+static PERSISTED_MODELS: std::sync::Mutex<Vec<(String, Vec<generate_cqrs_api::ModelId>)>> =
+    std::sync::Mutex::new(Vec::new());
+
+fn get_persisted_app_state(url: &str) -> Option<String> {
+    PERSISTED_APP_STATES
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|(persisted_url, _)| persisted_url == url)
+        .map(|(_, app_state)| app_state.to_owned())
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum InMemoryPersisterError {
+    #[error("No app state persisted yet")]
+    NotFound,
+    #[error("Can't (de)serialize the app state: {0}")]
+    Serde(#[from] serde_json::Error),
+}
+
+impl AppStatePersistError<ProcessingError> for InMemoryPersisterError {
+    fn to_processing_error(&self) -> ProcessingError {
+        ProcessingError::NotPersisted {
+            error: self.to_string(),
+            url: "memory".to_string(),
+        }
+    }
+    fn is_not_found(&self) -> bool {
+        matches!(self, InMemoryPersisterError::NotFound)
+    }
+}
+
+impl AppStatePersister for InMemoryPersister {
+    type Error = InMemoryPersisterError;
+    fn new<AC: AppConfig>(app_config: &AC) -> Result<Self, Self::Error> {
+        Ok(InMemoryPersister {
+            url: app_config.borrow_app_state_url().to_string(),
+        })
+    }
+    fn persist_app_state<AS: AppState + std::fmt::Debug>(
+        &self,
+        state: &AS,
+    ) -> Result<(), Self::Error> {
+        let serialized = serde_json::to_string(state)?;
+        let mut persisted_app_states = PERSISTED_APP_STATES.lock().unwrap();
+        persisted_app_states.retain(|(url, _)| url != &self.url);
+        persisted_app_states.push((self.url.to_owned(), serialized));
+        Ok(())
+    }
+    fn persist_models<AS: AppState + std::fmt::Debug>(
+        &self,
+        state: &AS,
+        models: &[generate_cqrs_api::ModelId],
+    ) -> Result<(), Self::Error> {
+        PERSISTED_MODELS
+            .lock()
+            .unwrap()
+            .push((self.url.to_owned(), models.to_vec()));
+        self.persist_app_state(state)
+    }
+    fn load_app_state<AC: AppConfig, AS: AppState>(&self) -> Result<AS, Self::Error> {
+        match get_persisted_app_state(&self.url) {
+            Some(persisted) => Ok(serde_json::from_str(&persisted)?),
+            None => Err(InMemoryPersisterError::NotFound),
+        }
+    }
+}
+
+pub struct LifecycleImpl {
+    app_state: AppStateImpl,
+    persister: InMemoryPersister,
+}
+
+// all Lifecycle functions are generated
+#[generate_api(
+    "tests/cqrs_lock_model_file/mod.rs",
+    generate_app_state,
+    default_lifecycle(app_config = AppConfigImpl, persister = InMemoryPersister)
+)]
+impl Lifecycle for LifecycleImpl {
+    type Error = InMemoryPersisterError;
+}
+
+#[test]
+fn default_lifecycle_persists_commands() {
+    LifecycleImpl::initialise(None).unwrap();
+    assert!(!LifecycleImpl::get_singleton().app_state.dirty_flag_value());
+
+    MyLockedDomainModelCommand::AddItem("first item".to_string())
+        .process()
+        .unwrap();
+    assert_eq!(
+        Some(r#"{"my_locked_domain_model_lock":{"items":["first item"]}}"#.to_string()),
+        get_persisted_app_state(AppConfigImpl::new(None).borrow_app_state_url())
+    );
+    assert!(!LifecycleImpl::get_singleton().app_state.dirty_flag_value());
+    LifecycleImpl::shutdown().unwrap();
+}
+
+#[test]
+fn isolated_lifecycle_instances() {
+    let profile_a =
+        LifecycleImpl::new_instance(&AppConfigImpl::new(Some("profile_a".to_string()))).unwrap();
+    let profile_b =
+        LifecycleImpl::new_instance(&AppConfigImpl::new(Some("profile_b".to_string()))).unwrap();
+
+    MyLockedDomainModelCommand::AddItem("only in a".to_string())
+        .process_with(&profile_a)
+        .unwrap();
+
+    let items = |lifecycle: &LifecycleImpl| {
+        lifecycle
+            .app_state
+            .my_locked_domain_model_lock
+            .lock
+            .blocking_read()
+            .get_items()
+    };
+    assert_eq!(vec!["only in a".to_string()], items(&profile_a));
+    assert!(items(&profile_b).is_empty());
+    // reloading the persisted profile
+    let profile_a_reloaded =
+        LifecycleImpl::new_instance(&AppConfigImpl::new(Some("profile_a".to_string()))).unwrap();
+    assert_eq!(vec!["only in a".to_string()], items(&profile_a_reloaded));
+}
+
+#[test]
+fn persist_changed_models_only() {
+    let app_config = AppConfigImpl::new(Some("changed_models".to_string()));
+    let lifecycle = LifecycleImpl::new_instance(&app_config).unwrap();
+    MyLockedDomainModelCommand::AddItem("changed".to_string())
+        .process_with(&lifecycle)
+        .unwrap();
+    assert!(PERSISTED_MODELS.lock().unwrap().contains(&(
+        app_config.borrow_app_state_url().to_string(),
+        vec![generate_cqrs_api::ModelId("my_locked_domain_model_lock")]
+    )));
+    assert!(lifecycle.app_state.dirty_models().is_empty());
+}
@@ -0,0 +1,49 @@
+mod cqrs_lock_model_file;
+
+use generate_cqrs_api::{FileAppStatePersister, FileAppStatePersisterError};
+use generate_cqrs_api_macro::generate_api;
+
+include!("./mocks/app_config_mock.rs");
+include!("./mocks/rust_auto_opaque_mock.rs");
+include!("./mocks/app_state_path_mock.rs");
+
+pub struct LifecycleImpl {
+    app_state: AppStateImpl,
+    persister: FileAppStatePersister,
+}
+
+#[generate_api(
+    "tests/cqrs_lock_model_file/mod.rs",
+    generate_app_state,
+    default_lifecycle(app_config = AppConfigImpl, persister = FileAppStatePersister)
+)]
+impl Lifecycle for LifecycleImpl {
+    type Error = FileAppStatePersisterError;
+}
+
+#[test]
+fn file_persister_creates_and_reloads_app_state() {
+    let app_state_path = fresh_app_state_path("file_persister_tests");
+    let app_config = AppConfigImpl::new(Some(app_state_path.to_string_lossy().to_string()));
+
+    // no file yet => a new app state is created
+    let lifecycle = LifecycleImpl::new_instance(&app_config).unwrap();
+    MyLockedDomainModelCommand::AddItem("persisted item".to_string())
+        .process_with(&lifecycle)
+        .unwrap();
+    assert_eq!(
+        "#cqrs:json\n{\"my_locked_domain_model_lock\":{\"items\":[\"persisted item\"]}}",
+        std::fs::read_to_string(&app_state_path).unwrap()
+    );
+
+    let reloaded = LifecycleImpl::new_instance(&app_config).unwrap();
+    assert_eq!(
+        vec!["persisted item".to_string()],
+        reloaded
+            .app_state
+            .my_locked_domain_model_lock
+            .lock
+            .blocking_read()
+            .get_items()
+    );
+}
@@ -0,0 +1,97 @@
+mod good_source_file;
+mod second_model_file;
+
+use generate_cqrs_api::{FileAppStatePersister, FileAppStatePersisterError, ModelId};
+use generate_cqrs_api_macro::generate_api;
+
+include!("./mocks/app_config_mock.rs");
+include!("./mocks/rust_auto_opaque_mock.rs");
+include!("./mocks/app_state_path_mock.rs");
+
+pub struct LifecycleImpl {
+    app_state: AppStateImpl,
+    persister: FileAppStatePersister,
+}
+
+// AppStateImpl is generated
+#[generate_api(
+    "tests/good_source_file/mod.rs",
+    "tests/second_model_file/mod.rs",
+    generate_app_state,
+    persistence = "manual",
+    default_lifecycle(app_config = AppConfigImpl, persister = FileAppStatePersister)
+)]
+impl Lifecycle for LifecycleImpl {
+    type Error = FileAppStatePersisterError;
+}
+
+#[test]
+fn track_and_persist_the_changed_models() {
+    let app_state_path = fresh_app_state_path("generated_app_state_tests");
+    let app_config = AppConfigImpl::new(Some(app_state_path.to_string_lossy().to_string()));
+    let lifecycle = LifecycleImpl::new_instance(&app_config).unwrap();
+    assert!(!lifecycle.app_state.dirty_flag_value());
+
+    MySecondDomainModelCommand::AddSecondItem("second".to_string())
+        .process_with(&lifecycle)
+        .unwrap();
+    MyGoodDomainModelCommand::AddItem("todo".to_string())
+        .process_with(&lifecycle)
+        .unwrap();
+    assert!(lifecycle.app_state.dirty_flag_value());
+    assert_eq!(
+        vec![
+            ModelId("my_second_domain_model_lock"),
+            ModelId("my_good_domain_model_lock")
+        ],
+        lifecycle.app_state.dirty_models()
+    );
+
+    lifecycle.persist_instance().unwrap();
+    assert!(!lifecycle.app_state.dirty_flag_value());
+    assert!(lifecycle.app_state.dirty_models().is_empty());
+    let reloaded = LifecycleImpl::new_instance(&app_config).unwrap();
+    assert_eq!(
+        vec!["todo".to_string()],
+        reloaded
+            .app_state
+            .my_good_domain_model_lock
+            .lock
+            .blocking_read()
+            .get_items_as_string()
+    );
+    assert_eq!(
+        vec!["second".to_string()],
+        reloaded
+            .app_state
+            .my_second_domain_model_lock
+            .lock
+            .blocking_read()
+            .get_items()
+    );
+}
+
+#[test]
+fn keep_the_models_dirty_if_persisting_fails() {
+    let app_state_path = fresh_app_state_path("generated_app_state_tests_failing");
+    let app_config = AppConfigImpl::new(Some(app_state_path.to_string_lossy().to_string()));
+    let lifecycle = LifecycleImpl::new_instance(&app_config).unwrap();
+    // the app state's directory can't be created anymore
+    let app_state_dir = app_state_path.parent().unwrap();
+    std::fs::remove_dir_all(app_state_dir).unwrap();
+    std::fs::write(app_state_dir, "blocking file").unwrap();
+
+    MyGoodDomainModelCommand::AddItem("todo".to_string())
+        .process_with(&lifecycle)
+        .unwrap();
+    assert!(lifecycle.persist_instance().is_err());
+    assert!(lifecycle.app_state.dirty_flag_value());
+    assert_eq!(
+        vec![ModelId("my_good_domain_model_lock")],
+        lifecycle.app_state.dirty_models()
+    );
+
+    std::fs::remove_file(app_state_dir).unwrap();
+    lifecycle.persist_instance().unwrap();
+    assert!(lifecycle.app_state.dirty_models().is_empty());
+}
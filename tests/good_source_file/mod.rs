@@ -1,4 +1,5 @@
 use crate::*;
+use generate_cqrs_api_macro::cqrs;
 
 #[derive(Clone, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct MyGoodDomainModel {
@@ -36,8 +37,8 @@ impl<'de> Deserialize<'de> for MyGoodDomainModelLock {
 }
 
 impl CqrsModelLock<MyGoodDomainModel> for MyGoodDomainModelLock {
-    fn for_model(_model: MyGoodDomainModel) -> Self {
-        todo!()
+    fn for_model(model: MyGoodDomainModel) -> Self {
+        model.into()
     }
 }
 
@@ -86,9 +87,11 @@ impl MyGoodDomainModelLock {
             vec![MyGoodDomainModelEffect::RenderItems(self.clone())],
         ))
     }
+    /// @param todo_pos: the item's position, starting with 1
+    #[cqrs]
     pub(crate) fn remove_item(
         &self,
-        todo_pos: usize,
+        #[validate(range(min = 1))] todo_pos: usize,
     ) -> Result<(bool, Vec<MyGoodDomainModelEffect>), MyGoodProcessingError> {
         let items = &mut self.lock.blocking_write().items;
         if todo_pos > items.len() {
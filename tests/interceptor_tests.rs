@@ -0,0 +1,98 @@
+mod cqrs_lock_model_file;
+
+use std::sync::{Arc, Mutex};
+
+use generate_cqrs_api::{
+    FileAppStatePersister, FileAppStatePersisterError, InterceptedCall, Interceptor, Interceptors,
+};
+use generate_cqrs_api_macro::generate_api;
+
+include!("./mocks/app_config_mock.rs");
+include!("./mocks/rust_auto_opaque_mock.rs");
+include!("./mocks/app_state_path_mock.rs");
+
+pub struct LifecycleImpl {
+    app_state: AppStateImpl,
+    persister: FileAppStatePersister,
+    interceptors: Interceptors<Effect, ProcessingError>,
+}
+
+#[generate_api(
+    "tests/cqrs_lock_model_file/mod.rs",
+    generate_app_state,
+    serializable_cqrs,
+    interceptors,
+    default_lifecycle(app_config = AppConfigImpl, persister = FileAppStatePersister)
+)]
+impl Lifecycle for LifecycleImpl {
+    type Error = FileAppStatePersisterError;
+}
+
+/// records every call and rejects adding empty items
+struct AuditInterceptor(Arc<Mutex<Vec<String>>>);
+
+impl Interceptor<Effect, ProcessingError> for AuditInterceptor {
+    fn before(&self, call: &InterceptedCall) -> Result<(), ProcessingError> {
+        if call.variant == "AddItem" && call.arguments == "" {
+            return Err(ProcessingError::MyLockedDomainProcessingError(
+                MyLockedDomainProcessingError::ItemDoesNotExist,
+            ));
+        }
+        Ok(())
+    }
+    fn after(&self, call: &InterceptedCall, result: Result<&[Effect], &ProcessingError>) {
+        self.0.lock().unwrap().push(format!(
+            "{}::{}({}) -> {} effect(s)",
+            call.model,
+            call.variant,
+            call.arguments,
+            result.map_or(0, <[Effect]>::len)
+        ));
+    }
+}
+
+#[test]
+fn intercept_commands_and_queries() {
+    let app_state_path = fresh_app_state_path("interceptor_tests");
+    let lifecycle = LifecycleImpl::new_instance(&AppConfigImpl::new(Some(
+        app_state_path.to_string_lossy().to_string(),
+    )))
+    .unwrap();
+    let audit_log = Arc::new(Mutex::new(Vec::new()));
+    lifecycle
+        .interceptors
+        .register(AuditInterceptor(audit_log.clone()));
+
+    MyLockedDomainModelCommand::AddItem("item".to_string())
+        .process_with(&lifecycle)
+        .unwrap();
+    // the any-enums delegate to the intercepted calls
+    AnyQuery::from(MyLockedDomainModelQuery::GetAllItems)
+        .process_with(&lifecycle)
+        .unwrap();
+    let rejected = MyLockedDomainModelCommand::AddItem(String::new()).process_with(&lifecycle);
+    assert!(matches!(
+        rejected,
+        Err(ProcessingError::MyLockedDomainProcessingError(
+            MyLockedDomainProcessingError::ItemDoesNotExist
+        ))
+    ));
+
+    assert_eq!(
+        vec![
+            r#"MyLockedDomainModel::AddItem("item") -> 1 effect(s)"#,
+            "MyLockedDomainModel::GetAllItems(null) -> 1 effect(s)",
+        ],
+        *audit_log.lock().unwrap()
+    );
+    // the rejected command wasn't processed
+    assert_eq!(
+        vec!["item"],
+        lifecycle
+            .app_state
+            .my_locked_domain_model_lock
+            .lock
+            .blocking_read()
+            .get_items()
+    );
+}
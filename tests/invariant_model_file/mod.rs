@@ -0,0 +1,60 @@
+use crate::*;
+use generate_cqrs_api_macro::{cqrs_lock, cqrs_model};
+
+pub const MAX_ITEMS: usize = 2;
+
+#[derive(Clone, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct MyLimitedModel {
+    items: Vec<String>,
+}
+
+#[cqrs_lock(model = MyLimitedModel)]
+#[derive(Debug, Clone, Default)]
+pub struct MyLimitedModelLock;
+
+#[allow(dead_code)]
+pub enum MyLimitedModelEffect {
+    RenderItems(MyLimitedModelLock),
+}
+
+#[allow(dead_code)]
+impl MyLimitedModel {
+    pub fn get_items(&self) -> Vec<String> {
+        self.items.clone()
+    }
+}
+
+#[allow(dead_code)]
+impl MyLimitedModelLock {
+    /// doesn't check the limit itself, so the invariant has to catch it
+    pub(crate) fn add_item(
+        &self,
+        item: String,
+    ) -> Result<(bool, Vec<MyLimitedModelEffect>), MyLimitedProcessingError> {
+        self.lock.blocking_write().items.push(item);
+        Ok((true, vec![MyLimitedModelEffect::RenderItems(self.clone())]))
+    }
+    pub(crate) fn get_all_items(
+        &self,
+    ) -> Result<Vec<MyLimitedModelEffect>, MyLimitedProcessingError> {
+        Ok(vec![MyLimitedModelEffect::RenderItems(self.clone())])
+    }
+}
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum MyLimitedProcessingError {
+    #[error("The item does not exist!")]
+    ItemDoesNotExist,
+}
+
+#[cqrs_model]
+impl CqrsModel for MyLimitedModel {
+    fn check_invariants(&self) -> Result<(), String> {
+        if self.items.len() > MAX_ITEMS {
+            // gives concurrent calls the chance to see the violating model, if they weren't kept waiting
+            std::thread::sleep(std::time::Duration::from_millis(5));
+            return Err(format!("at most {MAX_ITEMS} items"));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,104 @@
+mod invariant_model_file;
+
+use generate_cqrs_api::{FileAppStatePersister, FileAppStatePersisterError};
+use generate_cqrs_api_macro::generate_api;
+
+include!("./mocks/app_config_mock.rs");
+include!("./mocks/rust_auto_opaque_mock.rs");
+include!("./mocks/app_state_path_mock.rs");
+
+pub struct LifecycleImpl {
+    app_state: AppStateImpl,
+    persister: FileAppStatePersister,
+}
+
+#[generate_api(
+    "tests/invariant_model_file/mod.rs",
+    generate_app_state,
+    preview,
+    default_lifecycle(app_config = AppConfigImpl, persister = FileAppStatePersister)
+)]
+impl Lifecycle for LifecycleImpl {
+    type Error = FileAppStatePersisterError;
+}
+
+fn get_items(lifecycle: &LifecycleImpl) -> Vec<String> {
+    lifecycle
+        .app_state
+        .my_limited_model_lock
+        .lock
+        .blocking_read()
+        .get_items()
+}
+
+#[test]
+fn roll_back_command_violating_an_invariant() {
+    let app_state_path = fresh_app_state_path("invariant_tests");
+    let lifecycle = LifecycleImpl::new_instance(&AppConfigImpl::new(Some(
+        app_state_path.to_string_lossy().to_string(),
+    )))
+    .unwrap();
+    for item in ["first item", "second item"] {
+        MyLimitedModelCommand::AddItem(item.to_string())
+            .process_with(&lifecycle)
+            .unwrap();
+    }
+    let persisted = std::fs::read_to_string(&app_state_path).unwrap();
+
+    let preview = MyLimitedModelCommand::AddItem("third item".to_string()).preview_with(&lifecycle);
+    assert!(matches!(
+        preview,
+        Err(ProcessingError::InvariantViolated { model, rule })
+            if model == "MyLimitedModel" && rule == "at most 2 items"
+    ));
+    let Err(error) =
+        MyLimitedModelCommand::AddItem("third item".to_string()).process_with(&lifecycle)
+    else {
+        panic!("the command should violate the invariant");
+    };
+    assert_eq!(
+        "The command violated the invariant 'at most 2 items' of MyLimitedModel and was rolled back",
+        error.to_string()
+    );
+    assert_eq!(vec!["first item", "second item"], get_items(&lifecycle));
+    assert_eq!(persisted, std::fs::read_to_string(&app_state_path).unwrap());
+}
+
+#[test]
+fn check_concurrent_commands_one_by_one() {
+    let app_state_path = fresh_app_state_path("invariant_tests_concurrent");
+    let lifecycle = LifecycleImpl::new_instance(&AppConfigImpl::new(Some(
+        app_state_path.to_string_lossy().to_string(),
+    )))
+    .unwrap();
+
+    let results = std::thread::scope(|scope| {
+        let threads = (0..8)
+            .map(|thread| {
+                let lifecycle = &lifecycle;
+                scope.spawn(move || {
+                    MyLimitedModelCommand::AddItem(format!("item {thread}")).process_with(lifecycle)
+                })
+            })
+            .collect::<Vec<_>>();
+        threads
+            .into_iter()
+            .map(|thread| thread.join().unwrap())
+            .collect::<Vec<_>>()
+    });
+    // every command saw the model of the previous one, never one rolled back meanwhile
+    for result in &results {
+        if let Err(error) = result {
+            assert!(
+                matches!(error, ProcessingError::InvariantViolated { .. }),
+                "{error:?}"
+            );
+        }
+    }
+    let rolled_back = results
+        .iter()
+        .filter(|result| matches!(result, Err(ProcessingError::InvariantViolated { .. })))
+        .count();
+    assert_eq!(6, rolled_back);
+    assert_eq!(2, get_items(&lifecycle).len());
+}
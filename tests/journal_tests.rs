@@ -0,0 +1,161 @@
+mod cqrs_lock_model_file;
+
+use std::io::Write;
+
+use generate_cqrs_api::{
+    CommandJournal, CommandJournalError, FileAppStatePersister, FileAppStatePersisterError,
+};
+use generate_cqrs_api_macro::generate_api;
+
+include!("./mocks/app_config_mock.rs");
+include!("./mocks/rust_auto_opaque_mock.rs");
+include!("./mocks/app_state_path_mock.rs");
+
+pub struct LifecycleImpl {
+    app_state: AppStateImpl,
+    persister: FileAppStatePersister,
+    journal: CommandJournal,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum LifecycleError {
+    #[error(transparent)]
+    Persister(#[from] FileAppStatePersisterError),
+    #[error(transparent)]
+    Journal(#[from] CommandJournalError),
+}
+
+impl AppStatePersistError<ProcessingError> for LifecycleError {
+    fn to_processing_error(&self) -> ProcessingError {
+        match self {
+            LifecycleError::Persister(error) => {
+                AppStatePersistError::<ProcessingError>::to_processing_error(error)
+            }
+            LifecycleError::Journal(error) => {
+                AppStatePersistError::<ProcessingError>::to_processing_error(error)
+            }
+        }
+    }
+    fn is_not_found(&self) -> bool {
+        matches!(self, LifecycleError::Persister(error)
+            if AppStatePersistError::<ProcessingError>::is_not_found(error))
+    }
+}
+
+#[generate_api(
+    "tests/cqrs_lock_model_file/mod.rs",
+    generate_app_state,
+    serializable_cqrs,
+    persistence = "journal",
+    snapshot_every = 3,
+    default_lifecycle(app_config = AppConfigImpl, persister = FileAppStatePersister)
+)]
+impl Lifecycle for LifecycleImpl {
+    type Error = LifecycleError;
+}
+
+fn get_items(lifecycle: &LifecycleImpl) -> Vec<String> {
+    lifecycle
+        .app_state
+        .my_locked_domain_model_lock
+        .lock
+        .blocking_read()
+        .get_items()
+}
+
+fn add_item(lifecycle: &LifecycleImpl, item: &str) {
+    MyLockedDomainModelCommand::AddItem(item.to_string())
+        .process_with(lifecycle)
+        .unwrap();
+}
+
+#[test]
+fn replay_journal_since_snapshot() {
+    let app_state_path = fresh_app_state_path("journal_tests");
+    let app_config = AppConfigImpl::new(Some(app_state_path.to_string_lossy().to_string()));
+    let journal_path = format!("{}.journal", app_config.borrow_app_state_url());
+
+    let lifecycle = LifecycleImpl::new_instance(&app_config).unwrap();
+    add_item(&lifecycle, "first item");
+    add_item(&lifecycle, "second item");
+    // journaled only, no snapshot yet
+    assert!(!app_state_path.exists());
+    assert_eq!(
+        2,
+        std::fs::read_to_string(&journal_path)
+            .unwrap()
+            .lines()
+            .count()
+    );
+    drop(lifecycle);
+
+    let restarted = LifecycleImpl::new_instance(&app_config).unwrap();
+    assert_eq!(vec!["first item", "second item"], get_items(&restarted));
+    // the third command persists a snapshot and compacts the journal
+    add_item(&restarted, "third item");
+    assert_eq!(
+        r#"{"my_locked_domain_model_lock":{"items":["first item","second item","third item"]},"journal_position":3}"#,
+        std::fs::read_to_string(&app_state_path)
+            .unwrap()
+            .trim_start_matches("#cqrs:json\n")
+    );
+    assert_eq!("", std::fs::read_to_string(&journal_path).unwrap());
+    add_item(&restarted, "fourth item");
+    drop(restarted);
+
+    // a crash while appending a command
+    std::fs::OpenOptions::new()
+        .append(true)
+        .open(&journal_path)
+        .unwrap()
+        .write_all(br#"5 0000 {"MyLockedDomain"#)
+        .unwrap();
+    let restarted = LifecycleImpl::new_instance(&app_config).unwrap();
+    assert_eq!(
+        vec!["first item", "second item", "third item", "fourth item"],
+        get_items(&restarted)
+    );
+    assert!(std::fs::read_to_string(&journal_path)
+        .unwrap()
+        .starts_with(r#"4 "#));
+    assert_eq!(
+        1,
+        std::fs::read_to_string(&journal_path)
+            .unwrap()
+            .lines()
+            .count()
+    );
+}
+
+#[test]
+fn snapshot_concurrently_with_commands() {
+    let app_state_path = fresh_app_state_path("journal_tests_concurrent");
+    let app_config = AppConfigImpl::new(Some(app_state_path.to_string_lossy().to_string()));
+
+    let lifecycle = LifecycleImpl::new_instance(&app_config).unwrap();
+    std::thread::scope(|scope| {
+        for thread in 0..4 {
+            let lifecycle = &lifecycle;
+            scope.spawn(move || {
+                for item in 0..25 {
+                    add_item(lifecycle, &format!("item {thread}-{item}"));
+                }
+            });
+        }
+        scope.spawn(|| {
+            for _ in 0..50 {
+                lifecycle.persist_instance().unwrap();
+            }
+        });
+    });
+    let mut expected_items = get_items(&lifecycle);
+    drop(lifecycle);
+
+    // every command is either in the snapshot or replayed from the journal - exactly once
+    let restarted = LifecycleImpl::new_instance(&app_config).unwrap();
+    let mut items = get_items(&restarted);
+    items.sort();
+    expected_items.sort();
+    assert_eq!(100, items.len());
+    assert_eq!(expected_items, items);
+}
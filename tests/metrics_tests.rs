@@ -0,0 +1,83 @@
+mod cqrs_lock_model_file;
+
+use generate_cqrs_api::{
+    FileAppStatePersister, FileAppStatePersisterError, InterceptedCall, Interceptor, Interceptors,
+    MetricsRegistry,
+};
+use generate_cqrs_api_macro::generate_api;
+
+include!("./mocks/app_config_mock.rs");
+include!("./mocks/rust_auto_opaque_mock.rs");
+include!("./mocks/app_state_path_mock.rs");
+
+pub struct LifecycleImpl {
+    app_state: AppStateImpl,
+    persister: FileAppStatePersister,
+    interceptors: Interceptors<Effect, ProcessingError>,
+    metrics: MetricsRegistry,
+}
+
+#[generate_api(
+    "tests/cqrs_lock_model_file/mod.rs",
+    generate_app_state,
+    serializable_cqrs,
+    interceptors,
+    metrics,
+    default_lifecycle(app_config = AppConfigImpl, persister = FileAppStatePersister)
+)]
+impl Lifecycle for LifecycleImpl {
+    type Error = FileAppStatePersisterError;
+}
+
+struct RejectEmptyItems;
+
+impl Interceptor<Effect, ProcessingError> for RejectEmptyItems {
+    fn before(&self, call: &InterceptedCall) -> Result<(), ProcessingError> {
+        if call.arguments == "" {
+            return Err(ProcessingError::MyLockedDomainProcessingError(
+                MyLockedDomainProcessingError::ItemDoesNotExist,
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn record_calls_and_persistence() {
+    let app_state_path = fresh_app_state_path("metrics_tests");
+    let lifecycle = LifecycleImpl::new_instance(&AppConfigImpl::new(Some(
+        app_state_path.to_string_lossy().to_string(),
+    )))
+    .unwrap();
+    lifecycle.interceptors.register(RejectEmptyItems);
+
+    for item in ["first item", "second item", ""] {
+        let _ = MyLockedDomainModelCommand::AddItem(item.to_string()).process_with(&lifecycle);
+    }
+    MyLockedDomainModelQuery::GetAllItems
+        .process_with(&lifecycle)
+        .unwrap();
+
+    let snapshot = lifecycle.metrics.snapshot();
+    let calls = snapshot
+        .calls
+        .iter()
+        .map(|call| {
+            (
+                call.variant.as_str(),
+                call.count,
+                call.error_count,
+                call.latency.count(),
+            )
+        })
+        .collect::<Vec<(&str, u64, u64, u64)>>();
+    // the rejected call counts as error
+    assert_eq!(vec![("AddItem", 3, 1, 3), ("GetAllItems", 1, 0, 1)], calls);
+    assert!(snapshot
+        .calls
+        .iter()
+        .all(|call| call.model == "MyLockedDomainModel"));
+    // only the accepted commands changed the state
+    assert_eq!(2, snapshot.persistence.count);
+    assert_eq!(0, snapshot.persistence.error_count);
+}
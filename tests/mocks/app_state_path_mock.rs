@@ -0,0 +1,31 @@
+/// the path of an app state file in a temp directory of its own, which is removed when dropped
+pub struct FreshAppStatePath(std::path::PathBuf);
+
+/// @param name: unique among the tests, as they run in parallel
+/// @returns the path of `app_state.json` in an empty temp directory
+pub fn fresh_app_state_path(name: &str) -> FreshAppStatePath {
+    let app_state_path = std::env::temp_dir()
+        .join(format!("{name}_{}", std::process::id()))
+        .join("app_state.json");
+    let _ = std::fs::remove_dir_all(app_state_path.parent().unwrap());
+    FreshAppStatePath(app_state_path)
+}
+
+impl std::ops::Deref for FreshAppStatePath {
+    type Target = std::path::Path;
+    fn deref(&self) -> &std::path::Path {
+        &self.0
+    }
+}
+
+impl AsRef<std::path::Path> for FreshAppStatePath {
+    fn as_ref(&self) -> &std::path::Path {
+        &self.0
+    }
+}
+
+impl Drop for FreshAppStatePath {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(self.0.parent().unwrap());
+    }
+}
@@ -14,7 +14,7 @@ pub enum AppStatePersisterErrorMock {
     FileNotFound(String),
 }
 
-impl AppStatePersistError for AppStatePersisterErrorMock {
+impl AppStatePersistError<ProcessingError> for AppStatePersisterErrorMock {
     fn to_processing_error(&self) -> ProcessingError {
         match self {
             AppStatePersisterErrorMock::FileNotFound(path) => ProcessingError::NotPersisted {
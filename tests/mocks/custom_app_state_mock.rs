@@ -0,0 +1,29 @@
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub(crate) struct MyAppState {
+    todos: MyGoodDomainModelLock,
+    seconds: MySecondDomainModelLock,
+    #[serde(skip)]
+    dirty_flag: generate_cqrs_api::DirtyFlag,
+}
+
+impl AppState for MyAppState {
+    fn new<A: AppConfig>(_app_config: &A) -> Self {
+        Self {
+            todos: MyGoodDomainModel::default().into(),
+            seconds: MySecondDomainModel::default().into(),
+            dirty_flag: generate_cqrs_api::DirtyFlag::default(),
+        }
+    }
+    fn dirty_flag_value(&self) -> bool {
+        self.dirty_flag.is_dirty()
+    }
+    fn mark_dirty(&self) {
+        self.dirty_flag.mark_dirty();
+    }
+    fn mark_persisted(&self) {
+        self.dirty_flag.mark_persisted();
+    }
+    fn lock_persisting(&self) -> Option<std::sync::MutexGuard<'_, ()>> {
+        Some(self.dirty_flag.lock_persisting())
+    }
+}
@@ -1,19 +1,19 @@
+/// like flutter_rust_bridge's RustAutoOpaque, which is in essence an Arc<RwLock<T>>
 #[derive(Default, Clone, Debug)]
 pub(crate) struct RustAutoOpaque<M> {
-    pub model: M,
+    model: std::sync::Arc<std::sync::RwLock<M>>,
 }
 
-impl<M: CqrsModel> RustAutoOpaque<M> {
-    fn new(model: M) -> Self {
-        RustAutoOpaque { model }
+impl<M> RustAutoOpaque<M> {
+    pub(crate) fn new(model: M) -> Self {
+        RustAutoOpaque {
+            model: std::sync::Arc::new(std::sync::RwLock::new(model)),
+        }
     }
-}
-
-impl<T: std::clone::Clone> RustAutoOpaque<T> {
-    pub(crate) fn blocking_write(&self) -> T {
-        self.clone().model
+    pub(crate) fn blocking_write(&self) -> std::sync::RwLockWriteGuard<'_, M> {
+        self.model.write().expect("lock poisoned")
     }
-    pub(crate) fn blocking_read(&self) -> T {
-        self.clone().model
+    pub(crate) fn blocking_read(&self) -> std::sync::RwLockReadGuard<'_, M> {
+        self.model.read().expect("lock poisoned")
     }
 }
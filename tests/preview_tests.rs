@@ -0,0 +1,51 @@
+mod cqrs_lock_model_file;
+
+use generate_cqrs_api::{FileAppStatePersister, FileAppStatePersisterError};
+use generate_cqrs_api_macro::generate_api;
+
+include!("./mocks/app_config_mock.rs");
+include!("./mocks/rust_auto_opaque_mock.rs");
+include!("./mocks/app_state_path_mock.rs");
+
+pub struct LifecycleImpl {
+    app_state: AppStateImpl,
+    persister: FileAppStatePersister,
+}
+
+#[generate_api(
+    "tests/cqrs_lock_model_file/mod.rs",
+    generate_app_state,
+    preview,
+    default_lifecycle(app_config = AppConfigImpl, persister = FileAppStatePersister)
+)]
+impl Lifecycle for LifecycleImpl {
+    type Error = FileAppStatePersisterError;
+}
+
+#[test]
+fn preview_command_without_changing_the_state() {
+    let app_state_path = fresh_app_state_path("preview_tests");
+    let lifecycle = LifecycleImpl::new_instance(&AppConfigImpl::new(Some(
+        app_state_path.to_string_lossy().to_string(),
+    )))
+    .unwrap();
+
+    let (would_change_state, effects) = MyLockedDomainModelCommand::AddItem("item".to_string())
+        .preview_with(&lifecycle)
+        .unwrap();
+    assert!(would_change_state);
+    assert!(matches!(
+        effects.as_slice(),
+        [Effect::MyLockedDomainModelRenderItems(lock)]
+            if lock.lock.blocking_read().get_items() == vec!["item"]
+    ));
+    let app_state = &lifecycle.app_state;
+    assert!(app_state
+        .my_locked_domain_model_lock
+        .lock
+        .blocking_read()
+        .get_items()
+        .is_empty());
+    assert!(!app_state.dirty_flag_value());
+    assert!(!app_state_path.exists());
+}
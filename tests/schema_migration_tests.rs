@@ -0,0 +1,48 @@
+use generate_cqrs_api::{serde_json::Value, CqrsModel, CqrsModelLock};
+use generate_cqrs_api_macro::{cqrs_lock, cqrs_model};
+
+include!("./mocks/rust_auto_opaque_mock.rs");
+
+/// version 1 was `{"items": ["text"]}`
+#[derive(Clone, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct MyVersionedModel {
+    todos: Vec<String>,
+}
+
+#[cqrs_model(version = 2, migrations(migrate_v1_to_v2))]
+impl CqrsModel for MyVersionedModel {}
+
+fn migrate_v1_to_v2(mut model: Value) -> Value {
+    let items = model["items"].take();
+    generate_cqrs_api::serde_json::json!({ "todos": items })
+}
+
+#[cqrs_lock(model = MyVersionedModel)]
+#[derive(Debug, Clone, Default)]
+pub struct MyVersionedModelLock;
+
+#[test]
+fn migrate_persisted_model() {
+    let lock: MyVersionedModelLock = serde_json::from_str(r#"{"items":["first item"]}"#).unwrap();
+    assert_eq!(
+        vec!["first item".to_string()],
+        lock.lock.blocking_read().todos
+    );
+    assert_eq!(
+        r##"{"#schema_version":2,"model":{"todos":["first item"]}}"##,
+        serde_json::to_string(&lock).unwrap()
+    );
+}
+
+#[test]
+fn load_current_schema_version() {
+    let lock: MyVersionedModelLock =
+        serde_json::from_str(r##"{"#schema_version":2,"model":{"todos":["first item"]}}"##)
+            .unwrap();
+    assert_eq!(
+        MyVersionedModel {
+            todos: vec!["first item".to_string()]
+        },
+        MyVersionedModel::from(lock)
+    );
+}
@@ -36,8 +36,8 @@ impl<'de> Deserialize<'de> for MySecondDomainModelLock {
 }
 
 impl CqrsModelLock<MySecondDomainModel> for MySecondDomainModelLock {
-    fn for_model(_model: MySecondDomainModel) -> Self {
-        todo!()
+    fn for_model(model: MySecondDomainModel) -> Self {
+        model.into()
     }
 }
 
@@ -0,0 +1,62 @@
+mod cqrs_lock_model_file;
+
+use generate_cqrs_api::{FileAppStatePersister, FileAppStatePersisterError};
+use generate_cqrs_api_macro::generate_api;
+
+include!("./mocks/app_config_mock.rs");
+include!("./mocks/rust_auto_opaque_mock.rs");
+include!("./mocks/app_state_path_mock.rs");
+
+pub struct LifecycleImpl {
+    app_state: AppStateImpl,
+    persister: FileAppStatePersister,
+}
+
+#[generate_api(
+    "tests/cqrs_lock_model_file/mod.rs",
+    generate_app_state,
+    serializable_cqrs,
+    default_lifecycle(app_config = AppConfigImpl, persister = FileAppStatePersister)
+)]
+impl Lifecycle for LifecycleImpl {
+    type Error = FileAppStatePersisterError;
+}
+
+#[test]
+fn serialize_any_command_with_model_name() {
+    let command = AnyCommand::from(MyLockedDomainModelCommand::AddItem(
+        "serialized item".to_string(),
+    ));
+    let serialized = serde_json::to_string(&command).unwrap();
+    assert_eq!(
+        r#"{"MyLockedDomainModel":{"AddItem":"serialized item"}}"#,
+        serialized
+    );
+    assert_eq!(
+        r#"{"MyLockedDomainModel":"GetAllItems"}"#,
+        serde_json::to_string(&AnyQuery::from(MyLockedDomainModelQuery::GetAllItems)).unwrap()
+    );
+}
+
+#[test]
+fn process_deserialized_command() {
+    let app_state_path = fresh_app_state_path("serializable_cqrs_tests");
+    let lifecycle = LifecycleImpl::new_instance(&AppConfigImpl::new(Some(
+        app_state_path.to_string_lossy().to_string(),
+    )))
+    .unwrap();
+
+    serde_json::from_str::<AnyCommand>(r#"{"MyLockedDomainModel":{"AddItem":"replayed item"}}"#)
+        .unwrap()
+        .process_with(&lifecycle)
+        .unwrap();
+    assert_eq!(
+        vec!["replayed item".to_string()],
+        lifecycle
+            .app_state
+            .my_locked_domain_model_lock
+            .lock
+            .blocking_read()
+            .get_items()
+    );
+}
@@ -0,0 +1,106 @@
+//! run with `cargo test --features tracing`
+#![cfg(feature = "tracing")]
+mod cqrs_lock_model_file;
+
+use std::sync::{Arc, Mutex};
+
+use generate_cqrs_api::{
+    tracing, FileAppStatePersister, FileAppStatePersisterError, InterceptedCall, Interceptor,
+    Interceptors,
+};
+use generate_cqrs_api_macro::generate_api;
+use tracing_subscriber::fmt::format::FmtSpan;
+
+include!("./mocks/app_config_mock.rs");
+include!("./mocks/rust_auto_opaque_mock.rs");
+include!("./mocks/app_state_path_mock.rs");
+
+pub struct LifecycleImpl {
+    app_state: AppStateImpl,
+    persister: FileAppStatePersister,
+    interceptors: Interceptors<Effect, ProcessingError>,
+}
+
+#[generate_api(
+    "tests/cqrs_lock_model_file/mod.rs",
+    generate_app_state,
+    serializable_cqrs,
+    interceptors,
+    default_lifecycle(app_config = AppConfigImpl, persister = FileAppStatePersister)
+)]
+impl Lifecycle for LifecycleImpl {
+    type Error = FileAppStatePersisterError;
+}
+
+struct RejectEmptyItems;
+
+impl Interceptor<Effect, ProcessingError> for RejectEmptyItems {
+    fn before(&self, call: &InterceptedCall) -> Result<(), ProcessingError> {
+        if call.arguments == "" {
+            return Err(ProcessingError::MyLockedDomainProcessingError(
+                MyLockedDomainProcessingError::ItemDoesNotExist,
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// collects the formatted trace
+#[derive(Clone, Default)]
+struct TraceWriter(Arc<Mutex<Vec<u8>>>);
+
+impl std::io::Write for TraceWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn trace_commands_and_queries() {
+    let app_state_path = fresh_app_state_path("tracing_tests");
+    let lifecycle = LifecycleImpl::new_instance(&AppConfigImpl::new(Some(
+        app_state_path.to_string_lossy().to_string(),
+    )))
+    .unwrap();
+    lifecycle.interceptors.register(RejectEmptyItems);
+    let trace = TraceWriter::default();
+    let writer = trace.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_writer(move || writer.clone())
+        .with_span_events(FmtSpan::CLOSE)
+        .with_ansi(false)
+        .finish();
+
+    tracing::subscriber::with_default(subscriber, || {
+        MyLockedDomainModelCommand::AddItem("item".to_string())
+            .process_with(&lifecycle)
+            .unwrap();
+        MyLockedDomainModelQuery::GetAllItems
+            .process_with(&lifecycle)
+            .unwrap();
+        // rejected by the interceptor
+        assert!(MyLockedDomainModelCommand::AddItem(String::new())
+            .process_with(&lifecycle)
+            .is_err());
+    });
+
+    let trace = String::from_utf8(trace.0.lock().unwrap().clone()).unwrap();
+    let lines = trace.lines().collect::<Vec<&str>>();
+    assert_eq!(4, lines.len(), "{trace}");
+    assert!(lines[0].contains(
+        r#"process{model="MyLockedDomainModel" kind="Command" variant="AddItem" arguments=AddItem("item") lock_wait_us="#
+    ));
+    assert!(lines[0].contains(" execution_us="));
+    assert!(lines[0].contains(" state_changed=true persistence_us="));
+    // queries don't lock the model's commands
+    assert!(lines[1].contains(r#"variant="GetAllItems" arguments=GetAllItems execution_us="#));
+    assert!(lines[1].contains(" state_changed=false}"));
+    assert!(lines[2].contains("ERROR"));
+    assert!(lines[2]
+        .contains("processing failed error=Error during processing: The item does not exist!"));
+    assert!(lines[3].contains(r#"arguments=AddItem("")}"#));
+}
@@ -0,0 +1,131 @@
+mod cqrs_lock_model_file;
+
+use generate_cqrs_api::{
+    FileAppStatePersister, FileAppStatePersisterError, UndoHistory, UndoableModelLock,
+};
+use generate_cqrs_api_macro::generate_api;
+
+include!("./mocks/app_config_mock.rs");
+include!("./mocks/rust_auto_opaque_mock.rs");
+include!("./mocks/app_state_path_mock.rs");
+
+pub struct LifecycleImpl {
+    app_state: AppStateImpl,
+    persister: FileAppStatePersister,
+    undo_history: UndoHistory<ModelSnapshot>,
+}
+
+#[generate_api(
+    "tests/cqrs_lock_model_file/mod.rs",
+    generate_app_state,
+    undo,
+    undo_limit = 2,
+    default_lifecycle(app_config = AppConfigImpl, persister = FileAppStatePersister)
+)]
+impl Lifecycle for LifecycleImpl {
+    type Error = FileAppStatePersisterError;
+}
+
+impl UndoableModelLock<MyLockedDomainModel> for MyLockedDomainModelLock {
+    type Effect = MyLockedDomainModelEffect;
+    fn undo_effects(&self) -> Vec<MyLockedDomainModelEffect> {
+        vec![MyLockedDomainModelEffect::RenderItems(self.clone())]
+    }
+}
+
+fn get_items(lifecycle: &LifecycleImpl) -> Vec<String> {
+    lifecycle
+        .app_state
+        .my_locked_domain_model_lock
+        .lock
+        .blocking_read()
+        .get_items()
+}
+
+#[test]
+fn undo_and_redo_commands() {
+    let app_state_path = fresh_app_state_path("undo_tests");
+    let app_config = AppConfigImpl::new(Some(app_state_path.to_string_lossy().to_string()));
+    let lifecycle = LifecycleImpl::new_instance(&app_config).unwrap();
+    for item in ["first item", "second item", "third item"] {
+        MyLockedDomainModelCommand::AddItem(item.to_string())
+            .process_with(&lifecycle)
+            .unwrap();
+    }
+    // queries don't change the state, so there is nothing to record
+    MyLockedDomainModelQuery::GetAllItems
+        .process_with(&lifecycle)
+        .unwrap();
+
+    let effects = lifecycle.undo_with().unwrap();
+    assert!(matches!(
+        effects.as_slice(),
+        [Effect::MyLockedDomainModelRenderItems(lock)]
+            if lock.lock.blocking_read().get_items() == vec!["first item", "second item"]
+    ));
+    // the undone state is persisted
+    assert!(std::fs::read_to_string(&app_state_path)
+        .unwrap()
+        .ends_with(r#"{"my_locked_domain_model_lock":{"items":["first item","second item"]}}"#));
+    lifecycle.undo_with().unwrap();
+    assert_eq!(vec!["first item"], get_items(&lifecycle));
+    // only the last 2 commands can be undone
+    assert!(lifecycle.undo_with().unwrap().is_empty());
+    assert_eq!(vec!["first item"], get_items(&lifecycle));
+
+    assert_eq!(1, lifecycle.redo_with().unwrap().len());
+    assert_eq!(vec!["first item", "second item"], get_items(&lifecycle));
+    // a new command can't be combined with the undone ones
+    MyLockedDomainModelCommand::AddItem("other item".to_string())
+        .process_with(&lifecycle)
+        .unwrap();
+    assert!(lifecycle.redo_with().unwrap().is_empty());
+    assert_eq!(
+        vec!["first item", "second item", "other item"],
+        get_items(&lifecycle)
+    );
+}
+#[test]
+fn undo_concurrent_commands_one_by_one() {
+    let app_state_path = fresh_app_state_path("undo_concurrently_tests");
+    let app_config = AppConfigImpl::new(Some(app_state_path.to_string_lossy().to_string()));
+    let lifecycle = LifecycleImpl::new_instance(&app_config).unwrap();
+    std::thread::scope(|scope| {
+        for thread in 0..8 {
+            let lifecycle = &lifecycle;
+            scope.spawn(move || {
+                for item in 0..10 {
+                    MyLockedDomainModelCommand::AddItem(format!("item {thread}-{item}"))
+                        .process_with(lifecycle)
+                        .unwrap();
+                }
+            });
+        }
+    });
+    // each snapshot was taken right before its command, without another command in between
+    for remaining in [79, 78] {
+        lifecycle.undo_with().unwrap();
+        assert_eq!(remaining, get_items(&lifecycle).len());
+    }
+
+    // undoing while commands are processed neither deadlocks nor loses a command
+    let undone = std::thread::scope(|scope| {
+        for thread in 0..4 {
+            let lifecycle = &lifecycle;
+            scope.spawn(move || {
+                for item in 0..10 {
+                    MyLockedDomainModelCommand::AddItem(format!("other item {thread}-{item}"))
+                        .process_with(lifecycle)
+                        .unwrap();
+                }
+            });
+        }
+        let undoing = scope.spawn(|| {
+            (0..20)
+                .filter(|_| !lifecycle.undo_with().unwrap().is_empty())
+                .count()
+        });
+        undoing.join().unwrap()
+    });
+    assert_eq!(78 + 40 - undone, get_items(&lifecycle).len());
+}
@@ -0,0 +1,77 @@
+use crate::*;
+use generate_cqrs_api_macro::{cqrs, cqrs_lock, cqrs_model};
+
+pub const MAX_ITEM_LENGTH: usize = 20;
+
+#[derive(Clone, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct MyValidatedModel {
+    items: Vec<String>,
+}
+
+#[cqrs_lock(model = MyValidatedModel)]
+#[derive(Debug, Clone, Default)]
+pub struct MyValidatedModelLock;
+
+#[allow(dead_code)]
+pub enum MyValidatedModelEffect {
+    RenderItems(MyValidatedModelLock),
+}
+
+#[allow(dead_code)]
+impl MyValidatedModel {
+    pub fn get_items(&self) -> Vec<String> {
+        self.items.clone()
+    }
+}
+
+pub(crate) fn no_tabs(item: &str) -> Result<(), String> {
+    match item.contains('\t') {
+        true => Err("contains a tab".to_string()),
+        false => Ok(()),
+    }
+}
+
+#[allow(dead_code)]
+impl MyValidatedModelLock {
+    #[cqrs]
+    pub(crate) fn add_item(
+        &self,
+        #[validate(length(min = 1, max = MAX_ITEM_LENGTH), with = no_tabs)] item: String,
+    ) -> Result<(bool, Vec<MyValidatedModelEffect>), MyValidatedProcessingError> {
+        self.lock.blocking_write().items.push(item);
+        Ok((
+            true,
+            vec![MyValidatedModelEffect::RenderItems(self.clone())],
+        ))
+    }
+    /// @param position: the item's position, starting with 1
+    #[cqrs]
+    pub(crate) fn remove_item(
+        &self,
+        #[validate(range(min = 1))] position: usize,
+    ) -> Result<(bool, Vec<MyValidatedModelEffect>), MyValidatedProcessingError> {
+        let items = &mut self.lock.blocking_write().items;
+        if position > items.len() {
+            return Err(MyValidatedProcessingError::ItemDoesNotExist(position));
+        }
+        items.remove(position - 1);
+        Ok((
+            true,
+            vec![MyValidatedModelEffect::RenderItems(self.clone())],
+        ))
+    }
+    pub(crate) fn get_all_items(
+        &self,
+    ) -> Result<Vec<MyValidatedModelEffect>, MyValidatedProcessingError> {
+        Ok(vec![MyValidatedModelEffect::RenderItems(self.clone())])
+    }
+}
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum MyValidatedProcessingError {
+    #[error("The item {0} does not exist!")]
+    ItemDoesNotExist(usize),
+}
+
+#[cqrs_model]
+impl CqrsModel for MyValidatedModel {}
@@ -0,0 +1,101 @@
+mod validation_model_file;
+
+use generate_cqrs_api::{FileAppStatePersister, FileAppStatePersisterError};
+use generate_cqrs_api_macro::generate_api;
+
+include!("./mocks/app_config_mock.rs");
+include!("./mocks/rust_auto_opaque_mock.rs");
+include!("./mocks/app_state_path_mock.rs");
+
+pub struct LifecycleImpl {
+    app_state: AppStateImpl,
+    persister: FileAppStatePersister,
+}
+
+#[generate_api(
+    "tests/validation_model_file/mod.rs",
+    generate_app_state,
+    preview,
+    default_lifecycle(app_config = AppConfigImpl, persister = FileAppStatePersister)
+)]
+impl Lifecycle for LifecycleImpl {
+    type Error = FileAppStatePersisterError;
+}
+
+fn get_items(lifecycle: &LifecycleImpl) -> Vec<String> {
+    lifecycle
+        .app_state
+        .my_validated_model_lock
+        .lock
+        .blocking_read()
+        .get_items()
+}
+
+/// @returns (argument, reason) of the rejected command
+fn get_invalid_argument(result: Result<Vec<Effect>, ProcessingError>) -> (String, String) {
+    let Err(ProcessingError::InvalidArgument {
+        command,
+        argument,
+        reason,
+    }) = result
+    else {
+        panic!("the command has to be rejected as invalid");
+    };
+    assert!(command.starts_with("MyValidatedModelCommand::"));
+    (argument, reason)
+}
+
+#[test]
+fn reject_invalid_arguments() {
+    let app_state_path = fresh_app_state_path("validation_tests");
+    let lifecycle = LifecycleImpl::new_instance(&AppConfigImpl::new(Some(
+        app_state_path.to_string_lossy().to_string(),
+    )))
+    .unwrap();
+
+    MyValidatedModelCommand::AddItem("item".to_string())
+        .process_with(&lifecycle)
+        .unwrap();
+    assert_eq!(
+        (
+            "item".to_string(),
+            "the length 0 is less than the minimum 1".to_string()
+        ),
+        get_invalid_argument(
+            MyValidatedModelCommand::AddItem(String::new()).process_with(&lifecycle)
+        )
+    );
+    assert_eq!(
+        (
+            "item".to_string(),
+            "the length 21 is greater than the maximum 20".to_string()
+        ),
+        get_invalid_argument(
+            MyValidatedModelCommand::AddItem("x".repeat(21)).process_with(&lifecycle)
+        )
+    );
+    assert_eq!(
+        ("item".to_string(), "contains a tab".to_string()),
+        get_invalid_argument(
+            MyValidatedModelCommand::AddItem("a\tb".to_string()).process_with(&lifecycle)
+        )
+    );
+    // removing position 0 would underflow without the validation
+    assert_eq!(
+        (
+            "position".to_string(),
+            "0 is less than the minimum 1".to_string()
+        ),
+        get_invalid_argument(MyValidatedModelCommand::RemoveItem(0).process_with(&lifecycle))
+    );
+    assert!(matches!(
+        MyValidatedModelCommand::RemoveItem(0).preview_with(&lifecycle),
+        Err(ProcessingError::InvalidArgument { .. })
+    ));
+    assert_eq!(vec!["item"], get_items(&lifecycle));
+
+    MyValidatedModelCommand::RemoveItem(1)
+        .process_with(&lifecycle)
+        .unwrap();
+    assert!(get_items(&lifecycle).is_empty());
+}